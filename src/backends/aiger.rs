@@ -0,0 +1,339 @@
+use crate::boolean_network::*;
+use crate::flowmap::label::TopologicalOrder;
+use crate::flowmap::*;
+use std::io::{self, Write};
+
+/// Writes the network as an ASCII AIGER file.
+///
+/// Nodes with two ancestors become AND gates. A node with one ancestor is
+/// emitted as the literal of its ancestor wherever it's used if its logic is an
+/// AND, i.e. a buffer, or as the opposite literal otherwise, i.e. an inverter.
+/// Nodes with cover logic can't be written. PIs become inputs, except for the
+/// outputs of latches, which become latches. POs become outputs, and constants
+/// become literal 0. Inputs, latches and outputs are written in node index
+/// order, and AND gates in topological order, with variables assigned in the
+/// same order.
+pub fn write_aiger<T: io::Write, Ni: 'static + NodeIndex + std::fmt::Debug>(
+    writer: T,
+    network: &FlowMapBooleanNetwork<Ni>,
+) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(writer);
+
+    let nodes = network.nodes().collect::<Vec<_>>();
+    let is_latch = |ni: Ni| network.node_value(ni).latch_next.is_some();
+
+    let inputs = nodes
+        .iter()
+        .copied()
+        .filter(|ni| {
+            let node_value = network.node_value(*ni);
+            node_value.is_pi && !node_value.is_constant && !is_latch(*ni)
+        })
+        .collect::<Vec<_>>();
+    let latches = nodes
+        .iter()
+        .copied()
+        .filter(|ni| is_latch(*ni))
+        .collect::<Vec<_>>();
+    let outputs = nodes
+        .iter()
+        .copied()
+        .filter(|ni| network.node_value(*ni).is_po)
+        .collect::<Vec<_>>();
+
+    // The literal which represents each node, or `None` if the node is
+    // neither a PI nor driven by logic
+    let mut literals = vec![None; network.node_count()];
+    for ni in &nodes {
+        if network.node_value(*ni).is_constant {
+            literals[ni.node_index()] = Some(0);
+        }
+    }
+    for (variable, ni) in inputs.iter().chain(&latches).enumerate() {
+        literals[ni.node_index()] = Some((variable + 1) * 2);
+    }
+
+    let literal_of = |literals: &[Option<usize>], ni: Ni| {
+        literals[ni.node_index()].ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "node {:?} is used, but is neither a PI nor driven by logic",
+                    ni
+                ),
+            )
+        })
+    };
+
+    let mut and_gates = vec![];
+    let mut topo = TopologicalOrder::new(network);
+    while let Some(ni) = topo.next(network) {
+        if network.node_value(ni).is_pi {
+            continue;
+        }
+
+        let logic = &network.node_value(ni).logic;
+        if let Some(NodeLogic::Cover(_)) = logic {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "node {:?} has cover logic, but AIGER only has inverters and AND gates",
+                    ni
+                ),
+            ));
+        }
+
+        match network.ancestors(ni) {
+            [] => {}
+            [ancestor] => {
+                let literal = literals[ancestor.node_index()];
+                literals[ni.node_index()] = if logic == &Some(NodeLogic::And) {
+                    literal
+                } else {
+                    literal.map(|l| l ^ 1)
+                };
+            }
+            [input0, input1] => {
+                let output = (inputs.len() + latches.len() + and_gates.len() + 1) * 2;
+                literals[ni.node_index()] = Some(output);
+                and_gates.push((output, *input0, *input1));
+            }
+            ancestors => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "node {:?} has {} ancestors, but AIGER only has inverters and AND \
+                         gates",
+                        ni,
+                        ancestors.len()
+                    ),
+                ))
+            }
+        }
+    }
+
+    let max_variable = inputs.len() + latches.len() + and_gates.len();
+    writeln!(
+        writer,
+        "aag {} {} {} {} {}",
+        max_variable,
+        inputs.len(),
+        latches.len(),
+        outputs.len(),
+        and_gates.len()
+    )?;
+
+    for input in &inputs {
+        writeln!(writer, "{}", literal_of(&literals, *input)?)?;
+    }
+
+    for latch in &latches {
+        let next = network.node_value(*latch).latch_next.unwrap();
+        writeln!(
+            writer,
+            "{} {}",
+            literal_of(&literals, *latch)?,
+            literal_of(&literals, next)?
+        )?;
+    }
+
+    for output in &outputs {
+        writeln!(writer, "{}", literal_of(&literals, *output)?)?;
+    }
+
+    for (output, input0, input1) in &and_gates {
+        writeln!(
+            writer,
+            "{} {} {}",
+            output,
+            literal_of(&literals, *input0)?,
+            literal_of(&literals, *input1)?
+        )?;
+    }
+
+    // The symbol of an input which is also an output is written for the
+    // output
+    for (position, input) in inputs.iter().enumerate() {
+        let node_value = network.node_value(*input);
+        if let (Some(symbol), false) = (&node_value.symbol, node_value.is_po) {
+            writeln!(writer, "i{} {}", position, symbol)?;
+        }
+    }
+
+    for (position, latch) in latches.iter().enumerate() {
+        if let Some(symbol) = &network.node_value(*latch).symbol {
+            writeln!(writer, "l{} {}", position, symbol)?;
+        }
+    }
+
+    for (position, output) in outputs.iter().enumerate() {
+        if let Some(symbol) = &network.node_value(*output).symbol {
+            writeln!(writer, "o{} {}", position, symbol)?;
+        }
+    }
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluate;
+    use crate::flowmap::{label, map};
+    use crate::frontends;
+
+    fn network_from_str(s: &str) -> frontends::aiger::AIG {
+        let reader = aiger::Reader::from_reader(s.as_bytes()).unwrap();
+        frontends::aiger::from_reader(reader).unwrap()
+    }
+
+    fn write_aiger_to_string(network: &frontends::aiger::AIG) -> String {
+        let mut aiger = vec![];
+        write_aiger(&mut aiger, network).unwrap();
+
+        String::from_utf8(aiger).unwrap()
+    }
+
+    /// Returns the value of every PO of the network for every combination of
+    /// its PIs.
+    fn po_values(network: frontends::aiger::AIG, num_pis: usize) -> Vec<Vec<bool>> {
+        let network = label::label_network(network, 2);
+        let luts = map::map(&network, 2, &*network).unwrap();
+
+        (0..1 << num_pis)
+            .map(|i| {
+                let pi_values = (0..num_pis)
+                    .map(|bit| i & (1 << bit) != 0)
+                    .collect::<Vec<_>>();
+                evaluate::evaluate_multi_output(&network, &luts, &pi_values)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn write_aiger_and_gate() {
+        let network = network_from_str("aag 3 2 0 1 1\n2\n4\n6\n6 2 4\ni0 x\ni1 y\no0 z\n");
+
+        assert_eq!(
+            write_aiger_to_string(&network),
+            "aag 3 2 0 1 1\n2\n4\n6\n6 2 4\ni0 x\ni1 y\no0 z\n"
+        );
+    }
+
+    #[test]
+    fn write_aiger_inverted_output() {
+        let network = network_from_str("aag 3 2 0 1 1\n2\n4\n7\n6 3 5\n");
+
+        assert_eq!(
+            write_aiger_to_string(&network),
+            "aag 3 2 0 1 1\n2\n4\n7\n6 3 5\n"
+        );
+    }
+
+    #[test]
+    fn write_aiger_half_adder_round_trip() {
+        let aiger = "aag 5 2 0 2 3\n2\n4\n10\n6\n6 2 4\n8 3 5\n10 7 9\n\
+                     i0 x\ni1 y\no0 s\no1 c\n";
+        let network = network_from_str(aiger);
+        let written = write_aiger_to_string(&network);
+        let round_tripped = network_from_str(&written);
+
+        assert_eq!(
+            round_tripped.node_count_by_category(),
+            network.node_count_by_category()
+        );
+        assert_eq!(po_values(round_tripped, 2), po_values(network, 2));
+        // The network doesn't record the original order of the outputs, so
+        // they're written in node index order
+        assert!(written.ends_with("i0 x\ni1 y\no0 c\no1 s\n"));
+    }
+
+    #[test]
+    fn write_aiger_buffer_round_trip() {
+        let network = network_from_str("aag 2 1 0 1 1\n2\n4\n4 2 2\n");
+        let written = write_aiger_to_string(&network);
+
+        assert_eq!(written, "aag 1 1 0 1 0\n2\n2\n");
+        assert_eq!(
+            po_values(network_from_str(&written), 1),
+            po_values(network, 1)
+        );
+    }
+
+    #[test]
+    fn write_aiger_and_with_true_round_trip() {
+        let network = network_from_str("aag 2 1 0 1 1\n2\n4\n4 1 2\n");
+        let written = write_aiger_to_string(&network);
+
+        // An AND with true is read as a buffer of the other input
+        assert_eq!(written, "aag 1 1 0 1 0\n2\n2\n");
+        assert_eq!(
+            po_values(network_from_str(&written), 1),
+            po_values(network, 1)
+        );
+    }
+
+    #[test]
+    fn write_aiger_constant_true_round_trip() {
+        let network = network_from_str("aag 2 0 0 1 1\n4\n4 1 1\n");
+        let written = write_aiger_to_string(&network);
+
+        assert_eq!(written, "aag 0 0 0 1 0\n1\n");
+        assert_eq!(
+            po_values(network_from_str(&written), 0),
+            po_values(network, 0)
+        );
+    }
+
+    #[test]
+    fn write_aiger_cover() {
+        let mut network = FlowMapBooleanNetwork::<usize>::new(1);
+        network.add_edge(From(0), To(1));
+        network.node_value_mut(0).is_pi = true;
+        network.node_value_mut(1).is_po = true;
+        network.node_value_mut(1).logic = Some(NodeLogic::Cover(Cover {
+            cubes: vec![vec![Some(false)]],
+            value: true,
+        }));
+
+        let error = write_aiger(io::sink(), &network).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn write_aiger_too_many_ancestors() {
+        let mut network = FlowMapBooleanNetwork::<usize>::new(3);
+        network.add_edge(From(0), To(3));
+        network.add_edge(From(1), To(3));
+        network.add_edge(From(2), To(3));
+        for pi in &[0, 1, 2] {
+            network.node_value_mut(*pi).is_pi = true;
+        }
+        network.node_value_mut(3).is_po = true;
+
+        let error = write_aiger(io::sink(), &network).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn write_aiger_latch() {
+        let aiger = "aag 7 2 1 2 4\n2\n4\n6 8\n6\n7\n8 4 10\n10 13 15\n12 2 6\n14 3 7\n\
+                     i0 enable\ni1 reset\nl0 q\no0 Q\no1 !Q\n";
+        let network = network_from_str(aiger);
+
+        let written = write_aiger_to_string(&network);
+        // AND gates are renumbered in topological order, so the latch's next
+        // state moves
+        assert_eq!(
+            written,
+            "aag 7 2 1 2 4\n2\n4\n6 14\n6\n7\n8 2 6\n10 3 7\n12 9 11\n14 4 12\n\
+             i0 enable\ni1 reset\nl0 Q\no0 Q\no1 !Q\n"
+        );
+
+        let round_tripped = network_from_str(&written);
+        assert_eq!(
+            round_tripped.node_value(aiger::Literal(6)).latch_next,
+            Some(aiger::Literal(14))
+        );
+    }
+}
@@ -0,0 +1,361 @@
+use super::rtlil::{clock_name, latches, ports, to_symbol_and_bit};
+use crate::boolean_network::*;
+use crate::flowmap::map::LUT;
+use crate::flowmap::*;
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+/// Returns the BLIF signal name for a symbol. A symbol with a bit index names
+/// one bit of a multi-bit signal, which becomes its own `name[bit]` signal.
+fn symbol_signal(symbol: &str) -> String {
+    let (name, bit) = to_symbol_and_bit(symbol);
+    if name.len() == symbol.len() {
+        name.to_string()
+    } else {
+        format!("{}[{}]", name, bit)
+    }
+}
+
+/// Writes the mapped network as a BLIF model.
+///
+/// PIs become `.inputs` and POs become `.outputs`, named by their symbols where
+/// they have one. Each LUT becomes a `.names` block listing the input
+/// combinations for which its truth table is true. Constants become `.names`
+/// blocks with no rows, which are always false. The output of a node which is
+/// both a PI and a PO is driven from its input by a buffer.
+///
+/// Any other signal is named after its node, as `n<index>`, or `po<index>` for
+/// a buffered output, with underscores prepended while a symbol has that name.
+///
+/// Each latch becomes a `.latch` clocked on the rising edge of the input named
+/// by `clock_name`, with the latch's reset value as its initial value, or 2
+/// (don't care) if the latch is uninitialized.
+pub fn write_blif<T: io::Write, Ni: 'static + NodeIndex>(
+    writer: T,
+    network: &FlowMapBooleanNetwork<Ni>,
+    luts: &[LUT<Ni>],
+) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(writer);
+
+    let clock = clock_name(&ports(network));
+
+    // A symbol can take any name, including one made up for a node, so made up
+    // names are prefixed with underscores until they don't clash with a symbol
+    // or the clock
+    let mut taken = network
+        .nodes()
+        .filter_map(|ni| network.node_value(ni).symbol.as_ref())
+        .map(|symbol| symbol_signal(symbol))
+        .collect::<HashSet<_>>();
+    taken.insert(clock.clone());
+    let unique = |mut name: String| {
+        while taken.contains(&name) {
+            name.insert(0, '_');
+        }
+        name
+    };
+
+    // The signal carrying the value of each node, which is also the name of
+    // the input for a PI
+    let signal = |ni: Ni| {
+        let node_value = network.node_value(ni);
+        // Constants have no signal of their own, and the symbol of a
+        // feedthrough belongs to its output
+        let is_feedthrough = node_value.is_pi && node_value.is_po;
+        let symbol_names_node = !node_value.is_constant && !is_feedthrough;
+        match &node_value.symbol {
+            Some(symbol) if symbol_names_node => symbol_signal(symbol),
+            _ => unique(format!("n{}", ni.node_index())),
+        }
+    };
+
+    // The name of the output for a PO, which is the node's own signal unless
+    // the output has to be buffered from an input
+    let is_buffered = |ni: Ni| {
+        let node_value = network.node_value(ni);
+        node_value.is_pi && !node_value.is_constant
+    };
+    let output_name = |ni: Ni| {
+        if !is_buffered(ni) {
+            signal(ni)
+        } else if let Some(symbol) = &network.node_value(ni).symbol {
+            symbol_signal(symbol)
+        } else {
+            unique(format!("po{}", ni.node_index()))
+        }
+    };
+
+    // The output of a latch is a PI of the combinational logic, but it's
+    // driven by its latch rather than by the outside world
+    let inputs = network
+        .nodes()
+        .filter(|ni| {
+            let node_value = network.node_value(*ni);
+            node_value.is_pi && !node_value.is_constant && node_value.latch_next.is_none()
+        })
+        .collect::<Vec<_>>();
+    let latches = latches(network);
+    let outputs = network
+        .nodes()
+        .filter(|ni| network.node_value(*ni).is_po)
+        .collect::<Vec<_>>();
+
+    writeln!(writer, ".model top")?;
+
+    write!(writer, ".inputs")?;
+    for ni in &inputs {
+        write!(writer, " {}", signal(*ni))?;
+    }
+    if !latches.is_empty() {
+        write!(writer, " {}", clock)?;
+    }
+    writeln!(writer)?;
+
+    write!(writer, ".outputs")?;
+    for ni in &outputs {
+        write!(writer, " {}", output_name(*ni))?;
+    }
+    writeln!(writer)?;
+
+    // Constants are only driven where they're used, by a LUT, as a PO or as
+    // the next state of a latch
+    let mut constants_written = HashSet::new();
+    let used = luts
+        .iter()
+        .flat_map(|lut| lut.inputs.iter())
+        .chain(outputs.iter())
+        .chain(latches.iter().map(|(_, next)| next));
+    for ni in used {
+        if network.node_value(*ni).is_constant && constants_written.insert(ni.node_index()) {
+            writeln!(writer, ".names {}", signal(*ni))?;
+        }
+    }
+
+    for ni in &outputs {
+        if is_buffered(*ni) {
+            writeln!(writer, ".names {} {}", signal(*ni), output_name(*ni))?;
+            writeln!(writer, "1 1")?;
+        }
+    }
+
+    for (ni, next) in &latches {
+        let init = match network.node_value(*ni).latch_reset {
+            LatchReset::Zero => 0,
+            LatchReset::One => 1,
+            LatchReset::NonDeterministic => 2,
+        };
+
+        writeln!(
+            writer,
+            ".latch {} {} re {} {}",
+            signal(*next),
+            signal(*ni),
+            clock,
+            init
+        )?;
+    }
+
+    for lut in luts {
+        write!(writer, ".names")?;
+        for input in &lut.inputs {
+            write!(writer, " {}", signal(*input))?;
+        }
+        writeln!(writer, " {}", signal(lut.output))?;
+
+        // Each true entry of the truth table is a row of the on-set, with the
+        // first input as the most significant bit of the entry's index
+        let num_inputs = lut.inputs.len();
+        for (i, value) in lut.truth_table.entries().enumerate() {
+            if value {
+                writeln!(writer, "{:0width$b} 1", i, width = num_inputs)?;
+            }
+        }
+    }
+
+    writeln!(writer, ".end")?;
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::write_mapped_aiger;
+
+    #[test]
+    fn write_blif_and_gate() {
+        let blif = write_mapped_aiger(
+            "aag 3 2 0 1 1\n2\n4\n6\n6 2 4\ni0 a\ni1 b\no0 y\n",
+            6,
+            |w, n, l| write_blif(w, n, l),
+        );
+        let lines = blif.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines[0], ".model top");
+        assert_eq!(lines[1], ".inputs a b");
+        assert_eq!(lines[2], ".outputs y");
+        assert!(lines[3] == ".names a b y" || lines[3] == ".names b a y");
+        assert_eq!(lines[4], "11 1");
+        assert_eq!(lines[5], ".end");
+    }
+
+    #[test]
+    fn write_blif_or_gate() {
+        let blif = write_mapped_aiger(
+            "aag 3 2 0 1 1\n2\n4\n7\n6 3 5\ni0 a\ni1 b\no0 y\n",
+            6,
+            |w, n, l| write_blif(w, n, l),
+        );
+        let lines = blif.lines().collect::<Vec<_>>();
+
+        assert!(lines.contains(&".names a b y") || lines.contains(&".names b a y"));
+        assert!(lines.contains(&"01 1"));
+        assert!(lines.contains(&"10 1"));
+        assert!(lines.contains(&"11 1"));
+        assert!(!lines.contains(&"00 1"));
+    }
+
+    #[test]
+    fn write_blif_multi_bit_symbols() {
+        let blif = write_mapped_aiger(
+            "aag 3 2 0 2 1\n2\n4\n6\n7\n6 2 4\ni0 a[0]\ni1 a[1]\no0 y[1]\no1 z\n",
+            6,
+            |w, n, l| write_blif(w, n, l),
+        );
+        let lines = blif.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines[1], ".inputs a[0] a[1]");
+        assert_eq!(lines[2], ".outputs y[1] z");
+        assert!(
+            lines.contains(&".names a[0] a[1] y[1]") || lines.contains(&".names a[1] a[0] y[1]")
+        );
+        assert!(lines.contains(&".names a[0] a[1] z") || lines.contains(&".names a[1] a[0] z"));
+    }
+
+    #[test]
+    fn write_blif_constant_true_output() {
+        let blif = write_mapped_aiger("aag 1 1 0 1 0\n2\n1\n", 6, |w, n, l| write_blif(w, n, l));
+
+        assert_eq!(
+            blif.lines().collect::<Vec<_>>(),
+            vec![
+                ".model top",
+                ".inputs n2",
+                ".outputs n1",
+                ".names n0",
+                ".names n0 n1",
+                "0 1",
+                ".end",
+            ]
+        );
+    }
+
+    #[test]
+    fn write_blif_latch() {
+        // A toggle flip-flop, whose output is the inverse of its next state
+        let blif = write_mapped_aiger("aag 1 0 1 1 0\n2 3 1\n3\nl0 q\n", 6, |w, n, l| {
+            write_blif(w, n, l)
+        });
+
+        assert_eq!(
+            blif.lines().collect::<Vec<_>>(),
+            vec![
+                ".model top",
+                ".inputs clk",
+                ".outputs n3",
+                ".latch n3 q re clk 1",
+                ".names q n3",
+                "0 1",
+                ".end",
+            ]
+        );
+    }
+
+    #[test]
+    fn write_blif_latch_constant_next() {
+        let blif = write_mapped_aiger("aag 2 1 1 1 0\n2\n4 0 4\n4\n", 6, |w, n, l| {
+            write_blif(w, n, l)
+        });
+
+        assert_eq!(
+            blif.lines().collect::<Vec<_>>(),
+            vec![
+                ".model top",
+                ".inputs n2 clk",
+                ".outputs po4",
+                ".names n0",
+                ".names n4 po4",
+                "1 1",
+                ".latch n0 n4 re clk 2",
+                ".end",
+            ]
+        );
+    }
+
+    #[test]
+    fn write_blif_symbol_clashes_with_node_name() {
+        // The input named n6 must not be confused with the signal of node 6
+        let blif = write_mapped_aiger(
+            "aag 5 3 0 1 2\n2\n4\n10\n8\n6 2 4\n8 6 10\ni0 n6\no0 y\n",
+            2,
+            |w, n, l| write_blif(w, n, l),
+        );
+
+        assert_eq!(
+            blif.lines().collect::<Vec<_>>(),
+            vec![
+                ".model top",
+                ".inputs n6 n4 n10",
+                ".outputs y",
+                ".names n6 n4 _n6",
+                "11 1",
+                ".names _n6 n10 y",
+                "11 1",
+                ".end",
+            ]
+        );
+    }
+
+    #[test]
+    fn write_blif_feedthrough() {
+        let mut network = FlowMapBooleanNetwork::<usize>::new(0);
+        network.node_value_mut(0).is_pi = true;
+        network.node_value_mut(0).is_po = true;
+        network.node_value_mut(0).symbol = Some("y".to_string());
+
+        let mut blif = vec![];
+        write_blif(&mut blif, &network, &[]).unwrap();
+
+        assert_eq!(
+            String::from_utf8(blif).unwrap().lines().collect::<Vec<_>>(),
+            vec![
+                ".model top",
+                ".inputs n0",
+                ".outputs y",
+                ".names n0 y",
+                "1 1",
+                ".end"
+            ]
+        );
+    }
+
+    #[test]
+    fn write_blif_latch_input_named_clk() {
+        let blif = write_mapped_aiger("aag 2 1 1 1 0\n2\n4 2\n4\ni0 clk\n", 6, |w, n, l| {
+            write_blif(w, n, l)
+        });
+
+        assert_eq!(
+            blif.lines().collect::<Vec<_>>(),
+            vec![
+                ".model top",
+                ".inputs clk clk_1",
+                ".outputs po4",
+                ".names n4 po4",
+                "1 1",
+                ".latch clk n4 re clk_1 0",
+                ".end",
+            ]
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use crate::boolean_network::*;
+use crate::flowmap::*;
+use std::io::{self, Write};
+
+/// Escapes a string for use within a double-quoted DOT string.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Writes the network as a Graphviz DOT digraph, for visualising the network
+/// and the state of the labelling pass.
+///
+/// Each node is labelled with its node index, symbol, label and flow. PIs are
+/// drawn as boxes and POs as double circles, with PIs which are also POs drawn
+/// as double boxes. Edges which currently carry flow are drawn in red.
+pub fn write_dot<T: io::Write, Ni: 'static + NodeIndex>(
+    writer: T,
+    network: &FlowMapBooleanNetwork<Ni>,
+) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(writer);
+
+    writeln!(writer, "digraph network {{")?;
+
+    for ni in network.nodes() {
+        let node_value = network.node_value(ni);
+
+        let mut label = format!("{}", ni.node_index());
+        if let Some(symbol) = &node_value.symbol {
+            label += &format!("\\n{}", escape(symbol));
+        }
+        if let Some(node_label) = node_value.label {
+            label += &format!("\\nlabel {}", node_label);
+        }
+        label += &format!("\\nflow {}", node_value.flow);
+
+        let shape = match (node_value.is_pi, node_value.is_po) {
+            (true, false) => ", shape=box",
+            (true, true) => ", shape=box, peripheries=2",
+            (false, true) => ", shape=doublecircle",
+            (false, false) => "",
+        };
+
+        writeln!(
+            writer,
+            "    n{} [label=\"{}\"{}];",
+            ni.node_index(),
+            label,
+            shape
+        )?;
+    }
+
+    for ni in network.nodes() {
+        for descendent in network.descendents(ni) {
+            let (flow, _) = *network.edge_value(From(ni), To(*descendent));
+            let color = if flow != 0 { " [color=red]" } else { "" };
+
+            writeln!(
+                writer,
+                "    n{} -> n{}{};",
+                ni.node_index(),
+                descendent.node_index(),
+                color
+            )?;
+        }
+    }
+
+    writeln!(writer, "}}")?;
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_dot_to_string(network: &FlowMapBooleanNetwork<usize>) -> String {
+        let mut dot = vec![];
+        write_dot(&mut dot, network).unwrap();
+
+        String::from_utf8(dot).unwrap()
+    }
+
+    #[test]
+    fn write_dot_and_gate() {
+        let mut network = FlowMapBooleanNetwork::<usize>::new(2);
+        network.add_edge(From(0), To(2));
+        network.add_edge(From(1), To(2));
+
+        network.node_value_mut(0).is_pi = true;
+        network.node_value_mut(0).label = Some(0);
+        network.node_value_mut(0).symbol = Some("a \"b\"".to_string());
+        network.node_value_mut(1).is_pi = true;
+        network.node_value_mut(1).is_po = true;
+        network.node_value_mut(2).is_po = true;
+        network.node_value_mut(2).flow = 1;
+        network.edge_value_mut(From(1), To(2)).0 = 1;
+
+        assert_eq!(
+            write_dot_to_string(&network),
+            r#"digraph network {
+    n0 [label="0\na \"b\"\nlabel 0\nflow 0", shape=box];
+    n1 [label="1\nflow 0", shape=box, peripheries=2];
+    n2 [label="2\nflow 1", shape=doublecircle];
+    n0 -> n2;
+    n1 -> n2 [color=red];
+}
+"#
+        );
+    }
+}
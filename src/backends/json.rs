@@ -0,0 +1,330 @@
+use super::rtlil::{clock_name, latches, ports};
+use crate::boolean_network::*;
+use crate::flowmap::map::LUT;
+use crate::flowmap::*;
+use std::io::{self, Write};
+
+/// Returns the string as a quoted JSON string.
+fn json_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted += "\\\"",
+            '\\' => quoted += "\\\\",
+            c if (c as u32) < 0x20 => quoted += &format!("\\u{:04x}", c as u32),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+/// Returns the JSON value of the bit carrying the value of a node. Nets 0 and 1
+/// are reserved by Yosys, so node N is net N + 2. Constants are the constant
+/// bit "0", since the only constant in an AIGER network is false.
+fn net<Ni: 'static + NodeIndex>(network: &FlowMapBooleanNetwork<Ni>, ni: Ni) -> String {
+    if network.node_value(ni).is_constant {
+        "\"0\"".to_string()
+    } else {
+        format!("{}", ni.node_index() + 2)
+    }
+}
+
+/// Writes the mapped network as a Yosys JSON netlist with a single module named
+/// `top`.
+///
+/// Ports are derived from the PIs and POs in the same way as by `write_rtlil`,
+/// with the bits of a symbol forming one port unless they have gaps, in which
+/// case every bit becomes its own `name[bit]` port. Each LUT becomes a `$lut`
+/// cell with the LUT's truth table, and the net carrying the value of node N
+/// is N + 2.
+///
+/// Each latch becomes a `$dff` cell clocked by the input named by `clock_name`,
+/// with the latch's reset value as the `init` attribute of the net it drives.
+pub fn write_json<T: io::Write, Ni: 'static + NodeIndex>(
+    writer: T,
+    network: &FlowMapBooleanNetwork<Ni>,
+    luts: &[LUT<Ni>],
+) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(writer);
+
+    let ports = ports(network);
+    let latches = latches(network);
+
+    // The net of the clock is the first after those of the nodes
+    let clk = network.node_count() + 2;
+
+    let mut port_lines = ports
+        .iter()
+        .map(|port| {
+            let bits = port
+                .bits
+                .iter()
+                .map(|ni| net(network, *ni))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            format!(
+                "        {}: {{ \"direction\": \"{}\", \"bits\": [ {} ] }}",
+                json_string(&port.name),
+                if port.is_input { "input" } else { "output" },
+                bits
+            )
+        })
+        .collect::<Vec<_>>();
+    if !latches.is_empty() {
+        port_lines.push(format!(
+            "        {}: {{ \"direction\": \"input\", \"bits\": [ {} ] }}",
+            json_string(&clock_name(&ports)),
+            clk
+        ));
+    }
+
+    writeln!(writer, "{{")?;
+    writeln!(writer, "  \"creator\": \"flowmap\",")?;
+    writeln!(writer, "  \"modules\": {{")?;
+    writeln!(writer, "    \"top\": {{")?;
+
+    writeln!(writer, "      \"ports\": {{")?;
+    if !port_lines.is_empty() {
+        writeln!(writer, "{}", port_lines.join(",\n"))?;
+    }
+    writeln!(writer, "      }},")?;
+
+    writeln!(writer, "      \"cells\": {{")?;
+    for (i, lut) in luts.iter().enumerate() {
+        let num_inputs = lut.inputs.len();
+        let k = lut.k_used as usize;
+        assert!(
+            k >= num_inputs,
+            "LUT generating node {} has {} inputs but uses a {}-input LUT",
+            lut.output.node_index(),
+            num_inputs,
+            k
+        );
+
+        // Bits are listed least significant first, which is the last input,
+        // and unused inputs are tied to 0
+        let a = lut
+            .inputs
+            .iter()
+            .rev()
+            .map(|input| net(network, *input))
+            .chain((num_inputs..k).map(|_| "\"0\"".to_string()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let separator = if i + 1 < luts.len() || !latches.is_empty() {
+            ","
+        } else {
+            ""
+        };
+
+        writeln!(writer, "        \"$lut${}\": {{", lut.output.node_index())?;
+        writeln!(writer, "          \"hide_name\": 1,")?;
+        writeln!(writer, "          \"type\": \"$lut\",")?;
+        writeln!(writer, "          \"parameters\": {{")?;
+        writeln!(writer, "            \"WIDTH\": \"{:032b}\",", k)?;
+        writeln!(
+            writer,
+            "            \"LUT\": \"{}\"",
+            lut.truth_table.to_lut_string(k)
+        )?;
+        writeln!(writer, "          }},")?;
+        writeln!(writer, "          \"attributes\": {{ }},")?;
+        writeln!(
+            writer,
+            "          \"port_directions\": {{ \"A\": \"input\", \"Y\": \"output\" }},"
+        )?;
+        writeln!(
+            writer,
+            "          \"connections\": {{ \"A\": [ {} ], \"Y\": [ {} ] }}",
+            a,
+            net(network, lut.output)
+        )?;
+        writeln!(writer, "        }}{}", separator)?;
+    }
+    for (i, (ni, next)) in latches.iter().enumerate() {
+        let separator = if i + 1 < latches.len() { "," } else { "" };
+
+        writeln!(writer, "        \"$dff${}\": {{", ni.node_index())?;
+        writeln!(writer, "          \"hide_name\": 1,")?;
+        writeln!(writer, "          \"type\": \"$dff\",")?;
+        writeln!(writer, "          \"parameters\": {{")?;
+        writeln!(writer, "            \"CLK_POLARITY\": \"1\",")?;
+        writeln!(writer, "            \"WIDTH\": \"{:032b}\"", 1)?;
+        writeln!(writer, "          }},")?;
+        writeln!(writer, "          \"attributes\": {{ }},")?;
+        writeln!(
+            writer,
+            "          \"port_directions\": {{ \"CLK\": \"input\", \"D\": \"input\", \"Q\": \"output\" }},"
+        )?;
+        writeln!(
+            writer,
+            "          \"connections\": {{ \"CLK\": [ {} ], \"D\": [ {} ], \"Q\": [ {} ] }}",
+            clk,
+            net(network, *next),
+            net(network, *ni)
+        )?;
+        writeln!(writer, "        }}{}", separator)?;
+    }
+    writeln!(writer, "      }},")?;
+
+    // Yosys reads the reset value of a flip-flop from the init attribute of
+    // the net it drives
+    let inits = latches
+        .iter()
+        .filter_map(|(ni, _)| {
+            let init = match network.node_value(*ni).latch_reset {
+                LatchReset::Zero => "0",
+                LatchReset::One => "1",
+                LatchReset::NonDeterministic => return None,
+            };
+
+            Some(format!(
+                "        \"$ni${}\": {{ \"hide_name\": 1, \"bits\": [ {} ], \"attributes\": {{ \"init\": \"{}\" }} }}",
+                ni.node_index(),
+                net(network, *ni),
+                init
+            ))
+        })
+        .collect::<Vec<_>>();
+    writeln!(writer, "      \"netnames\": {{")?;
+    if !inits.is_empty() {
+        writeln!(writer, "{}", inits.join(",\n"))?;
+    }
+    writeln!(writer, "      }}")?;
+
+    writeln!(writer, "    }}")?;
+    writeln!(writer, "  }}")?;
+    writeln!(writer, "}}")?;
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::write_mapped_aiger;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn json_string_escapes() {
+        assert_eq!(json_string("a\"b\\c\n"), "\"a\\\"b\\\\c\\u000a\"");
+    }
+
+    #[test]
+    fn write_json_and_gate() {
+        let json = write_mapped_aiger(
+            "aag 3 2 0 1 1\n2\n4\n6\n6 2 4\ni0 a\ni1 b\no0 y\n",
+            6,
+            |w, n, l| write_json(w, n, l),
+        );
+        let json: Value = serde_json::from_str(&json).unwrap();
+        let top = &json["modules"]["top"];
+
+        assert_eq!(
+            top["ports"],
+            json!({
+                "a": { "direction": "input", "bits": [4] },
+                "b": { "direction": "input", "bits": [6] },
+                "y": { "direction": "output", "bits": [8] },
+            })
+        );
+
+        let cell = &top["cells"]["$lut$6"];
+        assert_eq!(cell["type"], "$lut");
+        assert_eq!(
+            cell["parameters"]["WIDTH"],
+            "00000000000000000000000000000010"
+        );
+        assert_eq!(cell["parameters"]["LUT"], "1000");
+        assert_eq!(cell["connections"]["Y"], json!([8]));
+        let a = &cell["connections"]["A"];
+        assert!(*a == json!([4, 6]) || *a == json!([6, 4]));
+    }
+
+    #[test]
+    fn write_json_bus_and_padding() {
+        let json = write_mapped_aiger(
+            "aag 3 2 0 2 1\n2\n4\n6\n7\n6 2 4\ni0 x[1]\ni1 x[0]\no0 y[0]\no1 y[2]\n",
+            KSpec::new(&[4]),
+            |w, n, l| write_json(w, n, l),
+        );
+        let json: Value = serde_json::from_str(&json).unwrap();
+        let top = &json["modules"]["top"];
+
+        assert_eq!(top["ports"]["x"]["bits"], json!([6, 4]));
+        assert_eq!(top["ports"]["y[0]"]["bits"], json!([8]));
+        assert_eq!(top["ports"]["y[2]"]["bits"], json!([9]));
+
+        let cell = &top["cells"]["$lut$6"];
+        assert_eq!(cell["parameters"]["LUT"], "1000100010001000");
+        let a = cell["connections"]["A"].as_array().unwrap();
+        assert_eq!(a[2..], [json!("0"), json!("0")]);
+    }
+
+    #[test]
+    fn write_json_latch() {
+        // A toggle flip-flop, whose output is the inverse of its next state
+        let json = write_mapped_aiger("aag 1 0 1 1 0\n2 3 1\n3\n", 6, |w, n, l| {
+            write_json(w, n, l)
+        });
+        let json: Value = serde_json::from_str(&json).unwrap();
+        let top = &json["modules"]["top"];
+
+        assert_eq!(
+            top["ports"],
+            json!({
+                "output$3": { "direction": "output", "bits": [5] },
+                "clk": { "direction": "input", "bits": [6] },
+            })
+        );
+
+        let cell = &top["cells"]["$dff$2"];
+        assert_eq!(cell["type"], "$dff");
+        assert_eq!(cell["parameters"]["CLK_POLARITY"], "1");
+        assert_eq!(
+            cell["connections"],
+            json!({ "CLK": [6], "D": [5], "Q": [4] })
+        );
+        assert_eq!(top["cells"]["$lut$3"]["connections"]["A"][0], json!(4));
+        assert_eq!(top["netnames"]["$ni$2"]["bits"], json!([4]));
+        assert_eq!(top["netnames"]["$ni$2"]["attributes"]["init"], "1");
+    }
+
+    #[test]
+    fn write_json_constant_false_output() {
+        let json = write_mapped_aiger("aag 0 0 0 1 0\n0\no0 y\n", 6, |w, n, l| write_json(w, n, l));
+        let json: Value = serde_json::from_str(&json).unwrap();
+        let top = &json["modules"]["top"];
+
+        assert_eq!(
+            top["ports"],
+            json!({ "y": { "direction": "output", "bits": ["0"] } })
+        );
+        assert_eq!(top["cells"], json!({}));
+    }
+
+    #[test]
+    fn write_json_latch_input_named_clk() {
+        let json = write_mapped_aiger("aag 2 1 1 1 0\n2\n4 2\n4\ni0 clk\n", 6, |w, n, l| {
+            write_json(w, n, l)
+        });
+        let json: Value = serde_json::from_str(&json).unwrap();
+        let top = &json["modules"]["top"];
+
+        assert_eq!(
+            top["ports"],
+            json!({
+                "clk": { "direction": "input", "bits": [4] },
+                "output$4": { "direction": "output", "bits": [6] },
+                "clk_1": { "direction": "input", "bits": [8] },
+            })
+        );
+        assert_eq!(
+            top["cells"]["$dff$4"]["connections"],
+            json!({ "CLK": [8], "D": [4], "Q": [6] })
+        );
+    }
+}
@@ -1 +1,14 @@
+//! Writers for networks and their mappings.
+//!
+//! Every writer is buffered internally, so an unbuffered writer such as a
+//! `File` can be passed directly without a write for every line.
+//!
+//! A node which is both a PI and a PO only has one symbol. Every backend uses
+//! it to name the output, which is driven directly from the input.
+
+pub mod aiger;
+pub mod blif;
+pub mod dot;
+pub mod json;
 pub mod rtlil;
+pub mod verilog;
@@ -1,143 +1,366 @@
 use crate::boolean_network::*;
 use crate::flowmap::map::LUT;
 use crate::flowmap::*;
-use std::collections::HashSet;
-use std::io;
-
-fn to_symbol_and_bit(s: &str) -> (&str, u32) {
-    let mut symbol = s;
-    let mut bit = 0;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
 
+/// Splits a symbol of the form `name[bit]` into its name and bit index. Any
+/// other symbol, including one whose brackets don't hold an integer, is a
+/// plain name for bit 0.
+pub(crate) fn to_symbol_and_bit(s: &str) -> (&str, u32) {
     if let Some(open_square_index) = s.find('[') {
-        let (symbol2, rest) = s.split_at(open_square_index);
-        symbol = symbol2;
-
-        assert_eq!(
-            rest.chars().rev().next(),
-            Some(']'),
-            "symbol had open square bracket but did not end with a closing square bracket"
-        );
+        let (symbol, rest) = s.split_at(open_square_index);
 
         // The 'rest' slice includes the open square bracket, so skip one
-        // character forward to ignore it. Subtract 1 from the length to skip
-        // over the closing bracket, too.
-        let bit_str = &rest[1..rest.len() - 1];
-        bit = u32::from_str_radix(bit_str, 10).expect("symbol bit index was not an integer");
+        // character forward to ignore it, along with the closing bracket
+        let bit = rest
+            .strip_suffix(']')
+            .and_then(|rest| rest[1..].parse().ok());
+        if let Some(bit) = bit {
+            return (symbol, bit);
+        }
     }
 
-    (symbol, bit)
+    (s, 0)
 }
 
-pub fn write_rtlil<T: io::Write, Ni: 'static + NodeIndex>(
-    mut writer: T,
-    network: &FlowMapBooleanNetwork<Ni>,
-    luts: &[LUT<Ni>],
-    evaluate_lut: impl Fn(&LUT<Ni>) -> Vec<bool>,
-) -> io::Result<()> {
-    enum WireType {
-        Input,
-        Output,
+/// A port of a module, made up of the bits of a symbol.
+pub(crate) struct Port<Ni> {
+    /// The name of the port.
+    pub name: String,
+    /// Whether the port is an input, rather than an output.
+    pub is_input: bool,
+    /// The node carrying each bit of the port, least significant bit first.
+    pub bits: Vec<Ni>,
+}
+
+/// Returns the ports of a module implementing the network, in the order their
+/// symbols first appear.
+///
+/// PIs become inputs and POs become outputs, except that constants and the
+/// outputs of latches are never inputs, since they aren't driven by the outside
+/// world. A constant which is a PO is still an output. The bits of a symbol
+/// form one port unless they have gaps, in which case every bit becomes its own
+/// `name[bit]` port. Nodes without a symbol become ports named `input$i` or
+/// `output$i`, where `i` is the position of the node in the network, as does
+/// the input of a node which is both a PI and a PO.
+///
+/// An input and an output with the same symbol are separate ports. A port
+/// whose name is taken by an earlier port is renamed to `name$N`, for the
+/// smallest N which makes it unique.
+pub(crate) fn ports<Ni: 'static + NodeIndex>(network: &FlowMapBooleanNetwork<Ni>) -> Vec<Port<Ni>> {
+    // Each symbol with the bit index of each of its bits, in the order the
+    // symbols first appear
+    let mut symbols: Vec<Port<(u32, Ni)>> = vec![];
+    for (i, ni) in network.nodes().enumerate() {
+        let node_value = network.node_value(ni);
+        let is_input =
+            node_value.is_pi && !node_value.is_constant && node_value.latch_next.is_none();
+        let directions = [(true, is_input), (false, node_value.is_po)];
+        for (is_input, _) in directions.iter().filter(|(_, is_port)| *is_port) {
+            let symbol = if node_value.is_po && *is_input {
+                None
+            } else {
+                node_value.symbol.as_ref()
+            };
+
+            let (name, bit) = match symbol {
+                Some(symbol) => {
+                    let (name, bit) = to_symbol_and_bit(symbol);
+                    (name.to_string(), bit)
+                }
+                None => {
+                    let direction = if *is_input { "input" } else { "output" };
+                    (format!("{}${}", direction, i), 0)
+                }
+            };
+
+            let port = symbols
+                .iter_mut()
+                .find(|symbol| symbol.name == name && symbol.is_input == *is_input);
+            match port {
+                Some(symbol) => symbol.bits.push((bit, ni)),
+                None => symbols.push(Port {
+                    name,
+                    is_input: *is_input,
+                    bits: vec![(bit, ni)],
+                }),
+            }
+        }
     }
 
-    let wires = (0..network.node_count())
-        .map(|ni| {
-            let ni = Ni::from_node_index(ni);
-            (ni, network.node_value(ni))
-        })
-        .enumerate()
-        .filter_map(|(i, (ni, nv))| {
-            // HACK HACK HACK
-            if ni.node_index() <= 1 {
-                return None;
+    let mut ports = vec![];
+    for Port {
+        name,
+        is_input,
+        mut bits,
+    } in symbols
+    {
+        // Bits may appear in any order in the symbol table
+        bits.sort_by_key(|(bit, _)| *bit);
+
+        let gapped = bits
+            .iter()
+            .enumerate()
+            .any(|(expected_bit, (bit, _))| *bit as usize != expected_bit);
+        if gapped {
+            for (bit, ni) in bits {
+                ports.push(Port {
+                    name: format!("{}[{}]", name, bit),
+                    is_input,
+                    bits: vec![ni],
+                });
             }
+        } else {
+            ports.push(Port {
+                name,
+                is_input,
+                bits: bits.into_iter().map(|(_, ni)| ni).collect(),
+            });
+        }
+    }
 
-            // TODO: This will require tweaking when latches are added
-            let wire_type = if nv.is_pi {
-                Some(WireType::Input)
-            } else if nv.is_po {
-                Some(WireType::Output)
-            } else {
-                None
-            };
+    // An input and an output can have the same symbol, and a symbol can name
+    // the same bit twice, so rename every port whose name is already taken
+    let mut names = HashSet::new();
+    for port in &mut ports {
+        let name = port.name.clone();
+        let mut n = 0;
+        while !names.insert(port.name.clone()) {
+            n += 1;
+            port.name = format!("{}${}", name, n);
+        }
+    }
 
-            if let Some(wire_type) = wire_type {
-                let ident = if let Some(symbol) = &nv.symbol {
-                    let (symbol, bit) = to_symbol_and_bit(&symbol);
+    ports
+}
 
-                    (symbol.to_string(), bit)
-                } else {
-                    let wire_type_str = match wire_type {
-                        WireType::Input => "input",
-                        WireType::Output => "output",
-                    };
+/// Returns the output and next state of every latch in the network, in node
+/// index order.
+pub(crate) fn latches<Ni: 'static + NodeIndex>(
+    network: &FlowMapBooleanNetwork<Ni>,
+) -> Vec<(Ni, Ni)> {
+    network
+        .nodes()
+        .filter_map(|ni| network.node_value(ni).latch_next.map(|next| (ni, next)))
+        .collect()
+}
 
-                    (format!("{}${}", wire_type_str, i), 0)
-                };
+/// Returns the name of the input which clocks every latch, since AIGER latches
+/// have no clock of their own. This is `clk`, unless a port already has that
+/// name, in which case it's `clk_N` for the smallest N which no port has.
+pub(crate) fn clock_name<Ni>(ports: &[Port<Ni>]) -> String {
+    let is_taken = |name: &str| ports.iter().any(|port| port.name == name);
 
-                Some((ni, ident, wire_type))
-            } else {
-                None
+    let mut name = "clk".to_string();
+    let mut n = 0;
+    while is_taken(&name) {
+        n += 1;
+        name = format!("clk_{}", n);
+    }
+
+    name
+}
+
+/// An error found while validating RTLIL output.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum RtlilError {
+    /// A wire was referenced in a connection before it was declared.
+    UndeclaredWire(String),
+    /// A wire was declared more than once.
+    DuplicateWire(String),
+}
+
+/// Checks that every wire referenced by a `connect` statement in the RTLIL
+/// module has already been declared by a `wire` statement, and that no wire is
+/// declared more than once.
+///
+/// Within a cell, the first signal of a `connect` statement names a port of
+/// the cell rather than a wire, so it is not checked.
+pub fn validate_rtlil_connections(module_text: &str) -> Result<(), RtlilError> {
+    let mut declared = HashSet::new();
+    let mut in_cell = false;
+
+    for line in module_text.lines() {
+        let mut tokens = line.split_whitespace();
+
+        match tokens.next() {
+            Some("wire") => {
+                if let Some(name) = tokens.last() {
+                    if !declared.insert(name.to_string()) {
+                        return Err(RtlilError::DuplicateWire(name.to_string()));
+                    }
+                }
             }
-        })
-        .collect::<Vec<_>>();
+            Some("cell") => in_cell = true,
+            Some("end") => in_cell = false,
+            Some("connect") => {
+                if in_cell {
+                    tokens.next();
+                }
 
-    writeln!(writer, "module \\top")?;
+                // Only identifiers name wires, everything else is a bit index
+                // or part of a concatenation
+                let undeclared = tokens
+                    .filter(|token| token.starts_with('\\') || token.starts_with('$'))
+                    .find(|wire| !declared.contains(*wire));
 
-    for lut in luts {
-        writeln!(writer, "  wire width 1 $ni${}", lut.output.node_index())?;
+                if let Some(wire) = undeclared {
+                    return Err(RtlilError::UndeclaredWire(wire.to_string()));
+                }
+            }
+            _ => {}
+        }
     }
 
-    let mut wires_written = HashSet::new();
-    for (i, (_, (symbol, _), wire_type)) in wires.iter().enumerate() {
-        if !wires_written.insert(symbol) {
-            continue;
+    Ok(())
+}
+
+/// Writes the mapped network as an RTLIL module named `top`.
+///
+/// The ports of the module are found by `ports`, and are numbered in the order
+/// they're returned. Each latch becomes a `$dff` cell clocked by the input
+/// named by `clock_name`, with the latch's reset value as the `init` attribute
+/// of its output wire.
+pub fn write_rtlil<T: io::Write, Ni: 'static + NodeIndex>(
+    writer: T,
+    network: &FlowMapBooleanNetwork<Ni>,
+    luts: &[LUT<Ni>],
+) -> io::Result<()> {
+    write_rtlil_named(writer, network, luts, "top")
+}
+
+/// Writes the mapped network as an RTLIL module named `module_name`, so that
+/// several mapped networks can be loaded into the same design.
+///
+/// The module name must be a valid RTLIL identifier without the leading
+/// backslash, i.e. it must not be empty or contain whitespace.
+pub fn write_rtlil_named<T: io::Write, Ni: 'static + NodeIndex>(
+    writer: T,
+    network: &FlowMapBooleanNetwork<Ni>,
+    luts: &[LUT<Ni>],
+    module_name: &str,
+) -> io::Result<()> {
+    assert!(
+        !module_name.is_empty() && !module_name.contains(char::is_whitespace),
+        "module name {:?} is not a valid RTLIL identifier",
+        module_name
+    );
+
+    let mut writer = io::BufWriter::new(writer);
+
+    let ports = ports(network);
+    let clock = clock_name(&ports);
+
+    writeln!(writer, "module \\{}", module_name)?;
+
+    // The internal wire for a node is shared between the LUT which generates
+    // it and any port it's connected to, so only declare it once
+    let mut ni_wires_written = HashSet::new();
+
+    for lut in luts {
+        let ni = lut.output.node_index();
+        if ni_wires_written.insert(ni) {
+            writeln!(writer, "  wire width 1 $ni${}", ni)?;
         }
+    }
 
-        let components = wires
-            .iter()
-            .filter(|(_, (symbol2, _), _)| symbol2 == symbol);
+    // The output of each latch is driven by its $dff cell, and its value on
+    // reset is given by the wire's init attribute
+    let latches = latches(network);
+    for (ni, _) in &latches {
+        if ni_wires_written.insert(ni.node_index()) {
+            match network.node_value(*ni).latch_reset {
+                LatchReset::Zero => writeln!(writer, "  attribute \\init 1'0")?,
+                LatchReset::One => writeln!(writer, "  attribute \\init 1'1")?,
+                LatchReset::NonDeterministic => {}
+            }
+            writeln!(writer, "  wire width 1 $ni${}", ni.node_index())?;
+        }
+    }
 
-        let max_bit = components
-            .clone()
-            .map(|(_, (_, bit), _)| bit)
-            .max()
-            .unwrap();
-        let width = max_bit + 1;
+    // Constants are never inputs, so any constant used as an input to a LUT or
+    // as the next state of a latch must be driven here instead. The only
+    // constant in an AIGER network is literal 0, which is false.
+    let lut_inputs = luts.iter().flat_map(|lut| lut.inputs.iter());
+    let latch_nexts = latches.iter().map(|(_, next)| next);
+    for input in lut_inputs.chain(latch_nexts) {
+        let ni = input.node_index();
+        if network.node_value(*input).is_constant && ni_wires_written.insert(ni) {
+            writeln!(writer, "  wire width 1 $ni${}", ni)?;
+            writeln!(writer, "  connect $ni${} 1'0", ni)?;
+        }
+    }
 
-        let wire_type_str = match wire_type {
-            WireType::Input => "input",
-            WireType::Output => "output",
-        };
+    // The input port and bit carrying each PI, so that a PO which is also a PI
+    // can be connected directly to its input
+    let input_bits = ports
+        .iter()
+        .filter(|port| port.is_input)
+        .flat_map(|port| {
+            port.bits
+                .iter()
+                .enumerate()
+                .map(move |(bit, ni)| (ni.node_index(), (&port.name, bit)))
+        })
+        .collect::<HashMap<_, _>>();
 
+    let mut feedthroughs = vec![];
+    for (i, port) in ports.iter().enumerate() {
+        let direction = if port.is_input { "input" } else { "output" };
         writeln!(
             writer,
             "  wire width {} {} {} \\{}",
-            width, wire_type_str, i, symbol
+            port.bits.len(),
+            direction,
+            i,
+            port.name
         )?;
 
-        for (ni, (_, bit), _) in components {
+        for (bit, ni) in port.bits.iter().enumerate() {
+            let is_constant = network.node_value(*ni).is_constant;
             let ni = ni.node_index();
-            match wire_type {
-                WireType::Input => {
+            if port.is_input {
+                if ni_wires_written.insert(ni) {
                     writeln!(writer, "  wire width 1 $ni${}", ni)?;
-                    writeln!(writer, "  connect $ni${} \\{} [{}]", ni, symbol, bit)?;
-                }
-                WireType::Output => {
-                    writeln!(writer, "  connect \\{} [{}] $ni${}", symbol, bit, ni)?;
                 }
+                writeln!(writer, "  connect $ni${} \\{} [{}]", ni, port.name, bit)?;
+            } else if is_constant {
+                writeln!(writer, "  connect \\{} [{}] 1'0", port.name, bit)?;
+            } else if let Some((input_name, input_bit)) = input_bits.get(&ni) {
+                // The output is driven directly by an input, which may not
+                // have been declared yet
+                feedthroughs.push((&port.name, bit, input_name, input_bit));
+            } else {
+                writeln!(writer, "  connect \\{} [{}] $ni${}", port.name, bit, ni)?;
             }
         }
     }
 
+    for (name, bit, input_name, input_bit) in feedthroughs {
+        writeln!(
+            writer,
+            "  connect \\{} [{}] \\{} [{}]",
+            name, bit, input_name, input_bit
+        )?;
+    }
+
+    if !latches.is_empty() {
+        writeln!(writer, "  wire width 1 input {} \\{}", ports.len(), clock)?;
+    }
+
     for lut in luts {
         let output_ni = lut.output.node_index();
-        let k = lut.inputs.len();
-        let output_bitstring = evaluate_lut(lut)
-            .iter()
-            .rev()
-            .map(|bit| if *bit { '1' } else { '0' })
-            .collect::<String>();
-        assert_eq!(output_bitstring.len(), 1 << k);
+        let num_inputs = lut.inputs.len();
+        let k = lut.k_used as usize;
+        assert!(
+            k >= num_inputs,
+            "LUT generating node {} has {} inputs but uses a {}-input LUT",
+            output_ni,
+            num_inputs,
+            k
+        );
+        let output_bitstring = lut.truth_table.to_lut_string(k);
 
         writeln!(writer, "  cell $lut $lut${}", output_ni)?;
         writeln!(writer, "    parameter \\WIDTH {}", k)?;
@@ -149,6 +372,9 @@ pub fn write_rtlil<T: io::Write, Ni: 'static + NodeIndex>(
         )?;
         writeln!(writer, "    connect \\Y $ni${}", output_ni)?;
         write!(writer, "    connect \\A {{")?;
+        for _ in num_inputs..k {
+            write!(writer, " 1'0")?;
+        }
         for input in &lut.inputs {
             write!(writer, " $ni${}", input.node_index())?;
         }
@@ -156,14 +382,44 @@ pub fn write_rtlil<T: io::Write, Ni: 'static + NodeIndex>(
         writeln!(writer, "  end")?;
     }
 
+    for (ni, next) in &latches {
+        let ni = ni.node_index();
+        writeln!(writer, "  cell $dff $dff${}", ni)?;
+        writeln!(writer, "    parameter \\WIDTH 1")?;
+        writeln!(writer, "    parameter \\CLK_POLARITY 1'1")?;
+        writeln!(writer, "    connect \\CLK \\{}", clock)?;
+        writeln!(writer, "    connect \\D $ni${}", next.node_index())?;
+        writeln!(writer, "    connect \\Q $ni${}", ni)?;
+        writeln!(writer, "  end")?;
+    }
+
     writeln!(writer, "end")?;
 
-    Ok(())
+    writer.flush()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::frontends;
+    use crate::test_utils::map_aiger;
+    use crate::truth_table::TruthTable;
+
+    /// Runs the full flow on an AIGER file, returning the AIGER header, the
+    /// number of LUTs generated and the RTLIL output.
+    fn aiger_to_rtlil(aiger: &str, k: impl Into<KSpec>) -> (aiger::Header, usize, String) {
+        let header = frontends::aiger::reader_from_any(aiger.as_bytes())
+            .unwrap()
+            .header();
+        let (network, luts) = map_aiger(aiger, k);
+
+        let mut rtlil = vec![];
+        write_rtlil(&mut rtlil, &network, &luts).unwrap();
+        let rtlil = String::from_utf8(rtlil).unwrap();
+        assert_eq!(validate_rtlil_connections(&rtlil), Ok(()));
+
+        (header, luts.len(), rtlil)
+    }
 
     #[test]
     fn test_to_symbol_and_bit() {
@@ -181,5 +437,620 @@ mod tests {
             to_symbol_and_bit("my_special_symbol[5]"),
             ("my_special_symbol", 5)
         );
+
+        // Brackets which don't hold an integer are part of the name
+        assert_eq!(to_symbol_and_bit("a[b]"), ("a[b]", 0));
+        assert_eq!(to_symbol_and_bit("a[-1]"), ("a[-1]", 0));
+        assert_eq!(to_symbol_and_bit("a["), ("a[", 0));
+        assert_eq!(to_symbol_and_bit("a[1]b"), ("a[1]b", 0));
+    }
+
+    #[test]
+    fn write_rtlil_non_integer_bit() {
+        let (_, _, rtlil) = aiger_to_rtlil("aag 1 1 0 1 0\n2\n3\ni0 a[b]\no0 y\n", 2);
+
+        assert!(rtlil
+            .lines()
+            .any(|line| line == "  connect $ni$2 \\a[b] [0]"));
+    }
+
+    #[test]
+    fn write_rtlil_feedthrough() {
+        let mut network = FlowMapBooleanNetwork::<usize>::new(0);
+        network.node_value_mut(0).is_pi = true;
+        network.node_value_mut(0).is_po = true;
+
+        let mut rtlil = vec![];
+        write_rtlil(&mut rtlil, &network, &[]).unwrap();
+
+        let rtlil = String::from_utf8(rtlil).unwrap();
+        assert_eq!(
+            rtlil.lines().collect::<Vec<_>>(),
+            vec![
+                "module \\top",
+                "  wire width 1 input 0 \\input$0",
+                "  wire width 1 $ni$0",
+                "  connect $ni$0 \\input$0 [0]",
+                "  wire width 1 output 1 \\output$0",
+                "  connect \\output$0 [0] \\input$0 [0]",
+                "end",
+            ]
+        );
+    }
+
+    #[test]
+    fn write_rtlil_feedthrough_mapped() {
+        // Input 2 is both an input and an output, alongside an AND gate
+        let (_, num_luts, rtlil) = aiger_to_rtlil("aag 3 2 0 2 1\n2\n4\n2\n6\n6 2 4\n", 2);
+
+        assert_eq!(num_luts, 1);
+        assert_rtlil(
+            &rtlil,
+            7,
+            &[
+                "  connect $ni$2 \\input$2 [0]",
+                "  connect \\output$2 [0] \\input$2 [0]",
+                "  cell $lut $lut$6",
+            ],
+        );
+        assert!(lut_inputs_are(&rtlil, 6, &[2, 4]));
+    }
+
+    #[test]
+    fn write_rtlil_low_node_indices() {
+        // Only nodes marked as constants are skipped, so a network which isn't
+        // from AIGER can use nodes 0 and 1 as real PIs
+        let mut network = FlowMapBooleanNetwork::<usize>::new(2);
+        network.add_edge(From(0), To(2));
+        network.add_edge(From(1), To(2));
+        network.node_value_mut(0).is_pi = true;
+        network.node_value_mut(1).is_pi = true;
+        network.node_value_mut(2).is_po = true;
+
+        let luts = [LUT {
+            output: 2,
+            inputs: vec![0, 1],
+            contains: [2].iter().copied().collect(),
+            truth_table: TruthTable::from_entries(&[false, false, false, true]),
+            depth: 1,
+            k_used: 2,
+        }];
+
+        let mut rtlil = vec![];
+        write_rtlil(&mut rtlil, &network, &luts).unwrap();
+        let rtlil = String::from_utf8(rtlil).unwrap();
+        assert_eq!(validate_rtlil_connections(&rtlil), Ok(()));
+
+        assert_rtlil(
+            &rtlil,
+            6,
+            &[
+                "  wire width 1 input 0 \\input$0",
+                "  connect $ni$0 \\input$0 [0]",
+                "  wire width 1 input 1 \\input$1",
+                "  connect $ni$1 \\input$1 [0]",
+                "    connect \\A { $ni$0 $ni$1 }",
+            ],
+        );
+    }
+
+    #[test]
+    fn write_rtlil_named_module() {
+        let mut network = FlowMapBooleanNetwork::<usize>::new(0);
+        network.node_value_mut(0).is_pi = true;
+        network.node_value_mut(0).is_po = true;
+
+        let mut rtlil = vec![];
+        write_rtlil_named(&mut rtlil, &network, &[], "adder$1").unwrap();
+
+        let rtlil = String::from_utf8(rtlil).unwrap();
+        assert_eq!(rtlil.lines().next(), Some("module \\adder$1"));
+        assert_eq!(rtlil.lines().last(), Some("end"));
+    }
+
+    #[test]
+    #[should_panic(expected = "module name \"my module\" is not a valid RTLIL identifier")]
+    fn write_rtlil_named_invalid() {
+        let network = FlowMapBooleanNetwork::<usize>::new(0);
+        write_rtlil_named(vec![], &network, &[], "my module").unwrap();
+    }
+
+    #[test]
+    fn write_rtlil_half_adder_structure() {
+        let (header, num_luts, rtlil) = aiger_to_rtlil(
+            "aag 5 2 0 2 3\n2\n4\n10\n6\n6 2 4\n8 3 5\n10 7 9\n\
+             i0 x\ni1 y\no0 s\no1 c\n",
+            6,
+        );
+        let lines = rtlil.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.iter().filter(|l| **l == "module \\top").count(), 1);
+        assert_eq!(lines.iter().filter(|l| **l == "end").count(), 1);
+
+        let wire_declarations = lines
+            .iter()
+            .filter(|l| l.starts_with("  wire width "))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            wire_declarations
+                .iter()
+                .filter(|l| l.contains(" input "))
+                .count(),
+            header.i
+        );
+        assert_eq!(
+            wire_declarations
+                .iter()
+                .filter(|l| l.contains(" output "))
+                .count(),
+            header.o
+        );
+
+        assert_eq!(
+            lines
+                .iter()
+                .filter(|l| l.starts_with("  cell $lut "))
+                .count(),
+            num_luts
+        );
+
+        let mut declared = HashSet::new();
+        for line in &lines {
+            if let Some(wire) = line.strip_prefix("  wire width 1 ") {
+                declared.insert(wire);
+            } else if let Some(inputs) = line.strip_prefix("    connect \\A {") {
+                for input in inputs.trim_end_matches('}').split_whitespace() {
+                    assert!(
+                        declared.contains(input),
+                        "LUT input {} used before being declared",
+                        input
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn write_rtlil_lut_output_is_po() {
+        let mut network = FlowMapBooleanNetwork::<usize>::new(1);
+        network.add_edge(From(0), To(1));
+        network.node_value_mut(0).is_pi = true;
+        network.node_value_mut(1).is_po = true;
+
+        let luts = [LUT {
+            output: 1,
+            inputs: vec![0],
+            contains: [1].iter().copied().collect(),
+            truth_table: TruthTable::from_entries(&[true, false]),
+            depth: 1,
+            k_used: 1,
+        }];
+
+        let mut rtlil = vec![];
+        write_rtlil(&mut rtlil, &network, &luts).unwrap();
+
+        let rtlil = String::from_utf8(rtlil).unwrap();
+        assert_eq!(
+            rtlil.lines().collect::<Vec<_>>(),
+            vec![
+                "module \\top",
+                "  wire width 1 $ni$1",
+                "  wire width 1 input 0 \\input$0",
+                "  wire width 1 $ni$0",
+                "  connect $ni$0 \\input$0 [0]",
+                "  wire width 1 output 1 \\output$1",
+                "  connect \\output$1 [0] $ni$1",
+                "  cell $lut $lut$1",
+                "    parameter \\WIDTH 1",
+                "    parameter \\LUT 2'01",
+                "    connect \\Y $ni$1",
+                "    connect \\A { $ni$0 }",
+                "  end",
+                "end",
+            ]
+        );
+    }
+
+    /// Asserts that the RTLIL contains the provided lines, and that it
+    /// declares the provided number of wires.
+    fn assert_rtlil(rtlil: &str, num_wires: usize, expected_lines: &[&str]) {
+        let lines = rtlil.lines().collect::<Vec<_>>();
+
+        assert_eq!(
+            lines
+                .iter()
+                .filter(|l| l.starts_with("  wire width "))
+                .count(),
+            num_wires
+        );
+
+        for expected_line in expected_lines {
+            assert!(
+                lines.contains(expected_line),
+                "line {:?} not in RTLIL:\n{}",
+                expected_line,
+                rtlil
+            );
+        }
+    }
+
+    /// Returns `true` if the RTLIL connects the inputs of the LUT generating
+    /// node `ni` to the provided nodes, in any order.
+    fn lut_inputs_are(rtlil: &str, ni: usize, inputs: &[usize]) -> bool {
+        let lines = rtlil.lines().collect::<Vec<_>>();
+        let cell = lines
+            .iter()
+            .position(|l| *l == format!("  cell $lut $lut${}", ni))
+            .expect("LUT cell to exist");
+        let connect = lines[cell..]
+            .iter()
+            .find_map(|l| l.strip_prefix("    connect \\A { "))
+            .expect("LUT cell to have inputs");
+
+        let mut actual = connect
+            .trim_end_matches(" }")
+            .split(' ')
+            .map(|wire| wire.trim_start_matches("$ni$").parse::<usize>().unwrap())
+            .collect::<Vec<_>>();
+        actual.sort();
+
+        actual == inputs
+    }
+
+    #[test]
+    fn write_rtlil_and_gate() {
+        let (_, _, rtlil) = aiger_to_rtlil("aag 3 2 0 1 1\n2\n4\n6\n6 2 4\n", 6);
+
+        assert_rtlil(
+            &rtlil,
+            6,
+            &[
+                "  wire width 1 $ni$6",
+                "  wire width 1 input 0 \\input$2",
+                "  connect $ni$2 \\input$2 [0]",
+                "  wire width 1 input 1 \\input$4",
+                "  connect $ni$4 \\input$4 [0]",
+                "  wire width 1 output 2 \\output$6",
+                "  connect \\output$6 [0] $ni$6",
+                "  cell $lut $lut$6",
+                "    parameter \\WIDTH 2",
+                "    parameter \\LUT 4'1000",
+                "    connect \\Y $ni$6",
+            ],
+        );
+        assert!(lut_inputs_are(&rtlil, 6, &[2, 4]));
+    }
+
+    #[test]
+    fn write_rtlil_and_gate_padded() {
+        let (_, _, rtlil) = aiger_to_rtlil("aag 3 2 0 1 1\n2\n4\n6\n6 2 4\n", KSpec::new(&[4]));
+
+        assert_rtlil(
+            &rtlil,
+            6,
+            &[
+                "  cell $lut $lut$6",
+                "    parameter \\WIDTH 4",
+                "    parameter \\LUT 16'1000100010001000",
+                "    connect \\Y $ni$6",
+            ],
+        );
+        assert!(rtlil
+            .lines()
+            .any(|l| l == "    connect \\A { 1'0 1'0 $ni$2 $ni$4 }"
+                || l == "    connect \\A { 1'0 1'0 $ni$4 $ni$2 }"));
+    }
+
+    #[test]
+    fn write_rtlil_or_gate() {
+        let (_, _, rtlil) = aiger_to_rtlil("aag 3 2 0 1 1\n2\n4\n7\n6 3 5\n", 6);
+
+        assert_rtlil(
+            &rtlil,
+            6,
+            &[
+                "  wire width 1 $ni$7",
+                "  wire width 1 output 2 \\output$7",
+                "  connect \\output$7 [0] $ni$7",
+                "  cell $lut $lut$7",
+                "    parameter \\WIDTH 2",
+                "    parameter \\LUT 4'1110",
+                "    connect \\Y $ni$7",
+            ],
+        );
+        assert!(lut_inputs_are(&rtlil, 7, &[2, 4]));
+    }
+
+    #[test]
+    fn write_rtlil_both_polarities() {
+        let (_, num_luts, rtlil) = aiger_to_rtlil("aag 3 2 0 2 1\n2\n4\n6\n7\n6 2 4\n", 6);
+
+        assert_eq!(num_luts, 2);
+        assert_rtlil(
+            &rtlil,
+            8,
+            &[
+                "  wire width 1 output 2 \\output$6",
+                "  connect \\output$6 [0] $ni$6",
+                "  wire width 1 output 3 \\output$7",
+                "  connect \\output$7 [0] $ni$7",
+                "  cell $lut $lut$6",
+                "    parameter \\LUT 4'1000",
+                "  cell $lut $lut$7",
+                "    parameter \\LUT 4'0111",
+            ],
+        );
+        assert!(lut_inputs_are(&rtlil, 6, &[2, 4]));
+        assert!(lut_inputs_are(&rtlil, 7, &[2, 4]));
+    }
+
+    #[test]
+    fn write_rtlil_half_adder() {
+        let (_, _, rtlil) = aiger_to_rtlil(
+            "aag 5 2 0 2 3\n2\n4\n10\n6\n6 2 4\n8 3 5\n10 7 9\n\
+             i0 x\ni1 y\no0 s\no1 c\n",
+            6,
+        );
+
+        assert_rtlil(
+            &rtlil,
+            8,
+            &[
+                "  wire width 1 $ni$6",
+                "  wire width 1 $ni$10",
+                "  wire width 1 input 0 \\x",
+                "  connect $ni$2 \\x [0]",
+                "  wire width 1 input 1 \\y",
+                "  connect $ni$4 \\y [0]",
+                "  wire width 1 output 2 \\c",
+                "  connect \\c [0] $ni$6",
+                "  wire width 1 output 3 \\s",
+                "  connect \\s [0] $ni$10",
+                "  cell $lut $lut$6",
+                "    parameter \\LUT 4'1000",
+                "  cell $lut $lut$10",
+                "    parameter \\LUT 4'0110",
+            ],
+        );
+        assert!(lut_inputs_are(&rtlil, 6, &[2, 4]));
+        assert!(lut_inputs_are(&rtlil, 10, &[2, 4]));
+    }
+
+    #[test]
+    fn write_rtlil_bus_out_of_order() {
+        let (_, _, rtlil) = aiger_to_rtlil(
+            "aag 6 4 0 2 2\n2\n4\n6\n8\n10\n12\n10 2 4\n12 6 8\n\
+             i0 data[2]\ni1 data[0]\ni2 data[3]\ni3 data[1]\no0 y[0]\no1 y[1]\n",
+            6,
+        );
+
+        assert_rtlil(
+            &rtlil,
+            8,
+            &[
+                "  wire width 4 input 0 \\data",
+                "  connect $ni$2 \\data [2]",
+                "  connect $ni$4 \\data [0]",
+                "  connect $ni$6 \\data [3]",
+                "  connect $ni$8 \\data [1]",
+                "  wire width 2 output 1 \\y",
+                "  connect \\y [0] $ni$10",
+                "  connect \\y [1] $ni$12",
+            ],
+        );
+
+        // Bits are connected in order of their index
+        let connects = rtlil
+            .lines()
+            .filter(|l| l.starts_with("  connect $ni$"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            connects,
+            [
+                "  connect $ni$4 \\data [0]",
+                "  connect $ni$8 \\data [1]",
+                "  connect $ni$2 \\data [2]",
+                "  connect $ni$6 \\data [3]",
+            ]
+        );
+    }
+
+    #[test]
+    fn write_rtlil_bus_with_gap() {
+        let (_, _, rtlil) =
+            aiger_to_rtlil("aag 3 2 0 1 1\n2\n4\n6\n6 2 4\ni0 a[2]\ni1 a[0]\no0 y\n", 6);
+
+        assert_rtlil(
+            &rtlil,
+            6,
+            &[
+                "  wire width 1 input 0 \\a[0]",
+                "  connect $ni$4 \\a[0] [0]",
+                "  wire width 1 input 1 \\a[2]",
+                "  connect $ni$2 \\a[2] [0]",
+                "  wire width 1 output 2 \\y",
+                "  connect \\y [0] $ni$6",
+            ],
+        );
+    }
+
+    #[test]
+    fn validate_rtlil_connections_undeclared() {
+        let rtlil = r"module \top
+  wire width 1 input 0 \a
+  cell $lut $lut$6
+    connect \Y $ni$6
+    connect \A { \a }
+  end
+end
+";
+
+        assert_eq!(
+            validate_rtlil_connections(rtlil),
+            Err(RtlilError::UndeclaredWire("$ni$6".to_string()))
+        );
+    }
+
+    #[test]
+    fn validate_rtlil_connections_declared_later() {
+        let rtlil = r"module \top
+  connect $ni$2 \a [0]
+  wire width 1 $ni$2
+  wire width 1 input 0 \a
+end
+";
+
+        assert_eq!(
+            validate_rtlil_connections(rtlil),
+            Err(RtlilError::UndeclaredWire("$ni$2".to_string()))
+        );
+    }
+
+    #[test]
+    fn write_rtlil_constant_true_output() {
+        let (_, _, rtlil) = aiger_to_rtlil("aag 3 2 0 2 1\n2\n4\n6\n1\n6 2 4\n", 6);
+
+        assert_rtlil(
+            &rtlil,
+            9,
+            &[
+                "  wire width 1 $ni$0",
+                "  connect $ni$0 1'0",
+                "  wire width 1 output 0 \\output$1",
+                "  connect \\output$1 [0] $ni$1",
+                "  wire width 1 input 1 \\input$2",
+                "  wire width 1 input 2 \\input$4",
+                "  cell $lut $lut$1",
+                "    parameter \\LUT 2'01",
+                "    connect \\A { $ni$0 }",
+            ],
+        );
+
+        // Neither constant literal is a port of the module
+        assert!(!rtlil.contains("input$0"));
+        assert!(!rtlil.contains("input$1"));
+    }
+
+    #[test]
+    fn write_rtlil_constant_false_output() {
+        let (_, num_luts, rtlil) = aiger_to_rtlil("aag 0 0 0 1 0\n0\no0 y\n", 6);
+
+        assert_eq!(num_luts, 0);
+        assert_eq!(
+            rtlil.lines().collect::<Vec<_>>(),
+            vec![
+                "module \\top",
+                "  wire width 1 output 0 \\y",
+                "  connect \\y [0] 1'0",
+                "end",
+            ]
+        );
+    }
+
+    #[test]
+    fn write_rtlil_toggle_flip_flop() {
+        // The latch 2 toggles on every clock, and the output is its inverse
+        let (_, num_luts, rtlil) = aiger_to_rtlil("aag 1 0 1 1 0\n2 3\n3\n", 6);
+
+        assert_eq!(num_luts, 1);
+        assert_rtlil(
+            &rtlil,
+            4,
+            &[
+                "  attribute \\init 1'0",
+                "  wire width 1 $ni$2",
+                "  wire width 1 $ni$3",
+                "  wire width 1 output 0 \\output$3",
+                "  wire width 1 input 1 \\clk",
+                "  cell $lut $lut$3",
+                "    connect \\A { $ni$2 }",
+                "  cell $dff $dff$2",
+                "    connect \\CLK \\clk",
+                "    connect \\D $ni$3",
+                "    connect \\Q $ni$2",
+            ],
+        );
+
+        // The latch output isn't an input of the module
+        assert!(!rtlil.contains("input$2"));
+    }
+
+    /// Runs the full flow on an AIGER file whose latches may have reset
+    /// values, returning the RTLIL output.
+    #[test]
+    fn write_rtlil_latch_reset() {
+        let (_, _, rtlil) = aiger_to_rtlil("aag 1 0 1 1 0\n2 3 1\n3\n", 6);
+        assert!(rtlil.contains("  attribute \\init 1'1\n  wire width 1 $ni$2\n"));
+
+        // A latch which may take either value has no init attribute
+        let (_, _, rtlil) = aiger_to_rtlil("aag 1 0 1 1 0\n2 3 2\n3\n", 6);
+        assert!(!rtlil.contains("init"));
+    }
+
+    #[test]
+    fn write_rtlil_latch_constant_next() {
+        // The latch 2 is cleared on the first clock
+        let (_, _, rtlil) = aiger_to_rtlil("aag 1 0 1 1 0\n2 0\n3\n", 6);
+
+        assert!(rtlil.contains("  wire width 1 $ni$0\n  connect $ni$0 1'0\n"));
+        assert!(rtlil.contains("    connect \\D $ni$0\n"));
+    }
+
+    #[test]
+    fn write_rtlil_latch_input_named_clk() {
+        // The clock must not be confused with the inputs named clk and clk_1
+        let (_, _, rtlil) = aiger_to_rtlil("aag 3 2 1 1 0\n2\n4\n6 2\n6\ni0 clk\ni1 clk_1\n", 6);
+
+        assert!(rtlil.contains("  wire width 1 input 0 \\clk\n"));
+        assert!(rtlil.contains("  wire width 1 input 1 \\clk_1\n"));
+        assert!(rtlil.contains("  wire width 1 input 3 \\clk_2\n"));
+        assert!(rtlil.contains("    connect \\CLK \\clk_2\n"));
+        assert_eq!(validate_rtlil_connections(&rtlil), Ok(()));
+    }
+
+    #[test]
+    fn write_rtlil_input_and_output_same_symbol() {
+        let (_, _, rtlil) = aiger_to_rtlil("aag 1 1 0 1 0\n2\n3\ni0 a\no0 a\n", 6);
+
+        assert_rtlil(
+            &rtlil,
+            4,
+            &[
+                "  wire width 1 input 0 \\a",
+                "  connect $ni$2 \\a [0]",
+                "  wire width 1 output 1 \\a$1",
+                "  connect \\a$1 [0] $ni$3",
+            ],
+        );
+        assert_eq!(validate_rtlil_connections(&rtlil), Ok(()));
+    }
+
+    #[test]
+    fn write_rtlil_repeated_symbol_bit() {
+        // Two inputs are both bit 2 of a, so the gapped port a is split into
+        // a[0] and two ports named a[2]
+        let (_, _, rtlil) = aiger_to_rtlil(
+            "aag 5 3 0 1 2\n2\n4\n6\n10\n8 2 4\n10 8 6\ni0 a[2]\ni1 a[0]\ni2 a[2]\no0 y\n",
+            6,
+        );
+
+        assert!(rtlil.contains("  wire width 1 input 0 \\a[0]\n"));
+        assert!(rtlil.contains("  wire width 1 input 1 \\a[2]\n"));
+        assert!(rtlil.contains("  wire width 1 input 2 \\a[2]$1\n"));
+        assert_eq!(validate_rtlil_connections(&rtlil), Ok(()));
+    }
+
+    #[test]
+    fn validate_rtlil_connections_duplicate_wire() {
+        let rtlil = r"module \top
+  wire width 1 input 0 \a
+  wire width 1 input 1 \a
+end
+";
+
+        assert_eq!(
+            validate_rtlil_connections(rtlil),
+            Err(RtlilError::DuplicateWire("\\a".to_string()))
+        );
     }
 }
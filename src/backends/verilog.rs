@@ -0,0 +1,407 @@
+use super::rtlil::{clock_name, latches, ports};
+use crate::boolean_network::*;
+use crate::flowmap::map::LUT;
+use crate::flowmap::*;
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+/// Verilog keywords which could plausibly be used as symbols, and so must be
+/// escaped to be used as identifiers.
+const KEYWORDS: &[&str] = &[
+    "always",
+    "and",
+    "assign",
+    "begin",
+    "case",
+    "default",
+    "else",
+    "end",
+    "endcase",
+    "endmodule",
+    "for",
+    "if",
+    "initial",
+    "inout",
+    "input",
+    "integer",
+    "module",
+    "nand",
+    "nor",
+    "not",
+    "or",
+    "output",
+    "parameter",
+    "reg",
+    "wire",
+    "xnor",
+    "xor",
+];
+
+/// Returns the name as a Verilog identifier, which is an escaped identifier
+/// unless the name is already a valid simple identifier.
+fn identifier(name: &str) -> String {
+    let mut chars = name.chars();
+    let is_simple = match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+        }
+        _ => false,
+    };
+
+    if is_simple && !KEYWORDS.contains(&name) {
+        name.to_string()
+    } else {
+        // An escaped identifier is terminated by whitespace
+        format!("\\{} ", name)
+    }
+}
+
+/// Returns an expression for the truth table as nested ternaries selecting on
+/// each input in turn, with the first input as the most significant bit of the
+/// truth table index. Halves of the truth table which are equal don't depend on
+/// the input, so they don't select on it, and selecting between constants is
+/// simplified to the input or its inverse.
+fn ternary(inputs: &[String], truth_table: &[bool]) -> String {
+    match inputs.split_first() {
+        None => if truth_table[0] { "1'b1" } else { "1'b0" }.to_string(),
+        Some((input, rest)) => {
+            let (when_false, when_true) = truth_table.split_at(truth_table.len() / 2);
+            if when_false == when_true {
+                return ternary(rest, when_false);
+            }
+
+            let when_true = ternary(rest, when_true);
+            let when_false = ternary(rest, when_false);
+            match (when_true.as_str(), when_false.as_str()) {
+                ("1'b1", "1'b0") => input.clone(),
+                ("1'b0", "1'b1") => format!("~{}", input),
+                _ => format!(
+                    "{} ? {} : {}",
+                    input,
+                    parenthesise(when_true),
+                    parenthesise(when_false)
+                ),
+            }
+        }
+    }
+}
+
+/// Parenthesises a ternary expression so it can be nested in another.
+fn parenthesise(expression: String) -> String {
+    if expression.contains('?') {
+        format!("({})", expression)
+    } else {
+        expression
+    }
+}
+
+/// Writes the mapped network as a structural Verilog module named `top`.
+///
+/// Ports are derived from the PIs and POs in the same way as by `write_rtlil`,
+/// with the bits of a symbol forming a vector port unless they have gaps, in
+/// which case every bit becomes its own `name[bit]` port. The value of node N
+/// is carried by the wire `ni$N`, and each LUT becomes an `assign` to the wire
+/// of its output, with its truth table expressed as nested ternaries.
+///
+/// The output of each latch is a `reg` initialised to the latch's reset value,
+/// which takes its next state on every rising edge of the input named by
+/// `clock_name`.
+pub fn write_verilog<T: io::Write, Ni: 'static + NodeIndex>(
+    writer: T,
+    network: &FlowMapBooleanNetwork<Ni>,
+    luts: &[LUT<Ni>],
+) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(writer);
+
+    let ports = ports(network);
+    let latches = latches(network);
+    let clock = identifier(&clock_name(&ports));
+    let wire = |ni: Ni| format!("ni${}", ni.node_index());
+    // Returns the expression for a bit of a port, since a single-bit port is a
+    // scalar which can't be indexed
+    let port_bit = |name: &str, width: usize, bit: usize| {
+        if width == 1 {
+            identifier(name)
+        } else {
+            format!("{}[{}]", identifier(name), bit)
+        }
+    };
+
+    let mut port_names = ports
+        .iter()
+        .map(|port| identifier(&port.name))
+        .collect::<Vec<_>>();
+    if !latches.is_empty() {
+        port_names.push(clock.clone());
+    }
+    writeln!(writer, "module top({});", port_names.join(", "))?;
+
+    for port in &ports {
+        let direction = if port.is_input { "input" } else { "output" };
+        if port.bits.len() == 1 {
+            writeln!(writer, "  {} {};", direction, identifier(&port.name))?;
+        } else {
+            writeln!(
+                writer,
+                "  {} [{}:0] {};",
+                direction,
+                port.bits.len() - 1,
+                identifier(&port.name)
+            )?;
+        }
+    }
+    if !latches.is_empty() {
+        writeln!(writer, "  input {};", clock)?;
+    }
+
+    // The wire for a node is shared between the LUT which generates it and any
+    // port it's connected to, so only declare it once
+    let mut wires_written = HashSet::new();
+
+    for port in ports.iter().filter(|port| port.is_input) {
+        for (bit, ni) in port.bits.iter().enumerate() {
+            wires_written.insert(ni.node_index());
+            writeln!(
+                writer,
+                "  wire {} = {};",
+                wire(*ni),
+                port_bit(&port.name, port.bits.len(), bit)
+            )?;
+        }
+    }
+
+    // Constants are never inputs, so any constant used as an input to a LUT
+    // or as the next state of a latch must be driven here instead. The only
+    // constant in an AIGER network is literal 0, which is false.
+    let lut_inputs = luts.iter().flat_map(|lut| lut.inputs.iter());
+    let latch_nexts = latches.iter().map(|(_, next)| next);
+    for input in lut_inputs.chain(latch_nexts) {
+        if network.node_value(*input).is_constant && wires_written.insert(input.node_index()) {
+            writeln!(writer, "  wire {} = 1'b0;", wire(*input))?;
+        }
+    }
+
+    for (ni, _) in &latches {
+        wires_written.insert(ni.node_index());
+        match network.node_value(*ni).latch_reset {
+            LatchReset::Zero => writeln!(writer, "  reg {} = 1'b0;", wire(*ni))?,
+            LatchReset::One => writeln!(writer, "  reg {} = 1'b1;", wire(*ni))?,
+            LatchReset::NonDeterministic => writeln!(writer, "  reg {};", wire(*ni))?,
+        }
+    }
+
+    for lut in luts {
+        if wires_written.insert(lut.output.node_index()) {
+            writeln!(writer, "  wire {};", wire(lut.output))?;
+        }
+    }
+
+    for lut in luts {
+        let inputs = lut.inputs.iter().map(|ni| wire(*ni)).collect::<Vec<_>>();
+        let entries = lut.truth_table.entries().collect::<Vec<_>>();
+
+        writeln!(
+            writer,
+            "  assign {} = {};",
+            wire(lut.output),
+            ternary(&inputs, &entries)
+        )?;
+    }
+
+    for (ni, next) in &latches {
+        writeln!(
+            writer,
+            "  always @(posedge {}) {} <= {};",
+            clock,
+            wire(*ni),
+            wire(*next)
+        )?;
+    }
+
+    for port in ports.iter().filter(|port| !port.is_input) {
+        for (bit, ni) in port.bits.iter().enumerate() {
+            let value = if network.node_value(*ni).is_constant {
+                "1'b0".to_string()
+            } else {
+                wire(*ni)
+            };
+
+            writeln!(
+                writer,
+                "  assign {} = {};",
+                port_bit(&port.name, port.bits.len(), bit),
+                value
+            )?;
+        }
+    }
+
+    writeln!(writer, "endmodule")?;
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::write_mapped_aiger;
+
+    #[test]
+    fn identifier_test() {
+        assert_eq!(identifier("a_b$1"), "a_b$1");
+        assert_eq!(identifier("input$2"), "input$2");
+        assert_eq!(identifier("y[2]"), "\\y[2] ");
+        assert_eq!(identifier("1a"), "\\1a ");
+        assert_eq!(identifier("wire"), "\\wire ");
+    }
+
+    #[test]
+    fn ternary_test() {
+        let inputs = ["a".to_string(), "b".to_string()];
+
+        assert_eq!(
+            ternary(&inputs, &[false, false, false, true]),
+            "a ? b : 1'b0"
+        );
+        assert_eq!(ternary(&inputs, &[false, true, true, false]), "a ? ~b : b");
+        assert_eq!(ternary(&inputs, &[false, false, true, true]), "a");
+        assert_eq!(ternary(&inputs, &[true, true, true, true]), "1'b1");
+    }
+
+    #[test]
+    fn write_verilog_and_gate() {
+        let verilog = write_mapped_aiger(
+            "aag 3 2 0 1 1\n2\n4\n6\n6 2 4\ni0 a\ni1 b\no0 y\n",
+            6,
+            |w, n, l| write_verilog(w, n, l),
+        );
+        let lines = verilog.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines[0], "module top(a, b, y);");
+        assert_eq!(lines[1], "  input a;");
+        assert_eq!(lines[2], "  input b;");
+        assert_eq!(lines[3], "  output y;");
+        assert_eq!(lines[4], "  wire ni$2 = a;");
+        assert_eq!(lines[5], "  wire ni$4 = b;");
+        assert_eq!(lines[6], "  wire ni$6;");
+        assert!(
+            lines[7] == "  assign ni$6 = ni$2 ? ni$4 : 1'b0;"
+                || lines[7] == "  assign ni$6 = ni$4 ? ni$2 : 1'b0;"
+        );
+        assert_eq!(lines[8], "  assign y = ni$6;");
+        assert_eq!(lines[9], "endmodule");
+    }
+
+    #[test]
+    fn write_verilog_buses() {
+        let verilog = write_mapped_aiger(
+            "aag 3 2 0 2 1\n2\n4\n6\n7\n6 2 4\ni0 x[1]\ni1 x[0]\no0 y[0]\no1 y[2]\n",
+            6,
+            |w, n, l| write_verilog(w, n, l),
+        );
+        let lines = verilog.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines[0], "module top(x, \\y[0] , \\y[2] );");
+        assert!(lines.contains(&"  input [1:0] x;"));
+        assert!(lines.contains(&"  output \\y[0] ;"));
+        assert!(lines.contains(&"  wire ni$4 = x[0];"));
+        assert!(lines.contains(&"  wire ni$2 = x[1];"));
+        assert!(lines.contains(&"  assign \\y[0]  = ni$6;"));
+        assert!(lines.contains(&"  assign \\y[2]  = ni$7;"));
+    }
+
+    #[test]
+    fn write_verilog_constant_true_output() {
+        let verilog =
+            write_mapped_aiger("aag 1 1 0 1 0\n2\n1\n", 6, |w, n, l| write_verilog(w, n, l));
+
+        assert_eq!(
+            verilog.lines().collect::<Vec<_>>(),
+            vec![
+                "module top(output$1, input$2);",
+                "  output output$1;",
+                "  input input$2;",
+                "  wire ni$2 = input$2;",
+                "  wire ni$0 = 1'b0;",
+                "  wire ni$1;",
+                "  assign ni$1 = ~ni$0;",
+                "  assign output$1 = ni$1;",
+                "endmodule",
+            ]
+        );
+    }
+
+    #[test]
+    fn write_verilog_latch() {
+        // A toggle flip-flop, whose output is the inverse of its next state
+        let verilog = write_mapped_aiger("aag 1 0 1 1 0\n2 3 1\n3\n", 6, |w, n, l| {
+            write_verilog(w, n, l)
+        });
+
+        assert_eq!(
+            verilog.lines().collect::<Vec<_>>(),
+            vec![
+                "module top(output$3, clk);",
+                "  output output$3;",
+                "  input clk;",
+                "  reg ni$2 = 1'b1;",
+                "  wire ni$3;",
+                "  assign ni$3 = ~ni$2;",
+                "  always @(posedge clk) ni$2 <= ni$3;",
+                "  assign output$3 = ni$3;",
+                "endmodule",
+            ]
+        );
+    }
+
+    #[test]
+    fn write_verilog_latch_constant_next() {
+        let verilog = write_mapped_aiger("aag 1 0 1 1 0\n2 0 2\n2\n", 6, |w, n, l| {
+            write_verilog(w, n, l)
+        });
+        let lines = verilog.lines().collect::<Vec<_>>();
+
+        assert!(lines.contains(&"  wire ni$0 = 1'b0;"));
+        assert!(lines.contains(&"  reg ni$2;"));
+        assert!(lines.contains(&"  always @(posedge clk) ni$2 <= ni$0;"));
+        assert!(lines.contains(&"  assign output$2 = ni$2;"));
+    }
+
+    #[test]
+    fn write_verilog_constant_false_output() {
+        let verilog = write_mapped_aiger("aag 0 0 0 1 0\n0\no0 y\n", 6, |w, n, l| {
+            write_verilog(w, n, l)
+        });
+
+        assert_eq!(
+            verilog.lines().collect::<Vec<_>>(),
+            vec![
+                "module top(y);",
+                "  output y;",
+                "  assign y = 1'b0;",
+                "endmodule",
+            ]
+        );
+    }
+
+    #[test]
+    fn write_verilog_latch_input_named_clk() {
+        let verilog = write_mapped_aiger("aag 2 1 1 1 0\n2\n4 2\n4\ni0 clk\n", 6, |w, n, l| {
+            write_verilog(w, n, l)
+        });
+
+        assert_eq!(
+            verilog.lines().collect::<Vec<_>>(),
+            vec![
+                "module top(clk, output$4, clk_1);",
+                "  input clk;",
+                "  output output$4;",
+                "  input clk_1;",
+                "  wire ni$2 = clk;",
+                "  reg ni$4 = 1'b0;",
+                "  always @(posedge clk_1) ni$4 <= ni$2;",
+                "  assign output$4 = ni$4;",
+                "endmodule",
+            ]
+        );
+    }
+}
@@ -2,6 +2,7 @@
 
 use std::hash::Hash;
 use std::iter;
+use std::mem;
 
 /// Wrapper around a node index for which an edge is "from", i.e., the edge
 /// points away from the node.
@@ -37,12 +38,17 @@ impl<Ni: NodeIndex> NodeIndex for To<Ni> {
 
 /// Internal node representation.
 #[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Node<Ni> {
     ancestors: Vec<Ni>,
     descendents: Vec<Ni>,
 }
 
 /// A boolean network.
+///
+/// With the `serde` feature, networks can be serialized and deserialized,
+/// e.g. to save a labelled network before mapping it in another process.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BooleanNetwork<N: Default, E: Default, Ni: NodeIndex> {
     nodes: Vec<Node<Ni>>,
     node_values: Vec<N>,
@@ -50,32 +56,67 @@ pub struct BooleanNetwork<N: Default, E: Default, Ni: NodeIndex> {
     max_node_index: usize,
 }
 
+/// An error which occurs while creating a boolean network.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum BooleanNetworkError {
+    /// Storage for the provided number of nodes could not be allocated.
+    TooManyNodes(usize),
+}
+
 impl<N: Default, E: Default, Ni: NodeIndex> BooleanNetwork<N, E, Ni> {
     /// Creates a new boolean network with the provided maximum index.
+    ///
+    /// Panics if storage for the network could not be allocated. See
+    /// `try_new` for a version which returns an error instead.
     pub fn new(max_index: Ni) -> BooleanNetwork<N, E, Ni> {
+        match Self::try_new(max_index) {
+            Ok(network) => network,
+            Err(BooleanNetworkError::TooManyNodes(num_nodes)) => panic!(
+                "could not allocate storage for a network with {} nodes",
+                num_nodes
+            ),
+        }
+    }
+
+    /// Creates a new boolean network with the provided maximum index, or
+    /// returns an error if storage for the network could not be allocated.
+    pub fn try_new(max_index: Ni) -> Result<BooleanNetwork<N, E, Ni>, BooleanNetworkError> {
         let max_node_index = max_index.node_index();
-        let num_nodes = max_node_index + 1;
+        let num_nodes = max_node_index
+            .checked_add(1)
+            .ok_or(BooleanNetworkError::TooManyNodes(max_node_index))?;
+
+        // Refuse sizes which could never be allocated before trying to reserve
+        // anything, since the reservation size would overflow
+        if num_nodes > usize::MAX / mem::size_of::<Node<Ni>>().max(1) {
+            return Err(BooleanNetworkError::TooManyNodes(num_nodes));
+        }
 
-        let nodes = iter::repeat(())
-            .map(|_| Node {
+        let mut nodes = Vec::new();
+        let mut node_values = Vec::new();
+        let mut edge_values = Vec::new();
+        nodes
+            .try_reserve_exact(num_nodes)
+            .and_then(|_| node_values.try_reserve_exact(num_nodes))
+            .and_then(|_| edge_values.try_reserve_exact(num_nodes))
+            .map_err(|_| BooleanNetworkError::TooManyNodes(num_nodes))?;
+
+        nodes.extend(
+            iter::repeat_with(|| Node {
                 ancestors: vec![],
                 descendents: vec![],
             })
-            .take(num_nodes)
-            .collect();
-
-        let node_values = iter::repeat(())
-            .map(|_| N::default())
-            .take(num_nodes)
-            .collect();
-        let edge_values = iter::repeat(()).map(|_| vec![]).take(num_nodes).collect();
+            .take(num_nodes),
+        );
+        node_values.extend(iter::repeat_with(N::default).take(num_nodes));
+        edge_values.extend(iter::repeat_with(Vec::new).take(num_nodes));
 
-        BooleanNetwork {
+        Ok(BooleanNetwork {
             nodes,
             node_values,
             edge_values,
             max_node_index,
-        }
+        })
     }
 
     /// Returns the direct ancestors of the provided node.
@@ -102,6 +143,61 @@ impl<N: Default, E: Default, Ni: NodeIndex> BooleanNetwork<N, E, Ni> {
         &self.nodes[of.node_index()].descendents
     }
 
+    /// Returns the fan-in of the provided node, i.e. its in-degree or the
+    /// number of direct ancestors. This is the length of a stored list, so it
+    /// takes constant time and needs no caching.
+    pub fn fan_in(&self, of: Ni) -> usize {
+        self.ancestors(of).len()
+    }
+
+    /// Returns the fan-out of the provided node, i.e. its out-degree or the
+    /// number of direct descendents. Like `fan_in`, this takes constant time.
+    pub fn fan_out(&self, of: Ni) -> usize {
+        self.descendents(of).len()
+    }
+
+    /// Returns the in-degree of the provided node, which is the same as its
+    /// fan-in.
+    pub fn in_degree(&self, of: Ni) -> usize {
+        self.fan_in(of)
+    }
+
+    /// Returns the out-degree of the provided node, which is the same as its
+    /// fan-out.
+    pub fn out_degree(&self, of: Ni) -> usize {
+        self.fan_out(of)
+    }
+
+    /// Returns the largest fan-in of any node in the network, e.g. to reject a
+    /// network with a node too wide to map before labelling it.
+    pub fn max_fanin(&self) -> usize {
+        self.nodes
+            .iter()
+            .map(|node| node.ancestors.len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns the direct ancestors of the provided node, without checking that
+    /// the node index is in bounds.
+    ///
+    /// # Safety
+    ///
+    /// The node index must be at most the maximum node index of the network.
+    pub unsafe fn ancestors_unchecked(&self, of: Ni) -> &[Ni] {
+        &self.nodes.get_unchecked(of.node_index()).ancestors
+    }
+
+    /// Returns the direct descendents of the provided node, without checking
+    /// that the node index is in bounds.
+    ///
+    /// # Safety
+    ///
+    /// The node index must be at most the maximum node index of the network.
+    pub unsafe fn descendents_unchecked(&self, of: Ni) -> &[Ni] {
+        &self.nodes.get_unchecked(of.node_index()).descendents
+    }
+
     /// Returns a reference to the provided node's value.
     pub fn node_value(&self, of: Ni) -> &N {
         assert!(
@@ -114,6 +210,16 @@ impl<N: Default, E: Default, Ni: NodeIndex> BooleanNetwork<N, E, Ni> {
         &self.node_values[of.node_index()]
     }
 
+    /// Returns a reference to the provided node's value, without checking that
+    /// the node index is in bounds.
+    ///
+    /// # Safety
+    ///
+    /// The node index must be at most the maximum node index of the network.
+    pub unsafe fn node_value_unchecked(&self, of: Ni) -> &N {
+        self.node_values.get_unchecked(of.node_index())
+    }
+
     /// Returns a mutable reference to the provided node's value.
     pub fn node_value_mut(&mut self, of: Ni) -> &mut N {
         assert!(
@@ -127,8 +233,8 @@ impl<N: Default, E: Default, Ni: NodeIndex> BooleanNetwork<N, E, Ni> {
     }
 
     /// Returns the two indices required to access the value for the specified
-    /// edge.
-    fn edge_value_index(&self, from: From<Ni>, to: To<Ni>) -> (usize, usize) {
+    /// edge, or `None` if the edge does not exist.
+    fn edge_value_index(&self, from: From<Ni>, to: To<Ni>) -> Option<(usize, usize)> {
         assert!(
             from.node_index() <= self.max_node_index,
             "node index out of bounds: the maximum node index is {} but the node index is {}",
@@ -146,26 +252,82 @@ impl<N: Default, E: Default, Ni: NodeIndex> BooleanNetwork<N, E, Ni> {
         let j = self.nodes[to.node_index()]
             .ancestors
             .iter()
-            .position(|ni| *ni == from.0)
-            .unwrap();
+            .position(|ni| *ni == from.0)?;
+
+        Some((i, j))
+    }
 
-        (i, j)
+    /// Returns `true` if there is an edge from `from` to `to`, or `false` if
+    /// there is not.
+    pub fn has_edge(&self, from: From<Ni>, to: To<Ni>) -> bool {
+        self.edge_value_index(from, to).is_some()
     }
 
     /// Returns a reference to the provided edge's value.
+    ///
+    /// Panics if the edge does not exist. See `edge_value_checked` for a
+    /// version which returns `None` instead.
     pub fn edge_value(&self, from: From<Ni>, to: To<Ni>) -> &E {
-        let (i, j) = self.edge_value_index(from, to);
-        &self.edge_values[i][j]
+        self.edge_value_checked(from, to)
+            .expect("edge does not exist")
+    }
+
+    /// Returns a reference to the provided edge's value, or `None` if the edge
+    /// does not exist.
+    pub fn edge_value_checked(&self, from: From<Ni>, to: To<Ni>) -> Option<&E> {
+        let (i, j) = self.edge_value_index(from, to)?;
+        Some(&self.edge_values[i][j])
     }
 
     /// Returns a mutable reference to the provided edge's value.
+    ///
+    /// Panics if the edge does not exist.
     pub fn edge_value_mut(&mut self, from: From<Ni>, to: To<Ni>) -> &mut E {
-        let (i, j) = self.edge_value_index(from, to);
+        let (i, j) = self
+            .edge_value_index(from, to)
+            .expect("edge does not exist");
         &mut self.edge_values[i][j]
     }
 
+    /// Returns the direct ancestors of the provided node, along with the value
+    /// of the edge from each ancestor to the node.
+    pub fn edge_values_of(&self, to: To<Ni>) -> impl Iterator<Item = (Ni, &E)> + '_ {
+        assert!(
+            to.node_index() <= self.max_node_index,
+            "node index out of bounds: the maximum node index is {} but the node index is {}",
+            self.max_node_index,
+            to.node_index()
+        );
+
+        let i = to.node_index();
+        self.nodes[i]
+            .ancestors
+            .iter()
+            .copied()
+            .zip(self.edge_values[i].iter())
+    }
+
     /// Adds an edge to the network graph.
+    ///
+    /// Parallel edges aren't supported, since an edge is identified only by the
+    /// nodes it connects, so the value of a second edge between the same nodes
+    /// could never be accessed. In debug builds, panics if the edge already
+    /// exists; use `add_edge_unique` where the same edge may be added more than
+    /// once.
     pub fn add_edge(&mut self, from: From<Ni>, to: To<Ni>) {
+        let (from_index, to_index) = (from.node_index(), to.node_index());
+        let added = self.add_edge_unique(from, to);
+
+        debug_assert!(
+            added,
+            "edge already exists: an edge from node index {} to node index {} was already added",
+            from_index, to_index
+        );
+    }
+
+    /// Adds an edge to the network graph if it does not already exist. Returns
+    /// `true` if the edge was added, or `false` if it already existed.
+    pub fn add_edge_unique(&mut self, from: From<Ni>, to: To<Ni>) -> bool {
         assert!(
             from.node_index() <= self.max_node_index,
             "node index out of bounds: the maximum node index is {} but the node index is {}",
@@ -179,15 +341,233 @@ impl<N: Default, E: Default, Ni: NodeIndex> BooleanNetwork<N, E, Ni> {
             to.node_index()
         );
 
+        if self.nodes[to.node_index()].ancestors.contains(&from.0) {
+            return false;
+        }
+
         self.nodes[to.node_index()].ancestors.push(from.0);
         self.nodes[from.node_index()].descendents.push(to.0);
         self.edge_values[to.node_index()].push(E::default());
+
+        true
+    }
+
+    /// Removes an edge from the network graph, along with its value. Returns
+    /// `true` if the edge was removed, or `false` if it didn't exist.
+    ///
+    /// The remaining ancestors of `to` keep their order.
+    pub fn remove_edge(&mut self, from: From<Ni>, to: To<Ni>) -> bool {
+        assert!(
+            from.node_index() <= self.max_node_index,
+            "node index out of bounds: the maximum node index is {} but the node index is {}",
+            self.max_node_index,
+            from.node_index()
+        );
+        assert!(
+            to.node_index() <= self.max_node_index,
+            "node index out of bounds: the maximum node index is {} but the node index is {}",
+            self.max_node_index,
+            to.node_index()
+        );
+
+        let i = to.node_index();
+        let j = match self.nodes[i].ancestors.iter().position(|ni| *ni == from.0) {
+            Some(j) => j,
+            None => return false,
+        };
+
+        self.nodes[i].ancestors.remove(j);
+        self.edge_values[i].remove(j);
+        self.nodes[from.node_index()]
+            .descendents
+            .retain(|descendent| *descendent != to.0);
+
+        true
+    }
+
+    /// Removes every edge into and out of the provided node, along with the
+    /// values of those edges, leaving the node isolated.
+    ///
+    /// The node index remains valid and the node keeps its value, since
+    /// networks have a fixed number of nodes. The index is never reused for
+    /// another node.
+    pub fn remove_node(&mut self, ni: Ni) {
+        assert!(
+            ni.node_index() <= self.max_node_index,
+            "node index out of bounds: the maximum node index is {} but the node index is {}",
+            self.max_node_index,
+            ni.node_index()
+        );
+
+        let node = mem::replace(
+            &mut self.nodes[ni.node_index()],
+            Node {
+                ancestors: vec![],
+                descendents: vec![],
+            },
+        );
+        self.edge_values[ni.node_index()].clear();
+
+        for ancestor in node.ancestors {
+            self.nodes[ancestor.node_index()]
+                .descendents
+                .retain(|descendent| *descendent != ni);
+        }
+
+        for descendent in node.descendents {
+            let i = descendent.node_index();
+            let ancestors = &mut self.nodes[i].ancestors;
+
+            // Edge values are stored in the same order as the ancestors they
+            // belong to, so remove both together
+            while let Some(j) = ancestors.iter().position(|ancestor| *ancestor == ni) {
+                ancestors.remove(j);
+                self.edge_values[i].remove(j);
+            }
+        }
+    }
+
+    /// Returns every node index of the network, in order.
+    pub fn nodes(&self) -> impl Iterator<Item = Ni> {
+        (0..self.node_count()).map(Ni::from_node_index)
     }
 
     /// Returns the number of nodes in the network.
     pub fn node_count(&self) -> usize {
         self.max_node_index + 1
     }
+
+    /// Returns the maximum valid node index of the network.
+    pub fn max_index(&self) -> usize {
+        self.max_node_index
+    }
+
+    /// Returns `true` if the node index is within the bounds of the network,
+    /// or `false` if it is not.
+    pub fn is_valid_index(&self, ni: Ni) -> bool {
+        ni.node_index() <= self.max_node_index
+    }
+
+    /// Returns the adjacency matrix of the network, where `matrix[i][j]` is
+    /// `true` if there is an edge from node index `i` to node index `j`.
+    ///
+    /// The matrix requires O(N^2) memory, so this is only suitable for
+    /// inspecting small networks of up to a few hundred nodes.
+    pub fn adjacency_matrix(&self) -> Vec<Vec<bool>> {
+        self.nodes
+            .iter()
+            .map(|node| {
+                let mut row = vec![false; self.node_count()];
+                for descendent in &node.descendents {
+                    row[descendent.node_index()] = true;
+                }
+
+                row
+            })
+            .collect()
+    }
+
+    /// Returns the nodes of a cycle in the network, in the order of the edges
+    /// between them, or `None` if the network is acyclic.
+    ///
+    /// A topological ordering never reaches the nodes of a cycle or anything
+    /// they drive, so this should be checked first for any network which
+    /// might contain one.
+    pub fn find_cycle(&self) -> Option<Vec<Ni>> {
+        #[derive(Copy, Clone, PartialEq)]
+        enum State {
+            Unvisited,
+            OnPath,
+            Done,
+        }
+
+        let mut states = vec![State::Unvisited; self.node_count()];
+
+        for start in 0..self.node_count() {
+            if states[start] != State::Unvisited {
+                continue;
+            }
+
+            // The path of a depth-first search from `start`, with the position
+            // of the next descendent of each node on the path to follow
+            let mut path = vec![(start, 0)];
+            states[start] = State::OnPath;
+
+            while let Some(&(ni, next)) = path.last() {
+                let descendent = match self.nodes[ni].descendents.get(next) {
+                    Some(descendent) => descendent.node_index(),
+                    None => {
+                        states[ni] = State::Done;
+                        path.pop();
+                        continue;
+                    }
+                };
+                path.last_mut().unwrap().1 += 1;
+
+                match states[descendent] {
+                    State::Unvisited => {
+                        states[descendent] = State::OnPath;
+                        path.push((descendent, 0));
+                    }
+                    State::OnPath => {
+                        let position = path.iter().position(|(ni, _)| *ni == descendent);
+                        let cycle = path[position.unwrap()..]
+                            .iter()
+                            .map(|(ni, _)| Ni::from_node_index(*ni))
+                            .collect();
+
+                        return Some(cycle);
+                    }
+                    State::Done => {}
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Returns the nodes whose depth is `target_level`, where `depths` holds
+    /// the depth of every node in node index order.
+    pub fn nodes_at_level<'a>(
+        &'a self,
+        depths: &'a [u32],
+        target_level: u32,
+    ) -> impl Iterator<Item = Ni> + 'a {
+        assert_eq!(
+            depths.len(),
+            self.node_count(),
+            "a depth must be provided for every node"
+        );
+
+        self.nodes()
+            .filter(move |ni| depths[ni.node_index()] == target_level)
+    }
+
+    /// Consumes the network, returning a network with the same structure and
+    /// edge values but with each node value transformed by `f`.
+    pub fn map_node_values<M: Default, F: Fn(N) -> M>(self, f: F) -> BooleanNetwork<M, E, Ni> {
+        BooleanNetwork {
+            nodes: self.nodes,
+            node_values: self.node_values.into_iter().map(f).collect(),
+            edge_values: self.edge_values,
+            max_node_index: self.max_node_index,
+        }
+    }
+
+    /// Consumes the network, returning a network with the same structure and
+    /// node values but with each edge value transformed by `f`.
+    pub fn map_edge_values<F2: Default, F: Fn(E) -> F2>(self, f: F) -> BooleanNetwork<N, F2, Ni> {
+        BooleanNetwork {
+            nodes: self.nodes,
+            node_values: self.node_values,
+            edge_values: self
+                .edge_values
+                .into_iter()
+                .map(|edge_values| edge_values.into_iter().map(&f).collect())
+                .collect(),
+            max_node_index: self.max_node_index,
+        }
+    }
 }
 
 /// Trait for types which represent a node in a boolean network, and thus can be
@@ -204,6 +584,36 @@ pub trait NodeIndex: Eq + PartialEq + Copy + Clone + Hash {
     fn node_index(&self) -> usize;
 }
 
+/// Implements `NodeIndex` for an unsigned integer type narrower than `usize`,
+/// panicking if a node index doesn't fit in the type rather than wrapping.
+macro_rules! impl_node_index_for_narrow_uint {
+    ($($t:ty),*) => {
+        $(
+            impl NodeIndex for $t {
+                fn from_node_index(ni: usize) -> $t {
+                    use std::convert::TryFrom;
+
+                    <$t>::try_from(ni).unwrap_or_else(|_| {
+                        panic!(
+                            "node index overflow: the node index {} does not fit in {}, \
+                             whose maximum is {}",
+                            ni,
+                            stringify!($t),
+                            <$t>::MAX
+                        )
+                    })
+                }
+
+                fn node_index(&self) -> usize {
+                    *self as usize
+                }
+            }
+        )*
+    };
+}
+
+impl_node_index_for_narrow_uint!(u8, u16, u32);
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -400,6 +810,41 @@ mod tests {
         let _edge_value = network.edge_value(From(0), To(1));
     }
 
+    #[test]
+    fn has_edge() {
+        let network = get_network();
+
+        assert_eq!(network.has_edge(From(2), To(7)), true);
+        assert_eq!(network.has_edge(From(7), To(2)), false);
+        assert_eq!(network.has_edge(From(0), To(15)), false);
+    }
+
+    #[test]
+    fn edge_value_checked() {
+        let network = get_network();
+
+        assert_eq!(network.edge_value_checked(From(2), To(7)), Some(&30));
+        assert_eq!(network.edge_value_checked(From(7), To(2)), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "edge does not exist")]
+    fn edge_value_missing_edge() {
+        let network = get_network();
+
+        let _edge_value = network.edge_value(From(7), To(2));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "node index out of bounds: the maximum node index is 0 but the node index is 1"
+    )]
+    fn has_edge_invalid_index() {
+        let network = BooleanNetwork::<(), (), usize>::new(0);
+
+        network.has_edge(From(0), To(1));
+    }
+
     #[test]
     fn edge_value_mut() {
         let mut network = get_network();
@@ -450,8 +895,346 @@ mod tests {
         network.add_edge(From(1), To(0));
     }
 
+    #[test]
+    fn add_edge_unique() {
+        let mut network = BooleanNetwork::<(), (), usize>::new(1);
+
+        assert_eq!(network.add_edge_unique(From(0), To(1)), true);
+        assert_eq!(network.add_edge_unique(From(0), To(1)), false);
+        assert_eq!(network.ancestors(1), [0]);
+        assert_eq!(network.descendents(0), [1]);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "node index out of bounds: the maximum node index is 0 but the node index is 1"
+    )]
+    fn add_edge_unique_invalid_index() {
+        let mut network = BooleanNetwork::<(), (), usize>::new(0);
+
+        network.add_edge_unique(From(0), To(1));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(
+        expected = "edge already exists: an edge from node index 0 to node index 1 was already added"
+    )]
+    fn add_edge_duplicate() {
+        let mut network = BooleanNetwork::<(), (), usize>::new(1);
+
+        network.add_edge(From(0), To(1));
+        network.add_edge(From(0), To(1));
+    }
+
+    #[test]
+    fn remove_edge() {
+        let mut network = get_network();
+        *network.edge_value_mut(From(8), To(14)) = 40;
+
+        assert_eq!(network.remove_edge(From(7), To(14)), true);
+        assert_eq!(network.remove_edge(From(7), To(14)), false);
+        assert_eq!(network.remove_edge(From(0), To(15)), false);
+
+        assert_eq!(network.ancestors(14), [8]);
+        assert_eq!(network.descendents(7), [8, 9, 10]);
+        assert_eq!(*network.edge_value(From(8), To(14)), 40);
+        assert_eq!(network.has_edge(From(7), To(14)), false);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "node index out of bounds: the maximum node index is 0 but the node index is 1"
+    )]
+    fn remove_edge_invalid_index() {
+        let mut network = BooleanNetwork::<(), (), usize>::new(0);
+        network.remove_edge(From(0), To(1));
+    }
+
+    #[test]
+    fn remove_node() {
+        let mut network = get_network();
+        *network.edge_value_mut(From(8), To(14)) = 40;
+
+        network.remove_node(7);
+
+        assert!(network.ancestors(7).is_empty());
+        assert!(network.descendents(7).is_empty());
+        assert_eq!(*network.node_value(7), 1);
+
+        assert_eq!(network.descendents(0), [3, 5]);
+        assert_eq!(network.descendents(2), [4]);
+        assert_eq!(network.ancestors(8), [5]);
+        assert_eq!(network.ancestors(9), [6]);
+        assert_eq!(network.ancestors(10), [6]);
+        assert_eq!(network.ancestors(14), [8]);
+
+        // Edge values of the remaining edges still belong to the same edges
+        assert_eq!(*network.edge_value(From(8), To(14)), 40);
+        assert_eq!(*network.edge_value(From(10), To(15)), 31);
+        assert_eq!(network.edge_values_of(To(14)).count(), 1);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "node index out of bounds: the maximum node index is 0 but the node index is 1"
+    )]
+    fn remove_node_invalid_index() {
+        let mut network = BooleanNetwork::<(), (), usize>::new(0);
+
+        network.remove_node(1);
+    }
+
+    #[test]
+    fn nodes() {
+        let network = BooleanNetwork::<(), (), usize>::new(3);
+
+        assert_eq!(network.nodes().collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_round_trip() {
+        let network = get_network();
+
+        let json = serde_json::to_string(&network).unwrap();
+        let round_tripped: BooleanNetwork<u32, u32, usize> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(round_tripped.max_index(), network.max_index());
+        for ni in network.nodes() {
+            assert_eq!(round_tripped.ancestors(ni), network.ancestors(ni));
+            assert_eq!(round_tripped.descendents(ni), network.descendents(ni));
+            assert_eq!(round_tripped.node_value(ni), network.node_value(ni));
+        }
+        assert_eq!(*round_tripped.edge_value(From(2), To(7)), 30);
+        assert_eq!(*round_tripped.edge_value(From(10), To(15)), 31);
+    }
+
     #[test]
     fn node_count() {
         assert_eq!(get_network().node_count(), 16);
     }
+
+    #[test]
+    fn map_node_values() {
+        let network = get_network().map_node_values(|node_value| node_value * 10);
+
+        assert_equiv!(network.ancestors(8), [5, 7]);
+        assert_equiv!(network.descendents(7), [8, 9, 10, 14]);
+        assert_eq!(*network.node_value(0), 0);
+        assert_eq!(*network.node_value(5), 20);
+        assert_eq!(*network.node_value(15), 40);
+        assert_eq!(*network.edge_value(From(2), To(7)), 30);
+        assert_eq!(*network.edge_value(From(10), To(15)), 31);
+    }
+
+    #[test]
+    fn map_edge_values() {
+        let network = get_network().map_edge_values(|edge_value| (edge_value, 1));
+
+        assert_equiv!(network.ancestors(8), [5, 7]);
+        assert_equiv!(network.descendents(7), [8, 9, 10, 14]);
+        assert_eq!(*network.node_value(5), 2);
+        assert_eq!(*network.edge_value(From(2), To(7)), (30, 1));
+        assert_eq!(*network.edge_value(From(10), To(15)), (31, 1));
+        assert_eq!(*network.edge_value(From(5), To(11)), (0, 1));
+    }
+
+    #[test]
+    fn nodes_at_level() {
+        let network = get_network();
+        let depths = network
+            .nodes()
+            .map(|ni| *network.node_value(ni))
+            .collect::<Vec<_>>();
+
+        assert_equiv!(
+            &network.nodes_at_level(&depths, 0).collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+        assert_equiv!(
+            &network.nodes_at_level(&depths, 3).collect::<Vec<_>>(),
+            [8, 9, 10, 11]
+        );
+        assert_eq!(network.nodes_at_level(&depths, 5).count(), 0);
+    }
+
+    #[test]
+    fn edge_values_of() {
+        let network = get_network();
+
+        assert_equiv!(
+            &network.edge_values_of(To(7)).collect::<Vec<_>>(),
+            [(0, &0), (2, &30)]
+        );
+        assert_equiv!(
+            &network.edge_values_of(To(15)).collect::<Vec<_>>(),
+            [(10, &31), (14, &0)]
+        );
+        assert_eq!(network.edge_values_of(To(0)).count(), 0);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "node index out of bounds: the maximum node index is 0 but the node index is 1"
+    )]
+    fn edge_values_of_invalid_index() {
+        let network = BooleanNetwork::<(), (), usize>::new(0);
+
+        let _edge_values = network.edge_values_of(To(1));
+    }
+
+    #[test]
+    fn unchecked() {
+        let network = get_network();
+
+        for ni in 0..network.node_count() {
+            unsafe {
+                assert_eq!(network.ancestors_unchecked(ni), network.ancestors(ni));
+                assert_eq!(network.descendents_unchecked(ni), network.descendents(ni));
+                assert_eq!(network.node_value_unchecked(ni), network.node_value(ni));
+            }
+        }
+    }
+
+    #[test]
+    fn max_index() {
+        let network = BooleanNetwork::<(), (), usize>::new(15);
+
+        assert_eq!(network.max_index(), 15);
+        assert_eq!(network.is_valid_index(0), true);
+        assert_eq!(network.is_valid_index(15), true);
+        assert_eq!(network.is_valid_index(16), false);
+    }
+
+    #[test]
+    fn fan_in_fan_out() {
+        let network = get_network();
+
+        assert_eq!(network.fan_in(0), 0);
+        assert_eq!(network.fan_out(0), 3);
+        assert_eq!(network.fan_in(7), 2);
+        assert_eq!(network.fan_out(7), 4);
+        assert_eq!(network.fan_in(15), 2);
+        assert_eq!(network.fan_out(15), 0);
+    }
+
+    #[test]
+    fn in_degree_out_degree() {
+        let network = get_network();
+
+        for ni in 0..network.node_count() {
+            assert_eq!(network.in_degree(ni), network.fan_in(ni));
+            assert_eq!(network.out_degree(ni), network.fan_out(ni));
+        }
+        assert_eq!(network.in_degree(7), 2);
+        assert_eq!(network.out_degree(7), 4);
+    }
+
+    #[test]
+    fn max_fanin() {
+        let mut network = get_network();
+        assert_eq!(network.max_fanin(), 2);
+
+        network.add_edge(From(0), To(15));
+        assert_eq!(network.max_fanin(), 3);
+
+        let network = BooleanNetwork::<(), (), usize>::new(0);
+        assert_eq!(network.max_fanin(), 0);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "node index out of bounds: the maximum node index is 0 but the node index is 1"
+    )]
+    fn fan_in_invalid_index() {
+        let network = BooleanNetwork::<(), (), usize>::new(0);
+
+        let _fan_in = network.fan_in(1);
+    }
+
+    #[test]
+    fn adjacency_matrix() {
+        let mut network = BooleanNetwork::<(), (), usize>::new(2);
+        network.add_edge(From(0), To(1));
+        network.add_edge(From(0), To(2));
+        network.add_edge(From(2), To(1));
+
+        assert_eq!(
+            network.adjacency_matrix(),
+            vec![
+                vec![false, true, true],
+                vec![false, false, false],
+                vec![false, true, false],
+            ]
+        );
+    }
+
+    #[test]
+    fn find_cycle_acyclic() {
+        // A diamond, which reaches 3 twice without a cycle
+        let mut network = BooleanNetwork::<(), (), usize>::new(3);
+        network.add_edge(From(0), To(1));
+        network.add_edge(From(0), To(2));
+        network.add_edge(From(1), To(3));
+        network.add_edge(From(2), To(3));
+
+        assert_eq!(network.find_cycle(), None);
+    }
+
+    #[test]
+    fn find_cycle() {
+        // 0 -> 1 -> 2 -> 3 -> 1, with 4 driven by the cycle
+        let mut network = BooleanNetwork::<(), (), usize>::new(4);
+        network.add_edge(From(0), To(1));
+        network.add_edge(From(1), To(2));
+        network.add_edge(From(2), To(3));
+        network.add_edge(From(3), To(1));
+        network.add_edge(From(3), To(4));
+
+        assert_eq!(network.find_cycle(), Some(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn find_cycle_self_loop() {
+        let mut network = BooleanNetwork::<(), (), usize>::new(1);
+        network.add_edge(From(0), To(1));
+        network.add_edge(From(1), To(1));
+
+        assert_eq!(network.find_cycle(), Some(vec![1]));
+    }
+
+    #[test]
+    fn try_new_too_many_nodes() {
+        assert_eq!(
+            BooleanNetwork::<(), (), usize>::try_new(usize::MAX).err(),
+            Some(BooleanNetworkError::TooManyNodes(usize::MAX))
+        );
+        assert_eq!(
+            BooleanNetwork::<(), (), usize>::try_new(usize::MAX / 2).err(),
+            Some(BooleanNetworkError::TooManyNodes(usize::MAX / 2 + 1))
+        );
+    }
+
+    #[test]
+    fn try_new() {
+        let network = BooleanNetwork::<(), (), u8>::try_new(255).unwrap();
+
+        assert_eq!(network.node_count(), 256);
+    }
+
+    #[test]
+    fn narrow_node_index() {
+        assert_eq!(u8::from_node_index(255), 255);
+        assert_eq!(u16::from_node_index(65535).node_index(), 65535);
+        assert_eq!(u32::from_node_index(7).node_index(), 7);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "node index overflow: the node index 256 does not fit in u8, whose maximum is 255"
+    )]
+    fn narrow_node_index_overflow() {
+        let _ni = u8::from_node_index(256);
+    }
 }
@@ -0,0 +1,1444 @@
+//! Evaluation of the logic implemented by a LUT.
+
+use crate::boolean_network::NodeIndex;
+use crate::flowmap::map::LUT;
+use crate::flowmap::*;
+use crate::truth_table::TruthTable;
+use hashbrown::{HashMap, HashSet};
+
+/// The internal logic of the LUT, encoded as a recursive structure.
+#[derive(Debug, PartialEq, Clone)]
+enum LogicNode<Ni> {
+    Node(Ni),
+    And(Box<LogicNode<Ni>>, Box<LogicNode<Ni>>),
+    Inverter(Box<LogicNode<Ni>>),
+    Value(bool),
+    /// Only produced by `simplify`.
+    Xor(Box<LogicNode<Ni>>, Box<LogicNode<Ni>>),
+    /// Only produced by `simplify`.
+    Mux {
+        select: Box<LogicNode<Ni>>,
+        when_true: Box<LogicNode<Ni>>,
+        when_false: Box<LogicNode<Ni>>,
+    },
+}
+
+impl<Ni: NodeIndex> LogicNode<Ni> {
+    /// Returns the logic of a node with the provided ancestors, in terms of
+    /// those ancestors.
+    fn from_node_logic(logic: &NodeLogic, ancestors: &[Ni]) -> LogicNode<Ni> {
+        let node = |ni: Ni| Box::new(LogicNode::Node(ni));
+        let and_all = |inputs: Vec<LogicNode<Ni>>| {
+            inputs
+                .into_iter()
+                .reduce(|a, b| LogicNode::And(Box::new(a), Box::new(b)))
+                .unwrap_or(LogicNode::Value(true))
+        };
+
+        match logic {
+            NodeLogic::And => and_all(ancestors.iter().map(|ni| *node(*ni)).collect()),
+            NodeLogic::Not => {
+                assert_eq!(
+                    ancestors.len(),
+                    1,
+                    "inverter should only be driven by one node"
+                );
+
+                LogicNode::Inverter(node(ancestors[0]))
+            }
+            NodeLogic::Cover(cover) => {
+                // The OR of the cubes is the inverse of the AND of their
+                // inverses, as in an AIGER network
+                let cubes = cover
+                    .cubes
+                    .iter()
+                    .map(|cube| {
+                        let entries = cube
+                            .iter()
+                            .zip(ancestors)
+                            .filter_map(|(entry, ni)| match entry {
+                                Some(true) => Some(*node(*ni)),
+                                Some(false) => Some(LogicNode::Inverter(node(*ni))),
+                                None => None,
+                            })
+                            .collect();
+
+                        LogicNode::Inverter(Box::new(and_all(entries)))
+                    })
+                    .collect::<Vec<_>>();
+                let any_cube = if cubes.is_empty() {
+                    LogicNode::Value(false)
+                } else {
+                    LogicNode::Inverter(Box::new(and_all(cubes)))
+                };
+
+                if cover.value {
+                    any_cube
+                } else {
+                    LogicNode::Inverter(Box::new(any_cube))
+                }
+            }
+        }
+    }
+
+    /// Recursively replaces the node `n` with the specified replacement.
+    fn replace(self, n: Ni, replacement: LogicNode<Ni>) -> LogicNode<Ni> {
+        match self {
+            LogicNode::Node(ni) if ni == n => replacement,
+            LogicNode::Node(ni) => LogicNode::Node(ni),
+            LogicNode::And(input0, input1) => {
+                let input0 = Box::new(input0.replace(n, replacement.clone()));
+                let input1 = Box::new(input1.replace(n, replacement));
+
+                LogicNode::And(input0, input1)
+            }
+            LogicNode::Inverter(ln) => LogicNode::Inverter(Box::new(ln.replace(n, replacement))),
+            LogicNode::Value(v) => LogicNode::Value(v),
+            LogicNode::Xor(input0, input1) => {
+                let input0 = Box::new(input0.replace(n, replacement.clone()));
+                let input1 = Box::new(input1.replace(n, replacement));
+
+                LogicNode::Xor(input0, input1)
+            }
+            LogicNode::Mux {
+                select,
+                when_true,
+                when_false,
+            } => LogicNode::Mux {
+                select: Box::new(select.replace(n, replacement.clone())),
+                when_true: Box::new(when_true.replace(n, replacement.clone())),
+                when_false: Box::new(when_false.replace(n, replacement)),
+            },
+        }
+    }
+
+    /// Returns the nodes which remain unresolved in the logic function.
+    fn leaves(&self) -> HashSet<Ni> {
+        match self {
+            LogicNode::Node(ni) => [*ni].iter().copied().collect(),
+            LogicNode::And(input0, input1) | LogicNode::Xor(input0, input1) => {
+                let mut leaves = input0.leaves();
+                leaves.extend(input1.leaves());
+
+                leaves
+            }
+            LogicNode::Inverter(ln) => ln.leaves(),
+            LogicNode::Value(_) => HashSet::new(),
+            LogicNode::Mux {
+                select,
+                when_true,
+                when_false,
+            } => {
+                let mut leaves = select.leaves();
+                leaves.extend(when_true.leaves());
+                leaves.extend(when_false.leaves());
+
+                leaves
+            }
+        }
+    }
+
+    /// Appends postfix instructions which evaluate the logic function, with
+    /// each unresolved node read from the input at its position in `inputs`.
+    fn compile(&self, inputs: &[Ni], instructions: &mut Vec<Instruction>) {
+        match self {
+            LogicNode::Node(ni) => {
+                let i = inputs
+                    .iter()
+                    .position(|input| input == ni)
+                    .expect("unresolved node should be an input");
+                instructions.push(Instruction::Input(i));
+            }
+            LogicNode::And(input0, input1) => {
+                input0.compile(inputs, instructions);
+                input1.compile(inputs, instructions);
+                instructions.push(Instruction::And);
+            }
+            LogicNode::Inverter(ln) => {
+                ln.compile(inputs, instructions);
+                instructions.push(Instruction::Not);
+            }
+            LogicNode::Value(v) => instructions.push(Instruction::Value(*v)),
+            LogicNode::Xor(input0, input1) => {
+                input0.compile(inputs, instructions);
+                input1.compile(inputs, instructions);
+                instructions.push(Instruction::Xor);
+            }
+            LogicNode::Mux {
+                select,
+                when_true,
+                when_false,
+            } => {
+                select.compile(inputs, instructions);
+                when_true.compile(inputs, instructions);
+                when_false.compile(inputs, instructions);
+                instructions.push(Instruction::Mux);
+            }
+        }
+    }
+
+    /// Evaluates the logic function, panicing if any unspecified values (i.e.
+    /// LogicNode::Node instances) remain.
+    fn evaluate(&self) -> bool {
+        match self {
+            LogicNode::Node(_) => panic!("can't evaluate logic node with unresolved node"),
+            LogicNode::And(input0, input1) => input0.evaluate() && input1.evaluate(),
+            LogicNode::Inverter(ln) => !ln.evaluate(),
+            LogicNode::Value(v) => *v,
+            LogicNode::Xor(input0, input1) => input0.evaluate() != input1.evaluate(),
+            LogicNode::Mux {
+                select,
+                when_true,
+                when_false,
+            } => {
+                if select.evaluate() {
+                    when_true.evaluate()
+                } else {
+                    when_false.evaluate()
+                }
+            }
+        }
+    }
+
+    /// Rewrites the logic function, collapsing the AND/inverter structures
+    /// which implement XOR, XNOR and 2:1 MUX functions into `Xor` and `Mux`
+    /// nodes, and removing double inversions.
+    ///
+    /// An OR is an inverted AND of inverted inputs, so `s·t + !s·f` appears as
+    /// `!(!(s·t)·!(!s·f))`, and is a MUX selecting `t` when `s` is true. A MUX
+    /// whose inputs are each other's inverse is an XOR or XNOR, such as
+    /// `!(!(a·!b)·!(!a·b))`. XNOR is an inverted `Xor`.
+    fn simplify(self) -> LogicNode<Ni> {
+        match self {
+            LogicNode::Node(_) | LogicNode::Value(_) => self,
+            LogicNode::Inverter(ln) => ln.simplify().invert(),
+            LogicNode::And(input0, input1) => {
+                let input0 = input0.simplify();
+                let input1 = input1.simplify();
+
+                // !p·!q is the inverse of p + q, which may be a MUX
+                if let (LogicNode::Inverter(p), LogicNode::Inverter(q)) = (&input0, &input1) {
+                    if let Some(mux) = LogicNode::mux(p, q) {
+                        return mux.invert();
+                    }
+                }
+
+                LogicNode::And(Box::new(input0), Box::new(input1))
+            }
+            LogicNode::Xor(input0, input1) => LogicNode::xor(input0.simplify(), input1.simplify()),
+            LogicNode::Mux {
+                select,
+                when_true,
+                when_false,
+            } => LogicNode::Mux {
+                select: Box::new(select.simplify()),
+                when_true: Box::new(when_true.simplify()),
+                when_false: Box::new(when_false.simplify()),
+            },
+        }
+    }
+
+    /// Returns the inverse of the logic, removing a double inversion.
+    fn invert(self) -> LogicNode<Ni> {
+        match self {
+            LogicNode::Inverter(ln) => *ln,
+            ln => LogicNode::Inverter(Box::new(ln)),
+        }
+    }
+
+    /// Returns the XOR of two functions, with any inversion of the inputs
+    /// moved to the output.
+    fn xor(input0: LogicNode<Ni>, input1: LogicNode<Ni>) -> LogicNode<Ni> {
+        match (input0, input1) {
+            (LogicNode::Inverter(input0), input1) => LogicNode::xor(*input0, input1).invert(),
+            (input0, LogicNode::Inverter(input1)) => LogicNode::xor(input0, *input1).invert(),
+            (input0, input1) => LogicNode::Xor(Box::new(input0), Box::new(input1)),
+        }
+    }
+
+    /// Returns `true` if one function is the inverse of the other.
+    fn is_complement(a: &LogicNode<Ni>, b: &LogicNode<Ni>) -> bool {
+        match (a, b) {
+            (LogicNode::Inverter(a), b) => **a == *b,
+            (a, LogicNode::Inverter(b)) => *a == **b,
+            _ => false,
+        }
+    }
+
+    /// Returns `p + q` as a MUX or XOR, if `p` and `q` are the two product
+    /// terms of a MUX, i.e. `s·t` and `!s·f` in any order.
+    fn mux(p: &LogicNode<Ni>, q: &LogicNode<Ni>) -> Option<LogicNode<Ni>> {
+        let (p0, p1, q0, q1) = match (p, q) {
+            (LogicNode::And(p0, p1), LogicNode::And(q0, q1)) => (p0, p1, q0, q1),
+            _ => return None,
+        };
+
+        for (s, t) in [(p0, p1), (p1, p0)].iter() {
+            for (not_s, f) in [(q0, q1), (q1, q0)].iter() {
+                if !LogicNode::is_complement(s, not_s) {
+                    continue;
+                }
+
+                // The select is the uninverted one of s and !s
+                let (select, when_true, when_false) = match &***s {
+                    LogicNode::Inverter(select) => (select, f, t),
+                    _ => (*s, t, f),
+                };
+
+                // s ? !f : f is s XOR f
+                if LogicNode::is_complement(when_true, when_false) {
+                    return Some(LogicNode::xor((**select).clone(), (***when_false).clone()));
+                }
+
+                return Some(LogicNode::Mux {
+                    select: select.clone(),
+                    when_true: (*when_true).clone(),
+                    when_false: (*when_false).clone(),
+                });
+            }
+        }
+
+        None
+    }
+}
+
+/// Returns the logic implemented by a LUT, in terms of its inputs.
+///
+/// Every node inside the LUT must have its `logic` set by the frontend, and
+/// constants are false. A LUT whose output is a PI must have that PI as its
+/// only input.
+fn lut_logic<Ni: 'static + NodeIndex + std::fmt::Debug>(
+    network: &FlowMapBooleanNetwork<Ni>,
+    lut: &LUT<Ni>,
+) -> LogicNode<Ni> {
+    let LUT {
+        output,
+        contains,
+        inputs,
+        ..
+    } = lut;
+
+    // TODO: This is just another topo search from the output, looking at
+    // ancestors. Consider extracting this into the boolean network itself
+    let mut logic = LogicNode::Node(*output);
+
+    let mut visited = HashSet::new();
+    let mut s = vec![*output];
+    while let Some(n) = s.pop() {
+        if !visited.insert(n) {
+            continue;
+        }
+
+        let node_value = network.node_value(n);
+        if node_value.is_constant && !inputs.contains(&n) {
+            // Constants are false, as literal 0 of an AIGER network is, even
+            // when the constant is the output of the LUT itself
+            logic = logic.replace(n, LogicNode::Value(false));
+        } else if !inputs.contains(&n)
+            && !node_value.is_pi
+            && (n == *output || contains.contains(&n))
+        {
+            // Only the output and nodes inside the LUT are expanded, so any
+            // other node is left as a leaf of the logic. A PI has no logic, so
+            // a LUT whose output is a PI passes its input through.
+            let ancestors = network.ancestors(n);
+            let node_logic = network.node_value(n).logic.as_ref().unwrap_or_else(|| {
+                panic!(
+                    "node {:?} inside LUT generating {:?} has no logic",
+                    n, output
+                )
+            });
+
+            logic = logic.replace(n, LogicNode::from_node_logic(node_logic, ancestors));
+
+            for ancestor in ancestors {
+                let remaining_descendents = network
+                    .descendents(*ancestor)
+                    .iter()
+                    .filter(|ni| contains.contains(*ni))
+                    .filter(|ni| !visited.contains(ni));
+
+                if remaining_descendents.count() == 0 {
+                    s.push(*ancestor);
+                }
+            }
+        }
+    }
+
+    // Every node left unresolved must be an input, since only inputs are given
+    // values when the LUT is evaluated
+    let leaves = logic.leaves();
+    if let Some(leaf) = leaves.iter().find(|leaf| !inputs.contains(leaf)) {
+        panic!(
+            "LUT generating {:?} depends on {:?}, which is not one of its inputs {:?}",
+            output, leaf, inputs
+        );
+    }
+
+    for input in inputs {
+        assert!(
+            leaves.contains(input) || network.node_value(*input).is_pi,
+            "input {:?} to LUT generating {:?} is neither a PI nor a leaf of the LUT's logic",
+            input,
+            output
+        );
+    }
+
+    logic
+}
+
+/// The kind of function implemented by a LUT, for reporting on the functions
+/// which a mapped network uses.
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Hash)]
+pub enum LutFunction {
+    /// The output of the LUT is the XOR or XNOR of two functions of its inputs.
+    Xor,
+    /// The output of the LUT is a 2:1 MUX, or an inverted one, selecting
+    /// between two functions of its inputs.
+    Mux,
+    /// The LUT implements some other function.
+    Other,
+}
+
+/// Returns the kind of function implemented by a LUT, recognised from the
+/// structure of the logic inside it.
+pub fn lut_function<Ni: 'static + NodeIndex + std::fmt::Debug>(
+    network: &FlowMapBooleanNetwork<Ni>,
+    lut: &LUT<Ni>,
+) -> LutFunction {
+    let logic = match lut_logic(network, lut).simplify() {
+        LogicNode::Inverter(ln) => *ln,
+        ln => ln,
+    };
+
+    match logic {
+        LogicNode::Xor(_, _) => LutFunction::Xor,
+        LogicNode::Mux { .. } => LutFunction::Mux,
+        _ => LutFunction::Other,
+    }
+}
+
+/// Returns a function which can be used to determine the output value of a LUT
+/// based on the value of its inputs.
+///
+/// The inputs to the LUT must be passed to the function returned in the same
+/// order as the inputs in `lut.inputs`. Every node inside the LUT must have its
+/// `logic` set by the frontend, and constants are false.
+pub fn evaluate<'a, Ni: 'static + NodeIndex + std::fmt::Debug>(
+    network: &FlowMapBooleanNetwork<Ni>,
+    lut: &'a LUT<Ni>,
+) -> impl Fn(&[bool]) -> bool + 'a {
+    let inputs = &lut.inputs;
+    let logic = lut_logic(network, lut).simplify();
+
+    // A LUT without inputs implements a constant, which only needs to be
+    // evaluated once
+    let constant = if inputs.is_empty() {
+        Some(logic.evaluate())
+    } else {
+        None
+    };
+
+    // The logic is only compiled once, so evaluating every row of a truth table
+    // doesn't rebuild the logic for each row
+    let mut instructions = vec![];
+    logic.compile(inputs, &mut instructions);
+
+    move |input_values| {
+        if let Some(constant) = constant {
+            return constant;
+        }
+
+        assert_eq!(
+            input_values.len(),
+            inputs.len(),
+            "a value should be provided for every input"
+        );
+
+        run(&instructions, input_values)
+    }
+}
+
+/// An instruction of a compiled logic function, which operates on a stack of
+/// values.
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum Instruction {
+    /// Pushes the value of the input at the given position.
+    Input(usize),
+    /// Pushes a constant value.
+    Value(bool),
+    /// Pops two values and pushes their AND.
+    And,
+    /// Pops a value and pushes its inverse.
+    Not,
+    /// Pops two values and pushes their XOR.
+    Xor,
+    /// Pops the value when false, the value when true and the select, and
+    /// pushes the selected value.
+    Mux,
+}
+
+/// Runs compiled logic with the provided input values, returning its output.
+fn run(instructions: &[Instruction], input_values: &[bool]) -> bool {
+    let mut stack = Vec::with_capacity(instructions.len());
+
+    for instruction in instructions {
+        let value = match instruction {
+            Instruction::Input(i) => input_values[*i],
+            Instruction::Value(v) => *v,
+            Instruction::And => {
+                let input1 = stack.pop().unwrap();
+                let input0 = stack.pop().unwrap();
+                input0 && input1
+            }
+            Instruction::Not => !stack.pop().unwrap(),
+            Instruction::Xor => stack.pop().unwrap() != stack.pop().unwrap(),
+            Instruction::Mux => {
+                let when_false = stack.pop().unwrap();
+                let when_true = stack.pop().unwrap();
+                if stack.pop().unwrap() {
+                    when_true
+                } else {
+                    when_false
+                }
+            }
+        };
+
+        stack.push(value);
+    }
+
+    assert_eq!(stack.len(), 1, "compiled logic should produce one value");
+    stack[0]
+}
+
+/// Returns the truth table of a LUT, whose inputs are the LUT's inputs in the
+/// order of `lut.inputs`.
+pub fn truth_table<Ni: 'static + NodeIndex + std::fmt::Debug>(
+    network: &FlowMapBooleanNetwork<Ni>,
+    lut: &LUT<Ni>,
+) -> TruthTable {
+    TruthTable::from_fn(lut.inputs.len(), evaluate(network, lut))
+}
+
+/// Returns a function which determines the output value of a LUT from its
+/// precomputed truth table, without requiring the network.
+///
+/// The inputs of the truth table must be `inputs`, and the inputs to the LUT
+/// must be passed to the function returned in the same order.
+pub fn evaluate_from_table<'a, Ni: NodeIndex>(
+    truth_table: &'a TruthTable,
+    inputs: &'a [Ni],
+) -> impl Fn(&[bool]) -> bool + 'a {
+    assert_eq!(
+        truth_table.num_inputs(),
+        inputs.len(),
+        "truth table should have an entry for every combination of inputs"
+    );
+
+    move |input_values| truth_table.evaluate(input_values)
+}
+
+/// Evaluates a mapped network, returning the value of every PO in node index
+/// order.
+///
+/// `pi_values` holds the value of every PI which isn't a constant, in node
+/// index order. Constants are false, as literal 0 of an AIGER network is. Each
+/// LUT is evaluated at most once, using its precomputed truth table, so logic
+/// shared between POs is not evaluated repeatedly.
+pub fn evaluate_multi_output<Ni: 'static + NodeIndex>(
+    network: &FlowMapBooleanNetwork<Ni>,
+    luts: &[LUT<Ni>],
+    pi_values: &[bool],
+) -> Vec<bool> {
+    let mut values = vec![None; network.node_count()];
+
+    let pis = network.nodes().filter(|ni| network.node_value(*ni).is_pi);
+    let mut pi_values = pi_values.iter();
+    for pi in pis {
+        values[pi.node_index()] = if network.node_value(pi).is_constant {
+            Some(false)
+        } else {
+            Some(*pi_values.next().expect("a value for every PI"))
+        };
+    }
+
+    let lut_for = luts
+        .iter()
+        .map(|lut| (lut.output, lut))
+        .collect::<HashMap<_, _>>();
+
+    let pos = network
+        .nodes()
+        .filter(|ni| network.node_value(*ni).is_po)
+        .collect::<Vec<_>>();
+
+    let mut s = pos.clone();
+    while let Some(&n) = s.last() {
+        if values[n.node_index()].is_some() {
+            s.pop();
+            continue;
+        }
+
+        let lut = lut_for
+            .get(&n)
+            .expect("every node used as an input to be a PI or LUT output");
+        let pending = lut
+            .inputs
+            .iter()
+            .filter(|ni| values[ni.node_index()].is_none())
+            .copied()
+            .collect::<Vec<_>>();
+
+        if pending.is_empty() {
+            let input_values = lut
+                .inputs
+                .iter()
+                .map(|ni| values[ni.node_index()].unwrap())
+                .collect::<Vec<_>>();
+            let value = evaluate_from_table(&lut.truth_table, &lut.inputs)(&input_values);
+            values[n.node_index()] = Some(value);
+            s.pop();
+        } else {
+            s.extend(pending);
+        }
+    }
+
+    pos.iter()
+        .map(|po| values[po.node_index()].unwrap())
+        .collect()
+}
+
+/// Trait for types which can determine the function implemented by a LUT.
+pub trait EvaluateLUT<Ni: NodeIndex> {
+    /// Returns the output of the LUT for the provided input values, which are
+    /// in the same order as the LUT's inputs in `lut.inputs`.
+    fn evaluate_lut(&self, lut: &LUT<Ni>, inputs: &[bool]) -> bool;
+
+    /// Returns the truth table of the LUT, whose inputs are the LUT's inputs
+    /// in the order of `lut.inputs`.
+    fn truth_table(&self, lut: &LUT<Ni>) -> TruthTable {
+        TruthTable::from_fn(lut.inputs.len(), |inputs| self.evaluate_lut(lut, inputs))
+    }
+}
+
+impl<Ni: 'static + NodeIndex + std::fmt::Debug> EvaluateLUT<Ni> for FlowMapBooleanNetwork<Ni> {
+    fn evaluate_lut(&self, lut: &LUT<Ni>, inputs: &[bool]) -> bool {
+        evaluate(self, lut)(inputs)
+    }
+
+    fn truth_table(&self, lut: &LUT<Ni>) -> TruthTable {
+        // Only build the logic of the LUT once for the entire truth table
+        truth_table(self, lut)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boolean_network::*;
+    use crate::flowmap::builder::NetworkBuilder;
+    use crate::flowmap::{label, map};
+    use crate::frontends;
+    use aiger::Literal;
+
+    type LogicNode = super::LogicNode<Literal>;
+
+    #[test]
+    fn logic_node_replace() {
+        let logic = LogicNode::Node(Literal(2));
+
+        let logic = logic.replace(Literal(2), LogicNode::Node(Literal(4)));
+
+        assert_eq!(logic, LogicNode::Node(Literal(4)));
+    }
+
+    #[test]
+    fn logic_node_replace_missing() {
+        let logic = LogicNode::Node(Literal(2));
+
+        let logic = logic.replace(Literal(4), LogicNode::Node(Literal(6)));
+
+        assert_eq!(logic, LogicNode::Node(Literal(2)));
+    }
+
+    #[test]
+    fn logic_node_replace_value_unaffected() {
+        let logic = LogicNode::Value(false);
+
+        let logic = logic.replace(Literal(2), LogicNode::Node(Literal(4)));
+
+        assert_eq!(logic, LogicNode::Value(false));
+    }
+
+    #[test]
+    fn logic_node_replace_and() {
+        let logic = LogicNode::And(
+            Box::new(LogicNode::Node(Literal(2))),
+            Box::new(LogicNode::Node(Literal(4))),
+        );
+
+        let logic = logic.replace(Literal(2), LogicNode::Node(Literal(6)));
+
+        assert_eq!(
+            logic,
+            LogicNode::And(
+                Box::new(LogicNode::Node(Literal(6))),
+                Box::new(LogicNode::Node(Literal(4))),
+            )
+        );
+    }
+
+    #[test]
+    fn logic_node_replace_or() {
+        let logic = LogicNode::Inverter(Box::new(LogicNode::And(
+            Box::new(LogicNode::Inverter(Box::new(LogicNode::Node(Literal(2))))),
+            Box::new(LogicNode::Inverter(Box::new(LogicNode::Node(Literal(4))))),
+        )));
+
+        let logic = logic.replace(Literal(2), LogicNode::Node(Literal(6)));
+
+        assert_eq!(
+            logic,
+            LogicNode::Inverter(Box::new(LogicNode::And(
+                Box::new(LogicNode::Inverter(Box::new(LogicNode::Node(Literal(6))))),
+                Box::new(LogicNode::Inverter(Box::new(LogicNode::Node(Literal(4))))),
+            )))
+        );
+    }
+
+    #[test]
+    fn logic_node_evaluate_value() {
+        assert_eq!(LogicNode::Value(false).evaluate(), false);
+        assert_eq!(LogicNode::Value(true).evaluate(), true);
+    }
+
+    #[test]
+    fn logic_node_evaluate_inverter() {
+        assert_eq!(
+            LogicNode::Inverter(Box::new(LogicNode::Value(false))).evaluate(),
+            true
+        );
+        assert_eq!(
+            LogicNode::Inverter(Box::new(LogicNode::Value(true))).evaluate(),
+            false
+        );
+    }
+
+    #[test]
+    fn logic_node_evaluate_and() {
+        assert_eq!(
+            LogicNode::And(
+                Box::new(LogicNode::Value(false)),
+                Box::new(LogicNode::Value(false))
+            )
+            .evaluate(),
+            false
+        );
+        assert_eq!(
+            LogicNode::And(
+                Box::new(LogicNode::Value(false)),
+                Box::new(LogicNode::Value(true))
+            )
+            .evaluate(),
+            false
+        );
+        assert_eq!(
+            LogicNode::And(
+                Box::new(LogicNode::Value(true)),
+                Box::new(LogicNode::Value(false))
+            )
+            .evaluate(),
+            false
+        );
+        assert_eq!(
+            LogicNode::And(
+                Box::new(LogicNode::Value(true)),
+                Box::new(LogicNode::Value(true))
+            )
+            .evaluate(),
+            true
+        );
+    }
+
+    #[test]
+    fn logic_node_compile() {
+        // !(2 & !4)
+        let logic = LogicNode::Inverter(Box::new(LogicNode::And(
+            Box::new(LogicNode::Node(Literal(2))),
+            Box::new(LogicNode::Inverter(Box::new(LogicNode::Node(Literal(4))))),
+        )));
+
+        let mut instructions = vec![];
+        logic.compile(&[Literal(4), Literal(2)], &mut instructions);
+
+        assert_eq!(
+            instructions,
+            vec![
+                Instruction::Input(1),
+                Instruction::Input(0),
+                Instruction::Not,
+                Instruction::And,
+                Instruction::Not,
+            ]
+        );
+        assert_eq!(run(&instructions, &[false, true]), false);
+        assert_eq!(run(&instructions, &[true, true]), true);
+        assert_eq!(run(&instructions, &[false, false]), true);
+    }
+
+    fn node(l: usize) -> Box<LogicNode> {
+        Box::new(LogicNode::Node(Literal(l)))
+    }
+
+    fn not(ln: Box<LogicNode>) -> Box<LogicNode> {
+        Box::new(LogicNode::Inverter(ln))
+    }
+
+    fn and(input0: Box<LogicNode>, input1: Box<LogicNode>) -> Box<LogicNode> {
+        Box::new(LogicNode::And(input0, input1))
+    }
+
+    #[test]
+    fn logic_node_simplify_xor() {
+        // !(!(a·!b)·!(!a·b)), with the second product term swapped
+        let logic = not(and(
+            not(and(node(2), not(node(4)))),
+            not(and(node(4), not(node(2)))),
+        ));
+
+        assert_eq!(logic.simplify(), LogicNode::Xor(node(2), node(4)));
+    }
+
+    #[test]
+    fn logic_node_simplify_xnor() {
+        // !(a·b)·!(!a·!b) is XOR, so its inverse is XNOR
+        let logic = not(and(
+            not(and(node(2), node(4))),
+            not(and(not(node(2)), not(node(4)))),
+        ));
+
+        assert_eq!(
+            logic.simplify(),
+            LogicNode::Inverter(Box::new(LogicNode::Xor(node(2), node(4))))
+        );
+    }
+
+    #[test]
+    fn logic_node_simplify_mux() {
+        // s·t + !s·f, with s inverted in the first product term
+        let logic = not(and(
+            not(and(not(node(2)), node(6))),
+            not(and(node(4), node(2))),
+        ));
+
+        assert_eq!(
+            logic.simplify(),
+            LogicNode::Mux {
+                select: node(2),
+                when_true: node(4),
+                when_false: node(6),
+            }
+        );
+    }
+
+    #[test]
+    fn logic_node_simplify_unrecognised() {
+        let logic = not(and(not(and(node(2), node(4))), not(and(node(6), node(8)))));
+
+        assert_eq!(logic.clone().simplify(), *logic);
+        assert_eq!(not(not(node(2))).simplify(), *node(2));
+    }
+
+    #[test]
+    fn run_xor_and_mux() {
+        let mut instructions = vec![];
+        LogicNode::Mux {
+            select: node(2),
+            when_true: Box::new(LogicNode::Xor(node(4), node(6))),
+            when_false: node(6),
+        }
+        .compile(&[Literal(2), Literal(4), Literal(6)], &mut instructions);
+
+        assert_eq!(run(&instructions, &[true, true, false]), true);
+        assert_eq!(run(&instructions, &[true, true, true]), false);
+        assert_eq!(run(&instructions, &[false, true, false]), false);
+        assert_eq!(run(&instructions, &[false, false, true]), true);
+    }
+
+    #[test]
+    fn evaluate_single_inverter() {
+        // --2-->|~|>--3--
+        let network = NetworkBuilder::new()
+            .inverter(Literal(3), Literal(2))
+            .build();
+
+        let lut = LUT {
+            output: Literal(3),
+            contains: [Literal(3)].iter().copied().collect(),
+            inputs: vec![Literal(2)],
+            truth_table: TruthTable::default(),
+            depth: 1,
+            k_used: 1,
+        };
+        let f = evaluate(&network, &lut);
+
+        assert_eq!(f(&[false]), true);
+        assert_eq!(f(&[true]), false);
+    }
+
+    #[test]
+    fn evaluate_single_and_gate() {
+        // --2-->|&|>--6--
+        // --4-->| |
+        let network = NetworkBuilder::new()
+            .and(Literal(6), Literal(2), Literal(4))
+            .build();
+
+        let lut = LUT {
+            output: Literal(6),
+            contains: [Literal(6)].iter().copied().collect(),
+            inputs: vec![Literal(2), Literal(4)],
+            truth_table: TruthTable::default(),
+            depth: 1,
+            k_used: 2,
+        };
+        let f = evaluate(&network, &lut);
+
+        assert_eq!(f(&[false, false]), false);
+        assert_eq!(f(&[false, true]), false);
+        assert_eq!(f(&[true, false]), false);
+        assert_eq!(f(&[true, true]), true);
+    }
+
+    #[test]
+    fn evaluate_single_and_gate_single_inverted_input() {
+        // --2-->|~|>--3-->|&|>--6--
+        // --4------------>| |
+        let network = NetworkBuilder::new()
+            .inverter(Literal(3), Literal(2))
+            .and(Literal(6), Literal(3), Literal(4))
+            .build();
+
+        let lut = LUT {
+            output: Literal(6),
+            contains: [Literal(3), Literal(6)].iter().copied().collect(),
+            inputs: vec![Literal(2), Literal(4)],
+            truth_table: TruthTable::default(),
+            depth: 1,
+            k_used: 2,
+        };
+        let f = evaluate(&network, &lut);
+
+        assert_eq!(f(&[false, false]), false);
+        assert_eq!(f(&[false, true]), true);
+        assert_eq!(f(&[true, false]), false);
+        assert_eq!(f(&[true, true]), false);
+    }
+
+    #[test]
+    fn evaluate_single_and_gate_single_inverted_input_unused_output() {
+        //        8
+        //        ^
+        // --2-->|~|>--3-->|&|>--6--
+        // --4------------>| |
+        let mut network = FlowMapBooleanNetwork::new(Literal(8));
+        network.add_edge(From(Literal(2)), To(Literal(3)));
+        network.add_edge(From(Literal(3)), To(Literal(6)));
+        network.add_edge(From(Literal(3)), To(Literal(8)));
+        network.add_edge(From(Literal(4)), To(Literal(6)));
+
+        frontends::aiger::assign_logic(&mut network);
+
+        let lut = LUT {
+            output: Literal(6),
+            contains: [Literal(3), Literal(6)].iter().copied().collect(),
+            inputs: vec![Literal(2), Literal(4)],
+            truth_table: TruthTable::default(),
+            depth: 1,
+            k_used: 2,
+        };
+        let f = evaluate(&network, &lut);
+
+        assert_eq!(f(&[false, false]), false);
+        assert_eq!(f(&[false, true]), true);
+        assert_eq!(f(&[true, false]), false);
+        assert_eq!(f(&[true, true]), false);
+    }
+
+    #[test]
+    fn evaluate_and_chain_single_inverted_input() {
+        // --2-->|~|--3-->|&|>--10-->| |
+        // --4----------->| |        | |
+        //                           |&|>--14--
+        // --6----------->|&|>--12-->| |
+        // --8----------->| |        | |
+        let network = NetworkBuilder::new()
+            .inverter(Literal(3), Literal(2))
+            .and(Literal(10), Literal(3), Literal(4))
+            .and(Literal(12), Literal(6), Literal(8))
+            .and(Literal(14), Literal(10), Literal(12))
+            .build();
+
+        let lut = LUT {
+            output: Literal(14),
+            contains: [Literal(3), Literal(10), Literal(12)]
+                .iter()
+                .copied()
+                .collect(),
+            inputs: vec![Literal(2), Literal(4), Literal(6), Literal(8)],
+            truth_table: TruthTable::default(),
+            depth: 1,
+            k_used: 4,
+        };
+
+        let f = evaluate(&network, &lut);
+
+        assert_eq!(f(&[false, false, false, false]), false);
+        assert_eq!(f(&[false, false, false, true]), false);
+        assert_eq!(f(&[false, false, true, false]), false);
+        assert_eq!(f(&[false, false, true, true]), false);
+        assert_eq!(f(&[false, true, false, false]), false);
+        assert_eq!(f(&[false, true, false, true]), false);
+        assert_eq!(f(&[false, true, true, false]), false);
+        assert_eq!(f(&[false, true, true, true]), true);
+        assert_eq!(f(&[true, false, false, false]), false);
+        assert_eq!(f(&[true, false, false, true]), false);
+        assert_eq!(f(&[true, false, true, false]), false);
+        assert_eq!(f(&[true, false, true, true]), false);
+        assert_eq!(f(&[true, true, false, false]), false);
+        assert_eq!(f(&[true, true, false, true]), false);
+        assert_eq!(f(&[true, true, true, false]), false);
+        assert_eq!(f(&[true, true, true, true]), false);
+    }
+
+    #[test]
+    fn evaluate_single_or_gate() {
+        // --2-->|~|>--3-->|&|>--6-->|~|>--7--
+        // --4-->|~|>--5-->| |
+        let network = NetworkBuilder::new()
+            .inverter(Literal(3), Literal(2))
+            .and(Literal(6), Literal(3), Literal(5))
+            .inverter(Literal(5), Literal(4))
+            .inverter(Literal(7), Literal(6))
+            .build();
+
+        let lut = LUT {
+            output: Literal(7),
+            contains: [Literal(3), Literal(5), Literal(6)]
+                .iter()
+                .copied()
+                .collect(),
+            inputs: vec![Literal(2), Literal(4)],
+            truth_table: TruthTable::default(),
+            depth: 1,
+            k_used: 2,
+        };
+        let f = evaluate(&network, &lut);
+
+        assert_eq!(f(&[false, false]), false);
+        assert_eq!(f(&[false, true]), true);
+        assert_eq!(f(&[true, false]), true);
+        assert_eq!(f(&[true, true]), true);
+    }
+
+    #[test]
+    fn truth_table_single_and_gate_single_inverted_input() {
+        // --2-->|~|>--3-->|&|>--6--
+        // --4------------>| |
+        let network = NetworkBuilder::new()
+            .inverter(Literal(3), Literal(2))
+            .and(Literal(6), Literal(3), Literal(4))
+            .build();
+
+        let lut = LUT {
+            output: Literal(6),
+            contains: [Literal(3), Literal(6)].iter().copied().collect(),
+            inputs: vec![Literal(2), Literal(4)],
+            truth_table: TruthTable::default(),
+            depth: 1,
+            k_used: 2,
+        };
+
+        assert_eq!(
+            truth_table(&network, &lut),
+            TruthTable::from_entries(&[false, true, false, false])
+        );
+    }
+
+    #[test]
+    fn evaluate_from_table_matches_network() {
+        // --2-->|~|>--3-->|&|>--6--
+        // --4------------>| |
+        let network = NetworkBuilder::new()
+            .inverter(Literal(3), Literal(2))
+            .and(Literal(6), Literal(3), Literal(4))
+            .build();
+
+        let mut lut = LUT {
+            output: Literal(6),
+            contains: [Literal(3), Literal(6)].iter().copied().collect(),
+            inputs: vec![Literal(2), Literal(4)],
+            truth_table: TruthTable::default(),
+            depth: 1,
+            k_used: 2,
+        };
+        lut.truth_table = truth_table(&network, &lut);
+
+        let from_network = evaluate(&network, &lut);
+        let from_table = evaluate_from_table(&lut.truth_table, &lut.inputs);
+        for inputs in &[[false, false], [false, true], [true, false], [true, true]] {
+            assert_eq!(from_table(inputs), from_network(inputs));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "truth table should have an entry for every combination of inputs")]
+    fn evaluate_from_table_wrong_size() {
+        let truth_table = TruthTable::from_entries(&[false, true]);
+        let _f = evaluate_from_table(&truth_table, &[Literal(2), Literal(4)]);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "LUT generating Literal(6) depends on Literal(4), which is not one of its inputs"
+    )]
+    fn evaluate_missing_input() {
+        // --2-->|~|>--3-->|&|>--6--
+        // --4------------>| |
+        let network = NetworkBuilder::new()
+            .inverter(Literal(3), Literal(2))
+            .and(Literal(6), Literal(3), Literal(4))
+            .build();
+
+        let lut = LUT {
+            output: Literal(6),
+            contains: [Literal(3), Literal(6)].iter().copied().collect(),
+            inputs: vec![Literal(2)],
+            truth_table: TruthTable::default(),
+            depth: 1,
+            k_used: 1,
+        };
+
+        let _f = evaluate(&network, &lut);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "input Literal(8) to LUT generating Literal(6) is neither a PI nor a leaf"
+    )]
+    fn evaluate_input_not_leaf() {
+        // --2-->|~|>--3-->|&|>--6--
+        // --4------------>| |
+        // --8--
+        let mut network = FlowMapBooleanNetwork::new(Literal(8));
+        network.add_edge(From(Literal(2)), To(Literal(3)));
+        network.add_edge(From(Literal(3)), To(Literal(6)));
+        network.add_edge(From(Literal(4)), To(Literal(6)));
+
+        frontends::aiger::assign_logic(&mut network);
+
+        let lut = LUT {
+            output: Literal(6),
+            contains: [Literal(3), Literal(6)].iter().copied().collect(),
+            inputs: vec![Literal(2), Literal(4), Literal(8)],
+            truth_table: TruthTable::default(),
+            depth: 1,
+            k_used: 3,
+        };
+
+        let _f = evaluate(&network, &lut);
+    }
+
+    #[test]
+    fn evaluate_lut_trait() {
+        // --2-->|~|>--3-->|&|>--6--
+        // --4------------>| |
+        let network = NetworkBuilder::new()
+            .inverter(Literal(3), Literal(2))
+            .and(Literal(6), Literal(3), Literal(4))
+            .build();
+
+        let lut = LUT {
+            output: Literal(6),
+            contains: [Literal(3), Literal(6)].iter().copied().collect(),
+            inputs: vec![Literal(2), Literal(4)],
+            truth_table: TruthTable::default(),
+            depth: 1,
+            k_used: 2,
+        };
+
+        assert_eq!(network.evaluate_lut(&lut, &[false, true]), true);
+        assert_eq!(network.evaluate_lut(&lut, &[true, true]), false);
+        assert_eq!(
+            EvaluateLUT::truth_table(&network, &lut),
+            TruthTable::from_entries(&[false, true, false, false])
+        );
+    }
+
+    #[test]
+    fn evaluate_node_logic() {
+        // Node 2 is a XOR b as a cover, and node 3 is node 2 AND NOT b
+        let mut network = FlowMapBooleanNetwork::<usize>::new(3);
+        network.add_edge(From(0), To(2));
+        network.add_edge(From(1), To(2));
+        network.add_edge(From(2), To(3));
+        network.add_edge(From(1), To(3));
+        network.node_value_mut(2).logic = Some(NodeLogic::Cover(Cover {
+            cubes: vec![vec![Some(true), Some(false)], vec![Some(false), Some(true)]],
+            value: true,
+        }));
+        network.node_value_mut(3).logic = Some(NodeLogic::Cover(Cover {
+            cubes: vec![vec![Some(false), None], vec![None, Some(true)]],
+            value: false,
+        }));
+
+        let lut = LUT {
+            output: 3,
+            contains: [2, 3].iter().copied().collect(),
+            inputs: vec![0, 1],
+            truth_table: TruthTable::default(),
+            depth: 1,
+            k_used: 2,
+        };
+
+        assert_eq!(
+            truth_table(&network, &lut),
+            TruthTable::from_entries(&[false, false, true, false])
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "node 2 inside LUT generating 2 has no logic")]
+    fn evaluate_no_logic() {
+        let mut network = FlowMapBooleanNetwork::<usize>::new(2);
+        network.add_edge(From(0), To(2));
+        network.add_edge(From(1), To(2));
+
+        let lut = LUT {
+            output: 2,
+            contains: [2].iter().copied().collect(),
+            inputs: vec![0, 1],
+            truth_table: TruthTable::default(),
+            depth: 1,
+            k_used: 2,
+        };
+
+        evaluate(&network, &lut)(&[false, false]);
+    }
+
+    #[test]
+    fn evaluate_multi_output_half_adder() {
+        // c = x & y (literal 6), s = x ^ y (literal 10)
+        let reader = aiger::Reader::from_reader(
+            "aag 5 2 0 2 3\n2\n4\n10\n6\n6 2 4\n8 3 5\n10 7 9\n".as_bytes(),
+        )
+        .unwrap();
+        let network = frontends::aiger::from_reader(reader).unwrap();
+        let network = label::label_network(network, 2);
+        let luts = map::map(&network, 2, &*network).unwrap();
+
+        assert_eq!(
+            evaluate_multi_output(&network, &luts, &[false, false]),
+            vec![false, false]
+        );
+        assert_eq!(
+            evaluate_multi_output(&network, &luts, &[false, true]),
+            vec![false, true]
+        );
+        assert_eq!(
+            evaluate_multi_output(&network, &luts, &[true, false]),
+            vec![false, true]
+        );
+        assert_eq!(
+            evaluate_multi_output(&network, &luts, &[true, true]),
+            vec![true, false]
+        );
+    }
+
+    #[test]
+    fn lut_function_half_adder() {
+        let reader = aiger::Reader::from_reader(
+            "aag 5 2 0 2 3\n2\n4\n10\n6\n6 2 4\n8 3 5\n10 7 9\n".as_bytes(),
+        )
+        .unwrap();
+        let network = frontends::aiger::from_reader(reader).unwrap();
+        let network = label::label_network(network, 2);
+        let luts = map::map(&network, 2, &*network).unwrap();
+
+        let function_of = |output: usize| {
+            let lut = luts.iter().find(|lut| lut.output == Literal(output));
+            lut_function(&network, lut.unwrap())
+        };
+        assert_eq!(function_of(10), LutFunction::Xor);
+        assert_eq!(function_of(6), LutFunction::Other);
+    }
+
+    #[test]
+    fn lut_function_mux() {
+        // 12 = !(!(s·t)·!(!s·f)), with s = 2, t = 4 and f = 6
+        let reader = aiger::Reader::from_reader(
+            "aag 6 3 0 1 3\n2\n4\n6\n13\n8 2 4\n10 3 6\n12 9 11\n".as_bytes(),
+        )
+        .unwrap();
+        let network = frontends::aiger::from_reader(reader).unwrap();
+        let network = label::label_network(network, 3);
+        let luts = map::map(&network, 3, &*network).unwrap();
+
+        assert_eq!(luts.len(), 1);
+        assert_eq!(lut_function(&network, &luts[0]), LutFunction::Mux);
+    }
+
+    #[test]
+    fn evaluate_pack_area_nand3() {
+        // !(x & y & z), mapped with K = 2 and then packed into 3-input LUTs
+        let reader =
+            aiger::Reader::from_reader("aag 5 3 0 1 2\n2\n4\n6\n11\n8 2 4\n10 8 6\n".as_bytes())
+                .unwrap();
+        let network = frontends::aiger::from_reader(reader).unwrap();
+        let network = label::label_network(network, 2);
+        let luts = map::map(&network, 2, &*network).unwrap();
+        let packed = map::pack_area(&network, luts.clone(), 3, &*network);
+
+        assert_eq!(luts.len(), 2);
+        assert_eq!(packed.len(), 1);
+
+        for i in 0..8 {
+            let pi_values = (0..3).map(|bit| i & (1 << bit) != 0).collect::<Vec<_>>();
+
+            assert_eq!(
+                evaluate_multi_output(&network, &packed, &pi_values),
+                evaluate_multi_output(&network, &luts, &pi_values)
+            );
+        }
+    }
+
+    #[test]
+    fn evaluate_no_inputs_constant_true() {
+        // 0 (false) -->|~|>--1--
+        let network = NetworkBuilder::new()
+            .constant(Literal(0))
+            .inverter(Literal(1), Literal(0))
+            .build();
+
+        let lut = LUT {
+            output: Literal(1),
+            contains: [Literal(1)].iter().copied().collect(),
+            inputs: vec![],
+            truth_table: TruthTable::default(),
+            depth: 1,
+            k_used: 0,
+        };
+
+        assert_eq!(evaluate(&network, &lut)(&[]), true);
+        assert_eq!(
+            truth_table(&network, &lut),
+            TruthTable::from_entries(&[true])
+        );
+    }
+
+    #[test]
+    fn evaluate_no_inputs_constant_false() {
+        // 0 (false) -->|~|>--1-->|&|>--6--
+        //     \---------------->| |
+        let network = NetworkBuilder::new()
+            .constant(Literal(0))
+            .inverter(Literal(1), Literal(0))
+            .and(Literal(6), Literal(0), Literal(1))
+            .build();
+
+        let lut = LUT {
+            output: Literal(6),
+            contains: [Literal(1), Literal(6)].iter().copied().collect(),
+            inputs: vec![],
+            truth_table: TruthTable::default(),
+            depth: 1,
+            k_used: 0,
+        };
+
+        assert_eq!(evaluate(&network, &lut)(&[]), false);
+        assert_eq!(
+            truth_table(&network, &lut),
+            TruthTable::from_entries(&[false])
+        );
+    }
+
+    #[test]
+    fn evaluate_pass_through() {
+        // 2 is both a PI and a PO, so its LUT is a wire from the PI
+        let network = NetworkBuilder::new()
+            .constant(Literal(0))
+            .input(Literal(2))
+            .output(Literal(2))
+            .build();
+
+        let lut = LUT {
+            output: Literal(2),
+            contains: [Literal(2)].iter().copied().collect(),
+            inputs: vec![Literal(2)],
+            truth_table: TruthTable::default(),
+            depth: 1,
+            k_used: 1,
+        };
+
+        assert_eq!(evaluate(&network, &lut)(&[false]), false);
+        assert_eq!(evaluate(&network, &lut)(&[true]), true);
+        assert_eq!(
+            truth_table(&network, &lut),
+            TruthTable::from_entries(&[false, true])
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "LUT generating Literal(2) depends on Literal(2), which is not one of its inputs []"
+    )]
+    fn evaluate_pass_through_missing_input() {
+        let network = NetworkBuilder::new()
+            .constant(Literal(0))
+            .input(Literal(2))
+            .output(Literal(2))
+            .build();
+
+        let lut = LUT {
+            output: Literal(2),
+            contains: [Literal(2)].iter().copied().collect(),
+            inputs: vec![],
+            truth_table: TruthTable::default(),
+            depth: 1,
+            k_used: 0,
+        };
+
+        let _ = evaluate(&network, &lut);
+    }
+
+    #[test]
+    fn evaluate_constant_output() {
+        // 0 (false) -->|~|>--1--
+        //     \---------------->|&|>--4--
+        let network = NetworkBuilder::new()
+            .constant(Literal(0))
+            .inverter(Literal(1), Literal(0))
+            .and(Literal(4), Literal(0), Literal(0))
+            .output(Literal(0))
+            .output(Literal(1))
+            .output(Literal(4))
+            .build();
+
+        for (output, expected) in &[(0, false), (1, true), (4, false)] {
+            let lut = LUT {
+                output: Literal(*output),
+                contains: [Literal(*output)].iter().copied().collect(),
+                inputs: vec![],
+                truth_table: TruthTable::default(),
+                depth: 1,
+                k_used: 0,
+            };
+
+            assert_eq!(evaluate(&network, &lut)(&[]), *expected);
+            assert_eq!(
+                truth_table(&network, &lut),
+                TruthTable::from_entries(&[*expected])
+            );
+        }
+    }
+}
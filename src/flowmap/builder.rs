@@ -0,0 +1,181 @@
+use super::*;
+
+/// Builds a network node by node, for logic which doesn't come from a file.
+///
+/// Nodes are created as they're mentioned, so the network built has every node
+/// up to the largest index used. Each method sets the flags, label, edges and
+/// logic that a frontend would, so the network is ready to be labelled and
+/// mapped.
+#[derive(Debug, Clone)]
+pub struct NetworkBuilder<Ni> {
+    max_index: usize,
+    inputs: Vec<Ni>,
+    constants: Vec<Ni>,
+    outputs: Vec<Ni>,
+    /// The nodes driven by logic, with their ancestors in order.
+    gates: Vec<(Ni, Vec<Ni>, NodeLogic)>,
+}
+
+impl<Ni: 'static + NodeIndex> NetworkBuilder<Ni> {
+    /// Creates a builder for an empty network.
+    pub fn new() -> NetworkBuilder<Ni> {
+        NetworkBuilder {
+            max_index: 0,
+            inputs: vec![],
+            constants: vec![],
+            outputs: vec![],
+            gates: vec![],
+        }
+    }
+
+    /// Records that a node exists, so that the network is large enough for it.
+    fn mention(&mut self, ni: Ni) {
+        self.max_index = std::cmp::max(self.max_index, ni.node_index());
+    }
+
+    /// Adds a PI.
+    pub fn input(&mut self, ni: Ni) -> &mut Self {
+        self.mention(ni);
+        self.inputs.push(ni);
+
+        self
+    }
+
+    /// Adds a constant false node, which is a PI that's never exposed as an
+    /// input, as literal 0 of an AIGER network is.
+    pub fn constant(&mut self, ni: Ni) -> &mut Self {
+        self.mention(ni);
+        self.constants.push(ni);
+
+        self
+    }
+
+    /// Marks a node as a PO.
+    pub fn output(&mut self, ni: Ni) -> &mut Self {
+        self.mention(ni);
+        self.outputs.push(ni);
+
+        self
+    }
+
+    /// Adds an AND gate driving `out`, whose inputs are `a` and `b` in that
+    /// order. If `a` and `b` are the same node, the gate is a buffer of it.
+    pub fn and(&mut self, out: Ni, a: Ni, b: Ni) -> &mut Self {
+        self.mention(out);
+        self.mention(a);
+        self.mention(b);
+        self.gates.push((out, vec![a, b], NodeLogic::And));
+
+        self
+    }
+
+    /// Adds an inverter driving `out` from `a`.
+    pub fn inverter(&mut self, out: Ni, a: Ni) -> &mut Self {
+        self.mention(out);
+        self.mention(a);
+        self.gates.push((out, vec![a], NodeLogic::Not));
+
+        self
+    }
+
+    /// Builds the network.
+    pub fn build(&self) -> FlowMapBooleanNetwork<Ni> {
+        let mut network = FlowMapBooleanNetwork::new(Ni::from_node_index(self.max_index));
+
+        for ni in &self.inputs {
+            network.node_value_mut(*ni).label = Some(0);
+            network.node_value_mut(*ni).is_pi = true;
+        }
+
+        for ni in &self.constants {
+            network.node_value_mut(*ni).label = Some(0);
+            network.node_value_mut(*ni).is_pi = true;
+            network.node_value_mut(*ni).is_constant = true;
+        }
+
+        for (out, ancestors, logic) in &self.gates {
+            // An AND gate of a node with itself has a single edge from it
+            for ancestor in ancestors {
+                network.add_edge_unique(From(*ancestor), To(*out));
+            }
+            network.node_value_mut(*out).logic = Some(logic.clone());
+        }
+
+        for ni in &self.outputs {
+            network.node_value_mut(*ni).is_po = true;
+        }
+
+        network
+    }
+}
+
+impl<Ni: 'static + NodeIndex> Default for NetworkBuilder<Ni> {
+    fn default() -> Self {
+        NetworkBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluate::EvaluateLUT;
+    use crate::flowmap::{label, map};
+
+    #[test]
+    fn build_flags_and_edges() {
+        let network = NetworkBuilder::<usize>::new()
+            .constant(0)
+            .input(1)
+            .input(2)
+            .and(3, 1, 2)
+            .inverter(4, 3)
+            .and(5, 0, 4)
+            .output(4)
+            .output(5)
+            .build();
+
+        assert_eq!(network.node_count(), 6);
+        assert_eq!(network.node_value(0).is_constant, true);
+        assert_eq!(network.node_value(0).is_pi, true);
+        assert_eq!(network.node_value(1).is_pi, true);
+        assert_eq!(network.node_value(1).label, Some(0));
+        assert_eq!(network.node_value(1).is_constant, false);
+        assert_eq!(network.ancestors(3), &[1, 2]);
+        assert_eq!(network.ancestors(4), &[3]);
+        assert_eq!(network.ancestors(5), &[0, 4]);
+        assert_eq!(network.node_value(3).logic, Some(NodeLogic::And));
+        assert_eq!(network.node_value(4).logic, Some(NodeLogic::Not));
+        assert_eq!(network.node_value(4).is_po, true);
+        assert_eq!(network.node_value(5).is_po, true);
+        assert_eq!(network.node_value(3).is_po, false);
+    }
+
+    #[test]
+    fn build_then_map() {
+        // y = !(a & b) & c
+        let network = NetworkBuilder::<usize>::new()
+            .input(0)
+            .input(1)
+            .input(2)
+            .and(3, 0, 1)
+            .inverter(4, 3)
+            .and(5, 4, 2)
+            .output(5)
+            .build();
+
+        let network = label::label_network(network, 3);
+        let luts = map::map(&network, 3, &*network).unwrap();
+
+        assert_eq!(luts.len(), 1);
+        let lut = &luts[0];
+        for i in 0..8 {
+            let values = [i & 4 != 0, i & 2 != 0, i & 1 != 0];
+            let inputs = lut.inputs.iter().map(|ni| values[*ni]).collect::<Vec<_>>();
+
+            assert_eq!(
+                network.evaluate_lut(lut, &inputs),
+                !(values[0] && values[1]) && values[2]
+            );
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use super::label::TopologicalOrder;
+use super::*;
+
+/// The logic of a node after folding the constants which drive it.
+enum Folded<Ni> {
+    /// The node always has the given value.
+    Constant(bool),
+    /// The node is driven by the given ancestors, in order, with the given
+    /// logic.
+    Logic(Vec<Ni>, NodeLogic),
+}
+
+/// Folds every constant in the network into the logic of the nodes it drives,
+/// so that no node is driven by a constant unless the node is itself constant.
+///
+/// Constant nodes are PIs which are always false, such as literal 0 of an
+/// AIGER network. A node which is found to be constant, e.g. an AND gate with a
+/// false input, becomes a buffer of a constant node if it's always false or an
+/// inverter of it if it's always true. It's then removed from the ancestors of
+/// the nodes it drives, whose logic is simplified in turn.
+///
+/// A node left as an AND of a single ancestor is a buffer, so the nodes it
+/// drives are rewired to use that ancestor directly, unless they're already
+/// driven by it. Folded nodes which no longer drive anything, and which aren't
+/// a PO or the next state of a latch, are left without any edges.
+pub fn propagate_constants<Ni: 'static + NodeIndex>(network: &mut FlowMapBooleanNetwork<Ni>) {
+    let constant = match network
+        .nodes()
+        .find(|ni| network.node_value(*ni).is_constant)
+    {
+        Some(constant) => constant,
+        None => return,
+    };
+
+    let mut values = vec![None; network.node_count()];
+    for ni in network.nodes() {
+        if network.node_value(ni).is_constant {
+            values[ni.node_index()] = Some(false);
+        }
+    }
+
+    // The node each node's descendents should use in its place, which is the
+    // node itself unless it's a buffer
+    let mut sources = network.nodes().collect::<Vec<_>>();
+
+    // Rewiring a node changes which nodes are ready next, so find the order
+    // before changing anything
+    let mut order = vec![];
+    let mut topo = TopologicalOrder::new(network);
+    while let Some(ni) = topo.next(network) {
+        order.push(ni);
+    }
+
+    let mut folded = vec![];
+    for ni in order {
+        let node_value = network.node_value(ni);
+        let logic = match &node_value.logic {
+            Some(logic) if !node_value.is_pi => logic.clone(),
+            _ => continue,
+        };
+
+        let ancestors = network.ancestors(ni).to_vec();
+        match fold(&ancestors, logic.clone(), &values, &sources) {
+            Folded::Constant(value) => {
+                let logic = if value {
+                    NodeLogic::Not
+                } else {
+                    NodeLogic::And
+                };
+                set_logic(network, ni, &[constant], logic);
+
+                values[ni.node_index()] = Some(value);
+                folded.push(ni);
+            }
+            Folded::Logic(new_ancestors, new_logic) => {
+                if let (NodeLogic::And, [source]) = (&new_logic, new_ancestors.as_slice()) {
+                    sources[ni.node_index()] = *source;
+                    folded.push(ni);
+                }
+
+                if new_ancestors != ancestors || new_logic != logic {
+                    set_logic(network, ni, &new_ancestors, new_logic);
+                }
+            }
+        }
+    }
+
+    let roots = network.mapping_roots();
+    for ni in folded.into_iter().rev() {
+        if network.descendents(ni).is_empty() && !roots.contains(&ni) {
+            network.remove_node(ni);
+        }
+    }
+}
+
+/// Returns the logic of a node after substituting the value of each constant
+/// ancestor, and replacing each buffer with the node it buffers.
+fn fold<Ni: NodeIndex>(
+    ancestors: &[Ni],
+    logic: NodeLogic,
+    values: &[Option<bool>],
+    sources: &[Ni],
+) -> Folded<Ni> {
+    // A buffer can't be replaced if the node it buffers already drives the node
+    // being folded, since parallel edges aren't allowed
+    let mut inputs = vec![];
+    let input = |ni: Ni, inputs: &mut Vec<Ni>| {
+        let source = sources[ni.node_index()];
+        if source != ni && (ancestors.contains(&source) || inputs.contains(&source)) {
+            inputs.push(ni);
+        } else {
+            inputs.push(source);
+        }
+    };
+
+    match logic {
+        NodeLogic::And => {
+            for ni in ancestors {
+                match values[ni.node_index()] {
+                    Some(false) => return Folded::Constant(false),
+                    Some(true) => {}
+                    // An AND of a node with itself is the node, so a buffer
+                    // of a node already driving this one can be dropped
+                    None if inputs.contains(&sources[ni.node_index()])
+                        || (sources[ni.node_index()] != *ni
+                            && ancestors.contains(&sources[ni.node_index()])) => {}
+                    None => input(*ni, &mut inputs),
+                }
+            }
+
+            if inputs.is_empty() {
+                Folded::Constant(true)
+            } else {
+                Folded::Logic(inputs, NodeLogic::And)
+            }
+        }
+        NodeLogic::Not => match values[ancestors[0].node_index()] {
+            Some(value) => Folded::Constant(!value),
+            None => {
+                input(ancestors[0], &mut inputs);
+
+                Folded::Logic(inputs, NodeLogic::Not)
+            }
+        },
+        NodeLogic::Cover(mut cover) => {
+            // Only the cubes which match the value of every constant can ever
+            // match, and the entries for constants are then always satisfied
+            let mut columns = vec![];
+            for (j, ni) in ancestors.iter().enumerate() {
+                match values[ni.node_index()] {
+                    Some(value) => cover.cubes.retain(|cube| cube[j] != Some(!value)),
+                    None => {
+                        columns.push(j);
+                        input(*ni, &mut inputs);
+                    }
+                }
+            }
+
+            for cube in &mut cover.cubes {
+                *cube = columns.iter().map(|j| cube[*j]).collect();
+            }
+
+            if cover.cubes.is_empty() {
+                Folded::Constant(!cover.value)
+            } else if cover
+                .cubes
+                .iter()
+                .any(|cube| cube.iter().all(Option::is_none))
+            {
+                Folded::Constant(cover.value)
+            } else {
+                Folded::Logic(inputs, NodeLogic::Cover(cover))
+            }
+        }
+    }
+}
+
+/// Replaces the ancestors and logic of a node.
+fn set_logic<Ni: 'static + NodeIndex>(
+    network: &mut FlowMapBooleanNetwork<Ni>,
+    ni: Ni,
+    ancestors: &[Ni],
+    logic: NodeLogic,
+) {
+    for ancestor in network.ancestors(ni).to_vec() {
+        network.remove_edge(From(ancestor), To(ni));
+    }
+
+    for ancestor in ancestors {
+        network.add_edge(From(*ancestor), To(ni));
+    }
+
+    network.node_value_mut(ni).logic = Some(logic);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flowmap::builder::NetworkBuilder;
+    use crate::frontends::aiger::{self, AIG, FALSE};
+    use crate::frontends::blif;
+    use ::aiger::{Literal, Reader};
+
+    fn aig_from_str(s: &str) -> AIG {
+        aiger::from_reader(Reader::from_reader(s.as_bytes()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn propagate_constants_false_input() {
+        let mut network = aig_from_str("aag 4 2 0 1 2\n2\n4\n8\n6 0 4\n8 6 2\n");
+
+        propagate_constants(&mut network);
+
+        assert_eq!(network.ancestors(Literal(8)), [FALSE]);
+        assert_eq!(network.node_value(Literal(8)).logic, Some(NodeLogic::And));
+        assert!(network.ancestors(Literal(6)).is_empty());
+        assert!(network.descendents(Literal(6)).is_empty());
+    }
+
+    #[test]
+    fn propagate_constants_true_input() {
+        let mut network = aig_from_str("aag 4 2 0 1 2\n2\n4\n8\n6 1 4\n8 6 2\n");
+
+        propagate_constants(&mut network);
+
+        assert_eq!(network.ancestors(Literal(8)), [Literal(4), Literal(2)]);
+        assert_eq!(network.node_value(Literal(8)).logic, Some(NodeLogic::And));
+        assert!(network.ancestors(Literal(6)).is_empty());
+        assert!(network.descendents(Literal(6)).is_empty());
+    }
+
+    #[test]
+    fn propagate_constants_chain() {
+        // 6 is false, so its inverter 7 is true, 8 is a buffer of 2 and 10 is
+        // an AND of 2 and 4
+        let mut network = aig_from_str("aag 5 2 0 2 3\n2\n4\n10\n7\n6 0 4\n8 7 2\n10 8 4\n");
+
+        propagate_constants(&mut network);
+
+        assert_eq!(network.ancestors(Literal(10)), [Literal(2), Literal(4)]);
+        assert!(network.ancestors(Literal(8)).is_empty());
+
+        // A constant PO is still driven by the constant
+        assert_eq!(network.ancestors(Literal(7)), [FALSE]);
+        assert_eq!(network.node_value(Literal(7)).logic, Some(NodeLogic::Not));
+        assert!(network.descendents(Literal(7)).is_empty());
+    }
+
+    #[test]
+    fn propagate_constants_buffer_of_existing_input() {
+        // 6 is a buffer of 2, which already drives 8
+        let mut network = aig_from_str("aag 4 1 0 1 2\n2\n8\n6 1 2\n8 6 2\n");
+
+        propagate_constants(&mut network);
+
+        assert_eq!(network.ancestors(Literal(8)), [Literal(2)]);
+    }
+
+    #[test]
+    fn propagate_constants_cover() {
+        let mut network = blif::from_reader(
+            ".model c\n.inputs a\n.outputs x y\n.names c\n.names a c x\n10 1\n01 1\n.names a c y\n1- 1\n-0 1\n.end\n"
+                .as_bytes(),
+        )
+        .unwrap();
+
+        propagate_constants(&mut network);
+
+        let node = |symbol: &str| {
+            network
+                .nodes()
+                .find(|ni| network.node_value(*ni).symbol.as_deref() == Some(symbol))
+                .unwrap()
+        };
+        let (a, x, y) = (node("a"), node("x"), node("y"));
+
+        // x is an XOR with false, so it's a buffer of a
+        assert_eq!(network.ancestors(x), [a]);
+        assert_eq!(
+            network.node_value(x).logic,
+            Some(NodeLogic::Cover(Cover {
+                cubes: vec![vec![Some(true)]],
+                value: true,
+            }))
+        );
+
+        // y is an OR with true, so it's always true
+        assert_eq!(network.ancestors(y), [0]);
+        assert_eq!(network.node_value(y).logic, Some(NodeLogic::Not));
+    }
+
+    #[test]
+    fn propagate_constants_no_constants() {
+        let mut network = NetworkBuilder::<usize>::new()
+            .input(0)
+            .input(1)
+            .and(2, 0, 1)
+            .output(2)
+            .build();
+
+        propagate_constants(&mut network);
+
+        assert_eq!(network.ancestors(2), [0, 1]);
+    }
+}
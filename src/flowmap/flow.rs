@@ -1,8 +1,9 @@
 use super::*;
 use crate::boolean_network::*;
 use hashbrown::HashSet;
+use std::collections::VecDeque;
 use std::iter;
-use std::marker::PhantomData;
+use std::mem;
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone, Hash)]
 enum Position<Ni: NodeIndex> {
@@ -18,38 +19,62 @@ struct Visited<Ni: 'static + NodeIndex> {
     sink: bool,
     before: Vec<bool>,
     after: Vec<bool>,
-    phantom: PhantomData<Ni>,
+    /// The nodes marked as visited since the last clear.
+    touched: Vec<Position<Ni>>,
 }
 
 impl<Ni: 'static + NodeIndex> Visited<Ni> {
     fn new(node_count: usize) -> Visited<Ni> {
-        let after = iter::repeat(false).take(node_count).collect::<Vec<_>>();
+        let after = iter::repeat_n(false, node_count).collect::<Vec<_>>();
 
         Visited {
             source: false,
             sink: false,
             before: after.clone(),
             after,
-            phantom: PhantomData,
+            touched: vec![],
         }
     }
 
-    /// Mark a node as visited. Returns `true` if the node has not been visited
-    /// before, or `false` if it was already marked as visited.
-    fn insert(&mut self, node: Position<Ni>) -> bool {
-        let visited_ref = match node {
+    fn get_mut(&mut self, node: Position<Ni>) -> &mut bool {
+        match node {
             Position::Source => &mut self.source,
             Position::Sink => &mut self.sink,
             Position::BeforeNode(ni) => &mut self.before[ni.node_index()],
             Position::AfterNode(ni) => &mut self.after[ni.node_index()],
-        };
+        }
+    }
+
+    /// Mark a node as visited. Returns `true` if the node has not been visited
+    /// before, or `false` if it was already marked as visited.
+    fn insert(&mut self, node: Position<Ni>) -> bool {
+        let visited_ref = self.get_mut(node);
 
         let old = *visited_ref;
         *visited_ref = true;
 
+        if !old {
+            self.touched.push(node);
+        }
+
         !old
     }
 
+    /// Marks every node as unvisited, growing to fit `node_count` nodes if
+    /// needed. Only the nodes visited since the last clear are reset.
+    fn clear(&mut self, node_count: usize) {
+        let mut touched = mem::take(&mut self.touched);
+        for node in touched.drain(..) {
+            *self.get_mut(node) = false;
+        }
+        self.touched = touched;
+
+        if self.after.len() < node_count {
+            self.before.resize(node_count, false);
+            self.after.resize(node_count, false);
+        }
+    }
+
     /// Returns `true` if the node has been marked as visited.
     fn contains(&self, node: Position<Ni>) -> bool {
         match node {
@@ -61,6 +86,74 @@ impl<Ni: 'static + NodeIndex> Visited<Ni> {
     }
 }
 
+/// The level of each node in the level graph of the residual network, i.e. the
+/// length of the shortest path from the source to the node.
+#[derive(Debug)]
+struct Levels<Ni: 'static + NodeIndex> {
+    source: Option<u32>,
+    sink: Option<u32>,
+    before: Vec<Option<u32>>,
+    after: Vec<Option<u32>>,
+    /// The nodes given a level since the last clear.
+    touched: Vec<Position<Ni>>,
+}
+
+impl<Ni: 'static + NodeIndex> Levels<Ni> {
+    fn new(node_count: usize) -> Levels<Ni> {
+        let after = iter::repeat_n(None, node_count).collect::<Vec<_>>();
+
+        Levels {
+            source: None,
+            sink: None,
+            before: after.clone(),
+            after,
+            touched: vec![],
+        }
+    }
+
+    fn get_mut(&mut self, node: Position<Ni>) -> &mut Option<u32> {
+        match node {
+            Position::Source => &mut self.source,
+            Position::Sink => &mut self.sink,
+            Position::BeforeNode(ni) => &mut self.before[ni.node_index()],
+            Position::AfterNode(ni) => &mut self.after[ni.node_index()],
+        }
+    }
+
+    /// Returns the level of the node, or `None` if it is unreachable from the
+    /// source.
+    fn get(&self, node: Position<Ni>) -> Option<u32> {
+        match node {
+            Position::Source => self.source,
+            Position::Sink => self.sink,
+            Position::BeforeNode(ni) => self.before[ni.node_index()],
+            Position::AfterNode(ni) => self.after[ni.node_index()],
+        }
+    }
+
+    /// Sets the level of the node.
+    fn set(&mut self, node: Position<Ni>, level: u32) {
+        if self.get_mut(node).replace(level).is_none() {
+            self.touched.push(node);
+        }
+    }
+
+    /// Makes every node unreachable, growing to fit `node_count` nodes if
+    /// needed. Only the nodes given a level since the last clear are reset.
+    fn clear(&mut self, node_count: usize) {
+        let mut touched = mem::take(&mut self.touched);
+        for node in touched.drain(..) {
+            *self.get_mut(node) = None;
+        }
+        self.touched = touched;
+
+        if self.after.len() < node_count {
+            self.before.resize(node_count, None);
+            self.after.resize(node_count, None);
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 struct PathStep<Ni: NodeIndex> {
     from: Position<Ni>,
@@ -73,17 +166,29 @@ struct Path<Ni: NodeIndex> {
     sink: Option<Position<Ni>>,
     before: Vec<Option<Position<Ni>>>,
     after: Vec<Option<Position<Ni>>>,
+    /// The nodes given a "from" node since the last clear.
+    touched: Vec<Position<Ni>>,
 }
 
 impl<Ni: NodeIndex + std::fmt::Debug> Path<Ni> {
     fn new(node_count: usize) -> Path<Ni> {
-        let after = iter::repeat(None).take(node_count).collect::<Vec<_>>();
+        let after = iter::repeat_n(None, node_count).collect::<Vec<_>>();
 
         Path {
             source: None,
             sink: None,
             before: after.clone(),
             after,
+            touched: vec![],
+        }
+    }
+
+    fn get_mut(&mut self, to: Position<Ni>) -> &mut Option<Position<Ni>> {
+        match to {
+            Position::Source => &mut self.source,
+            Position::Sink => &mut self.sink,
+            Position::BeforeNode(ni) => &mut self.before[ni.node_index()],
+            Position::AfterNode(ni) => &mut self.after[ni.node_index()],
         }
     }
 
@@ -100,14 +205,25 @@ impl<Ni: NodeIndex + std::fmt::Debug> Path<Ni> {
     /// Sets the "from" node for a "to" node, i.e. the node `from` which was
     /// used to access `to`.
     fn set_from(&mut self, from: Position<Ni>, to: Position<Ni>) {
-        let from_ref = match to {
-            Position::Source => &mut self.source,
-            Position::Sink => &mut self.sink,
-            Position::BeforeNode(ni) => &mut self.before[ni.node_index()],
-            Position::AfterNode(ni) => &mut self.after[ni.node_index()],
-        };
+        if self.get_mut(to).replace(from).is_none() {
+            self.touched.push(to);
+        }
+    }
 
-        *from_ref = Some(from);
+    /// Removes every step from the path, growing to fit `node_count` nodes if
+    /// needed. Only the nodes given a "from" node since the last clear are
+    /// reset.
+    fn clear(&mut self, node_count: usize) {
+        let mut touched = mem::take(&mut self.touched);
+        for to in touched.drain(..) {
+            *self.get_mut(to) = None;
+        }
+        self.touched = touched;
+
+        if self.after.len() < node_count {
+            self.before.resize(node_count, None);
+            self.after.resize(node_count, None);
+        }
     }
 
     /// Returns an iterator over the steps in the path, working backwards from
@@ -119,7 +235,7 @@ impl<Ni: NodeIndex + std::fmt::Debug> Path<Ni> {
             if let Some(to) = prev_to {
                 let from = self.get_from(to);
                 if let Some(from) = from {
-                    let path_step = PathStep { from: from, to: to };
+                    let path_step = PathStep { from, to };
 
                     prev_to = Some(from);
 
@@ -139,8 +255,70 @@ enum NetworkEdgeDirection {
     Ancestor,
 }
 
+/// The buffers used while searching for augmenting paths, which are sized to
+/// the whole network.
+///
+/// Labelling finds a flow for every node, usually through a small cone of a
+/// much larger network, so the same scratch space should be passed to each
+/// flow rather than allocating the buffers again. Only the entries touched by
+/// a search are cleared before the next one.
+#[derive(Debug)]
+pub struct FlowScratch<Ni: 'static + NodeIndex> {
+    levels: Levels<Ni>,
+    dead: Visited<Ni>,
+    path: Path<Ni>,
+}
+
+impl<Ni: NodeIndex + std::fmt::Debug> FlowScratch<Ni> {
+    /// Creates an empty scratch space, which grows to fit the network of each
+    /// flow it's used with.
+    pub fn new() -> FlowScratch<Ni> {
+        FlowScratch {
+            levels: Levels::new(0),
+            dead: Visited::new(0),
+            path: Path::new(0),
+        }
+    }
+}
+
+impl<Ni: NodeIndex + std::fmt::Debug> Default for FlowScratch<Ni> {
+    fn default() -> FlowScratch<Ni> {
+        FlowScratch::new()
+    }
+}
+
+/// A minimum cut of a flow network, separating the nodes which can be reached
+/// from the source on the residual graph from those which can't.
+#[derive(Debug, Clone)]
+pub struct MinCut<Ni: NodeIndex> {
+    /// The nodes on the source side of the cut, X''. This includes the nodes
+    /// whose own capacity is part of the cut.
+    pub x: HashSet<Ni>,
+    /// The other nodes of the flow network, \bar{X}''. The node being labelled
+    /// is the sink itself, so it's never included.
+    pub x_bar: HashSet<Ni>,
+    /// The saturated edges from the source side to the sink side, in no
+    /// particular order.
+    pub edges: Vec<CutEdge<Ni>>,
+}
+
+/// An edge of the flow network which is part of a cut.
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Hash)]
+pub enum CutEdge<Ni: NodeIndex> {
+    /// The edge through a node, whose capacity is the node's `capacity`.
+    Node(Ni),
+    /// The edge of the network between two nodes, from the first to the
+    /// second.
+    Edge(Ni, Ni),
+}
+
+/// A flow through the cone of the node being labelled, from a source joined to
+/// its PIs to a sink which replaces the node. Each node has the capacity given
+/// by its `capacity`, which is 1 unless changed, so the maximum flow is the
+/// fewest nodes which cut the cone.
 pub struct Flow<'a, Ni: 'static + NodeIndex + std::fmt::Debug> {
     network: &'a mut FlowMapBooleanNetwork<Ni>,
+    scratch: &'a mut FlowScratch<Ni>,
     node: Ni,
     source: Vec<(Ni, u32)>,
     sink: Vec<(Ni, u32)>,
@@ -149,71 +327,155 @@ pub struct Flow<'a, Ni: 'static + NodeIndex + std::fmt::Debug> {
 impl<Ni: NodeIndex + std::fmt::Debug> Flow<'_, Ni> {
     pub fn new<'a>(
         network: &'a mut FlowMapBooleanNetwork<Ni>,
+        scratch: &'a mut FlowScratch<Ni>,
         node: Ni,
         source: &[Ni],
         sink: &[Ni],
     ) -> Flow<'a, Ni> {
+        // Every other node index used by the flow is found through the edges of
+        // the network, so checking these here means the flow never needs to
+        // check the bounds of a node index again
+        let node_count = network.node_count();
+        assert!(
+            iter::once(&node)
+                .chain(source)
+                .chain(sink)
+                .all(|ni| ni.node_index() < node_count),
+            "node index out of bounds: the maximum node index is {}",
+            node_count - 1
+        );
+
         Flow {
             network,
+            scratch,
             node,
             source: source.iter().map(|ni| (*ni, 0)).collect(),
             sink: sink.iter().map(|ni| (*ni, 0)).collect(),
         }
     }
 
-    pub fn step(&mut self) -> bool {
-        let mut visited = Visited::<Ni>::new(self.network.node_count());
-        let mut path = Path::new(self.network.node_count());
-        let mut s: Vec<Position<Ni>> = vec![Position::Source];
+    /// Augments the flow until it is maximal or reaches `limit`, returning the
+    /// total flow found.
+    ///
+    /// This uses Dinic's algorithm: each phase builds the level graph of the
+    /// residual network with a BFS, then finds a blocking flow within it,
+    /// pruning nodes which can no longer reach the sink. Each augmenting path
+    /// carries one unit of flow, which is all that a path through a node with
+    /// the usual capacity of 1 can carry.
+    pub fn max_flow(&mut self, limit: u32) -> u32 {
+        let node_count = self.network.node_count();
+        let mut max_flow = 0;
 
-        while let Some(p) = s.pop() {
-            if !visited.insert(p) {
-                continue;
+        // The searches borrow the rest of the flow, so move the buffers out of
+        // the scratch space while they're in use
+        let mut scratch = mem::take(self.scratch);
+
+        while max_flow < limit {
+            scratch.levels.clear(node_count);
+            if !self.levels(&mut scratch.levels) {
+                break;
             }
 
-            for descendent in self.descendents(p) {
-                if visited.contains(descendent) {
-                    continue;
+            scratch.dead.clear(node_count);
+            while max_flow < limit {
+                scratch.path.clear(node_count);
+                if !self.level_path(&scratch.levels, &mut scratch.dead, &mut scratch.path) {
+                    break;
                 }
 
-                // Descendents are "forward" edges which can only be travelled
-                // on if the current capacity is non-zero
-                let (_, cap) = self.flow_cap(p, descendent);
-                if cap > 0 {
-                    path.set_from(p, descendent);
-                    s.push(descendent);
+                for path_step in scratch.path.path_rev(Position::Sink) {
+                    self.augment(path_step.from, path_step.to, 1);
                 }
+
+                max_flow += 1;
             }
+        }
 
-            for ancestor in self.ancestors(p) {
-                if visited.contains(ancestor) {
-                    continue;
-                }
+        *self.scratch = scratch;
 
-                // Ancestors are "backwards" edges which can only be travelled
-                // on if the current flow is non-zero
-                let (flow, _) = self.flow_cap(ancestor, p);
-                if flow > 0 {
-                    path.set_from(p, ancestor);
-                    s.push(ancestor);
+        max_flow
+    }
+
+    /// Returns the nodes which can be reached from `position` in one step on
+    /// the residual network.
+    fn residual_neighbours(&self, position: Position<Ni>) -> Vec<Position<Ni>> {
+        let forward = self
+            .descendents(position)
+            .filter(|descendent| !self.flow_cap(position, *descendent).1.is_zero());
+        let backward = self
+            .ancestors(position)
+            .filter(|ancestor| self.flow_cap(*ancestor, position).0 > 0);
+
+        forward.chain(backward).collect()
+    }
+
+    /// Finds the level of every node in the residual network, returning
+    /// `false` if the sink is unreachable and the flow is already maximal.
+    ///
+    /// `levels` must be cleared beforehand.
+    fn levels(&self, levels: &mut Levels<Ni>) -> bool {
+        levels.set(Position::Source, 0);
+
+        let mut q = VecDeque::new();
+        q.push_back((Position::Source, 0));
+        while let Some((p, level)) = q.pop_front() {
+            for neighbour in self.residual_neighbours(p) {
+                if levels.get(neighbour).is_none() {
+                    levels.set(neighbour, level + 1);
+                    q.push_back((neighbour, level + 1));
                 }
             }
         }
 
-        // Did we fail to find an augmenting path?
-        if !visited.contains(Position::Sink) {
-            return false;
-        }
+        levels.get(Position::Sink).is_some()
+    }
+
+    /// Finds a path from the source to the sink which only moves from each
+    /// level of the level graph to the next, storing it in `path`, or returns
+    /// `false` if there is no such path.
+    ///
+    /// Nodes found to have no such path to the sink are marked as `dead`, so
+    /// later searches in the same phase never visit them again. `path` must be
+    /// cleared beforehand.
+    fn level_path(&self, levels: &Levels<Ni>, dead: &mut Visited<Ni>, path: &mut Path<Ni>) -> bool {
+        let level_neighbours = |p: Position<Ni>| {
+            let next_level = levels.get(p).map(|level| level + 1);
+
+            self.residual_neighbours(p)
+                .into_iter()
+                .filter(|neighbour| levels.get(*neighbour) == next_level)
+                .collect::<Vec<_>>()
+        };
+
+        let mut s = vec![(Position::Source, level_neighbours(Position::Source))];
+        while let Some((p, neighbours)) = s.last_mut() {
+            let p = *p;
+
+            match neighbours.pop() {
+                Some(Position::Sink) => {
+                    path.set_from(p, Position::Sink);
 
-        for path_step in path.path_rev(Position::Sink) {
-            self.augment(path_step.from, path_step.to, 1);
+                    return true;
+                }
+                Some(neighbour) => {
+                    if !dead.contains(neighbour) {
+                        path.set_from(p, neighbour);
+                        s.push((neighbour, level_neighbours(neighbour)));
+                    }
+                }
+                None => {
+                    dead.insert(p);
+                    s.pop();
+                }
+            }
         }
 
-        true
+        false
     }
 
-    pub fn cut(&self, orig: &HashSet<Ni>) -> Vec<Ni> {
-        let mut reachable = HashSet::new();
+    /// Returns every position which can be reached from the source on the
+    /// residual graph.
+    fn residual_reachable(&self) -> HashSet<Position<Ni>> {
         let mut visited = HashSet::new();
         let mut s = vec![Position::Source];
         while let Some(n) = s.pop() {
@@ -221,13 +483,6 @@ impl<Ni: NodeIndex + std::fmt::Debug> Flow<'_, Ni> {
                 continue;
             }
 
-            match n {
-                Position::BeforeNode(n) | Position::AfterNode(n) => {
-                    reachable.insert(n);
-                }
-                _ => {}
-            }
-
             for descendent in self.descendents(n) {
                 if self.is_undirected_path(n, descendent, NetworkEdgeDirection::Descendent) {
                     s.push(descendent);
@@ -241,8 +496,122 @@ impl<Ni: NodeIndex + std::fmt::Debug> Flow<'_, Ni> {
             }
         }
 
+        visited
+    }
+
+    /// Returns the minimum cut closest to the source, found from the residual
+    /// graph.
+    ///
+    /// The cut is only minimal once `max_flow` has found the maximum flow.
+    /// Before then, the sink is still reachable from the source and the cut
+    /// has no edges.
+    pub fn min_cut(&self) -> MinCut<Ni> {
+        let reachable = self.residual_reachable();
+        let x = nodes_of(&reachable);
+
+        // Every node in the flow has a path to the sink, so walk back from it
+        // to find the nodes which the source can't reach
+        let mut all = HashSet::new();
+        let mut s = vec![Position::Sink];
+        while let Some(n) = s.pop() {
+            if all.insert(n) {
+                s.extend(self.ancestors(n));
+            }
+        }
+        let x_bar = nodes_of(&all)
+            .difference(&x)
+            .copied()
+            .collect::<HashSet<_>>();
+
+        // Every edge leaving the source side must be saturated, otherwise its
+        // other end would be reachable too. Edges without any capacity, such
+        // as those leaving the cone, carry no flow and aren't part of the cut.
+        let mut edges = vec![];
+        for from in &reachable {
+            for to in self.descendents(*from) {
+                if reachable.contains(&to) || self.flow_cap(*from, to).0 == 0 {
+                    continue;
+                }
+
+                match (*from, to) {
+                    (Position::BeforeNode(ni), Position::AfterNode(_)) => {
+                        edges.push(CutEdge::Node(ni));
+                    }
+                    (Position::AfterNode(from), Position::BeforeNode(to)) => {
+                        edges.push(CutEdge::Edge(from, to));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        MinCut { x, x_bar, edges }
+    }
+
+    pub fn cut(&self, orig: &HashSet<Ni>) -> Vec<Ni> {
+        let reachable = nodes_of(&self.residual_reachable());
+
         // Our "reachable" set is X'', so generate \bar{X}''
-        orig.difference(&reachable).copied().collect()
+        let x_bar = orig.difference(&reachable).copied().collect::<HashSet<_>>();
+
+        // A node upstream of a node in the cut can be unreachable too, but it
+        // only drives the node being labelled through the cut, so it's not
+        // part of \bar{X}. Keep only the nodes with a path to the node being
+        // labelled within \bar{X}''.
+        let mut kept = vec![];
+        let mut visited = HashSet::new();
+        let mut s = vec![self.node];
+        while let Some(ni) = s.pop() {
+            if !x_bar.contains(&ni) || !visited.insert(ni) {
+                continue;
+            }
+
+            kept.push(ni);
+            s.extend_from_slice(self.network.ancestors(ni));
+        }
+
+        kept
+    }
+
+    /// Returns the nodes of `orig` on the sink side of the minimum cut closest
+    /// to the sink, i.e. the nodes which can still reach the sink on the
+    /// residual graph.
+    ///
+    /// Unlike `cut`, this does not include the node being labelled or any
+    /// nodes collapsed into the sink, since they never appear in the flow.
+    pub fn sink_cut(&self, orig: &HashSet<Ni>) -> Vec<Ni> {
+        let mut reaching = HashSet::new();
+        let mut visited = HashSet::new();
+        let mut s = vec![Position::Sink];
+        while let Some(n) = s.pop() {
+            if !visited.insert(n) {
+                continue;
+            }
+
+            if let Position::BeforeNode(n) = n {
+                reaching.insert(n);
+            }
+
+            // Walk the residual graph backwards, only staying within the
+            // original nodes since edges leaving them hold stale flows
+            for ancestor in self.ancestors(n) {
+                if is_within(ancestor, orig)
+                    && self.is_undirected_path(ancestor, n, NetworkEdgeDirection::Descendent)
+                {
+                    s.push(ancestor);
+                }
+            }
+
+            for descendent in self.descendents(n) {
+                if is_within(descendent, orig)
+                    && self.is_undirected_path(descendent, n, NetworkEdgeDirection::Ancestor)
+                {
+                    s.push(descendent);
+                }
+            }
+        }
+
+        orig.intersection(&reaching).copied().collect()
     }
 
     fn descendents(&self, position: Position<Ni>) -> Box<dyn Iterator<Item = Position<Ni>> + '_> {
@@ -256,7 +625,9 @@ impl<Ni: NodeIndex + std::fmt::Debug> Flow<'_, Ni> {
                 if self.sink.iter().any(|(ni2, _)| *ni2 == ni) {
                     Box::new(iter::once(Position::Sink))
                 } else {
-                    Box::new(self.network.descendents(ni).iter().map(move |ni| {
+                    // SAFETY: ni is always a valid node index (see Flow::new)
+                    let descendents = unsafe { self.network.descendents_unchecked(ni) };
+                    Box::new(descendents.iter().map(move |ni| {
                         if *ni == self.node {
                             Position::Sink
                         } else {
@@ -272,53 +643,58 @@ impl<Ni: NodeIndex + std::fmt::Debug> Flow<'_, Ni> {
         match position {
             Position::Source => Box::new(iter::empty()),
             Position::Sink => Box::new(self.sink.iter().map(|(ni, _)| Position::AfterNode(*ni))),
-            Position::BeforeNode(ni) => Box::new(
-                self.network
-                    .ancestors(ni)
-                    .iter()
-                    .map(|ni| Position::AfterNode(*ni)),
-            ),
+            Position::BeforeNode(ni) => {
+                // SAFETY: ni is always a valid node index (see Flow::new)
+                let ancestors = unsafe { self.network.ancestors_unchecked(ni) };
+                Box::new(ancestors.iter().map(|ni| Position::AfterNode(*ni)))
+            }
             Position::AfterNode(ni) => Box::new(iter::once(Position::BeforeNode(ni))),
         }
     }
 
     /// Returns the current flow and current capacity (i.e. the capacity of the
     /// edge, minus the current flow) of the provided edge.
-    fn flow_cap(&self, from: Position<Ni>, to: Position<Ni>) -> (u32, u32) {
+    ///
+    /// Edges from the source and to the sink have infinite capacity.
+    fn flow_cap(&self, from: Position<Ni>, to: Position<Ni>) -> (u32, Capacity) {
         match (from, to) {
             (Position::Source, Position::BeforeNode(ni)) => self
                 .source
                 .iter()
                 .find_map(|(ni2, flow)| {
                     if *ni2 == ni {
-                        // TODO: Handle infinite capacity better
-                        Some((*flow, 1000))
+                        Some((*flow, Capacity::Infinite))
                     } else {
                         None
                     }
                 })
-                .unwrap_or((0, 0)),
+                .unwrap_or((0, Capacity::Finite(0))),
             (Position::BeforeNode(ni1), Position::AfterNode(ni2)) if ni1 == ni2 => {
-                let flow = self.network.node_value(ni1).flow;
+                // SAFETY: ni1 is always a valid node index (see Flow::new)
+                let node_value = unsafe { self.network.node_value_unchecked(ni1) };
 
-                (flow, 1 - flow)
-            }
-            (Position::AfterNode(ni1), Position::BeforeNode(ni2)) => {
-                *self.network.edge_value(From(ni1), To(ni2))
+                (
+                    node_value.flow,
+                    Capacity::Finite(node_value.capacity - node_value.flow),
+                )
             }
+            (Position::AfterNode(ni1), Position::BeforeNode(ni2)) => self
+                .network
+                .edge_value_checked(From(ni1), To(ni2))
+                .copied()
+                .unwrap_or((0, Capacity::Finite(0))),
             (Position::AfterNode(ni), Position::Sink) => self
                 .sink
                 .iter()
                 .find_map(|(ni2, flow)| {
                     if *ni2 == ni {
-                        // TODO: Handle infinite capacity better
-                        Some((*flow, 1000))
+                        Some((*flow, Capacity::Infinite))
                     } else {
                         None
                     }
                 })
-                .unwrap_or((0, 0)),
-            _ => (0, 0),
+                .unwrap_or((0, Capacity::Finite(0))),
+            _ => (0, Capacity::Finite(0)),
         }
     }
 
@@ -351,17 +727,16 @@ impl<Ni: NodeIndex + std::fmt::Debug> Flow<'_, Ni> {
             (Position::AfterNode(ni1), Position::BeforeNode(ni2)) => {
                 let (flow, cap) = self.network.edge_value_mut(From(ni1), To(ni2));
                 *flow += f;
-                *cap -= f;
+                *cap = cap.decrease(f);
             }
             (Position::BeforeNode(ni1), Position::AfterNode(ni2)) => {
                 let (flow, cap) = self.network.edge_value_mut(From(ni2), To(ni1));
                 *flow -= f;
-                *cap += f;
+                *cap = cap.increase(f);
             }
             (Position::AfterNode(ni), Position::Sink) => {
                 for (ni2, flow) in &mut self.sink {
                     if *ni2 == ni {
-                        // TODO: Handle infinite capacity better
                         *flow += f;
                     }
                 }
@@ -369,7 +744,6 @@ impl<Ni: NodeIndex + std::fmt::Debug> Flow<'_, Ni> {
             (Position::Sink, Position::AfterNode(ni)) => {
                 for (ni2, flow) in &mut self.sink {
                     if *ni2 == ni {
-                        // TODO: Handle infinite capacity better
                         *flow -= f;
                     }
                 }
@@ -394,7 +768,7 @@ impl<Ni: NodeIndex + std::fmt::Debug> Flow<'_, Ni> {
             NetworkEdgeDirection::Ancestor => self.flow_cap(to, from),
         };
 
-        let is_undir_path_forward = cap != 0;
+        let is_undir_path_forward = !cap.is_zero();
         let is_undir_path_backward = flow != 0;
 
         match (from, to) {
@@ -432,9 +806,30 @@ impl<Ni: NodeIndex + std::fmt::Debug> Flow<'_, Ni> {
     }
 }
 
+/// Returns the nodes which the provided positions belong to.
+fn nodes_of<Ni: NodeIndex>(positions: &HashSet<Position<Ni>>) -> HashSet<Ni> {
+    positions
+        .iter()
+        .filter_map(|position| match position {
+            Position::BeforeNode(ni) | Position::AfterNode(ni) => Some(*ni),
+            Position::Source | Position::Sink => None,
+        })
+        .collect()
+}
+
+/// Returns `true` if the position is the source, sink, or belongs to one of
+/// the provided nodes.
+fn is_within<Ni: NodeIndex>(position: Position<Ni>, nodes: &HashSet<Ni>) -> bool {
+    match position {
+        Position::Source | Position::Sink => true,
+        Position::BeforeNode(ni) | Position::AfterNode(ni) => nodes.contains(&ni),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::assert_equiv;
 
     #[test]
     fn visited() {
@@ -486,6 +881,21 @@ mod tests {
         assert_eq!(visited.insert(Position::Sink), false);
     }
 
+    #[test]
+    fn visited_clear() {
+        let mut visited = Visited::<usize>::new(1);
+        visited.insert(Position::Source);
+        visited.insert(Position::AfterNode(0));
+
+        visited.clear(3);
+
+        assert_eq!(visited.contains(Position::Source), false);
+        assert_eq!(visited.contains(Position::AfterNode(0)), false);
+        assert_eq!(visited.contains(Position::BeforeNode(2)), false);
+        assert_eq!(visited.insert(Position::AfterNode(0)), true);
+        assert_eq!(visited.insert(Position::BeforeNode(2)), true);
+    }
+
     #[test]
     fn path() {
         let mut path = Path::<usize>::new(9);
@@ -527,4 +937,120 @@ mod tests {
             ]
         );
     }
+
+    /// Returns a network where each of the nodes 3, 4 and 5 is driven by a
+    /// different PI and drives node 6.
+    fn get_parallel_network() -> FlowMapBooleanNetwork<usize> {
+        let mut network = FlowMapBooleanNetwork::<usize>::new(6);
+        for (from, to) in &[(0, 3), (1, 4), (2, 5), (3, 6), (4, 6), (5, 6)] {
+            network.add_edge(From(*from), To(*to));
+            *network.edge_value_mut(From(*from), To(*to)) = (0, Capacity::Infinite);
+        }
+
+        network
+    }
+
+    #[test]
+    fn max_flow() {
+        let mut network = get_parallel_network();
+        let mut scratch = FlowScratch::new();
+        let mut flow = Flow::new(&mut network, &mut scratch, 6, &[0, 1, 2], &[3, 4, 5]);
+
+        assert_eq!(flow.max_flow(10), 3);
+        assert_eq!(flow.max_flow(10), 0);
+        assert_equiv!(
+            &flow.cut(&[3, 4, 5, 6].iter().copied().collect()),
+            &vec![3, 4, 5, 6]
+        );
+    }
+
+    #[test]
+    fn max_flow_limit() {
+        let mut network = get_parallel_network();
+        let mut scratch = FlowScratch::new();
+        let mut flow = Flow::new(&mut network, &mut scratch, 6, &[0, 1, 2], &[3, 4, 5]);
+
+        assert_eq!(flow.max_flow(2), 2);
+        assert_eq!(flow.max_flow(2), 1);
+    }
+
+    #[test]
+    fn max_flow_reused_scratch() {
+        let mut scratch = FlowScratch::new();
+
+        let mut network = get_parallel_network();
+        let mut flow = Flow::new(&mut network, &mut scratch, 6, &[0, 1, 2], &[3, 4, 5]);
+        assert_eq!(flow.max_flow(10), 3);
+
+        // A larger network than the scratch space has seen before, with only
+        // two paths from the source to the sink
+        let mut network = FlowMapBooleanNetwork::<usize>::new(8);
+        for (from, to) in &[(0, 3), (1, 3), (2, 4), (3, 5), (4, 5), (5, 8), (4, 8)] {
+            network.add_edge(From(*from), To(*to));
+            *network.edge_value_mut(From(*from), To(*to)) = (0, Capacity::Infinite);
+        }
+        let mut flow = Flow::new(&mut network, &mut scratch, 8, &[0, 1, 2], &[5, 4]);
+        assert_eq!(flow.max_flow(10), 2);
+
+        let mut network = get_parallel_network();
+        let mut flow = Flow::new(&mut network, &mut scratch, 6, &[0, 1, 2], &[3, 4, 5]);
+        assert_eq!(flow.max_flow(10), 3);
+    }
+
+    #[test]
+    fn min_cut() {
+        let mut network = get_parallel_network();
+        let mut scratch = FlowScratch::new();
+        let mut flow = Flow::new(&mut network, &mut scratch, 6, &[0, 1, 2], &[3, 4, 5]);
+        assert_eq!(flow.max_flow(10), 3);
+
+        let min_cut = flow.min_cut();
+        assert_equiv!(&min_cut.x, &vec![0, 1, 2]);
+        assert_equiv!(&min_cut.x_bar, &vec![3, 4, 5]);
+        assert_equiv!(
+            &min_cut.edges,
+            &vec![CutEdge::Node(0), CutEdge::Node(1), CutEdge::Node(2)]
+        );
+    }
+
+    #[test]
+    fn min_cut_through_node() {
+        // Every path from the PIs 0, 1 and 2 to node 5 passes through node 3,
+        // so it's the only node in the cut
+        let mut network = FlowMapBooleanNetwork::<usize>::new(5);
+        for (from, to) in &[(0, 3), (1, 3), (2, 3), (3, 4), (4, 5)] {
+            network.add_edge(From(*from), To(*to));
+            *network.edge_value_mut(From(*from), To(*to)) = (0, Capacity::Infinite);
+        }
+
+        let mut scratch = FlowScratch::new();
+        let mut flow = Flow::new(&mut network, &mut scratch, 5, &[0, 1, 2], &[4]);
+        assert_eq!(flow.max_flow(10), 1);
+
+        let min_cut = flow.min_cut();
+        assert_equiv!(&min_cut.x, &vec![0, 1, 2, 3]);
+        assert_equiv!(&min_cut.x_bar, &vec![4]);
+        assert_eq!(min_cut.edges, vec![CutEdge::Node(3)]);
+    }
+
+    #[test]
+    fn max_flow_node_capacity() {
+        // As in min_cut_through_node, but node 3 can carry all of the flow from
+        // the PIs, leaving node 4 as the only bottleneck
+        let mut network = FlowMapBooleanNetwork::<usize>::new(5);
+        for (from, to) in &[(0, 3), (1, 3), (2, 3), (3, 4), (4, 5)] {
+            network.add_edge(From(*from), To(*to));
+            *network.edge_value_mut(From(*from), To(*to)) = (0, Capacity::Infinite);
+        }
+        network.node_value_mut(3).capacity = 3;
+        network.node_value_mut(4).capacity = 2;
+
+        let mut scratch = FlowScratch::new();
+        let mut flow = Flow::new(&mut network, &mut scratch, 5, &[0, 1, 2], &[4]);
+        assert_eq!(flow.max_flow(10), 2);
+
+        let min_cut = flow.min_cut();
+        assert_equiv!(&min_cut.x, &vec![0, 1, 2, 3, 4]);
+        assert_eq!(min_cut.edges, vec![CutEdge::Node(4)]);
+    }
 }
@@ -1,42 +1,172 @@
 use super::flow::*;
 use super::*;
-use hashbrown::HashSet;
+use hashbrown::{HashMap, HashSet};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::ops::{ControlFlow, Deref};
+
+/// A network which has been through the FlowMap labelling pass, and is thus
+/// ready to be mapped.
+///
+/// A labelled network can only be created by `label_network`.
+pub struct LabeledNetwork<Ni: 'static + NodeIndex>(pub(super) FlowMapBooleanNetwork<Ni>);
+
+impl<Ni: 'static + NodeIndex> LabeledNetwork<Ni> {
+    /// Consumes the labelled network, returning the underlying network.
+    pub fn into_inner(self) -> FlowMapBooleanNetwork<Ni> {
+        self.0
+    }
+}
+
+impl<Ni: 'static + NodeIndex> Deref for LabeledNetwork<Ni> {
+    type Target = FlowMapBooleanNetwork<Ni>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// A `NodeIndex` of a network which `label_network` can label. With the `rayon`
+/// feature, nodes are labelled on several threads, so the index must also be
+/// `Send` and `Sync`.
+#[cfg(feature = "rayon")]
+pub trait LabelNodeIndex: NodeIndex + Send + Sync {}
+
+#[cfg(feature = "rayon")]
+impl<Ni: NodeIndex + Send + Sync> LabelNodeIndex for Ni {}
+
+/// A `NodeIndex` of a network which `label_network` can label. With the `rayon`
+/// feature, nodes are labelled on several threads, so the index must also be
+/// `Send` and `Sync`.
+#[cfg(not(feature = "rayon"))]
+pub trait LabelNodeIndex: NodeIndex {}
+
+#[cfg(not(feature = "rayon"))]
+impl<Ni: NodeIndex> LabelNodeIndex for Ni {}
+
+/// The nodes which are ready to be visited next in a topological ordering.
+enum Ready<Ni: NodeIndex> {
+    /// Nodes are visited in last-in, first-out order.
+    Stack(Vec<Ni>),
+    /// Nodes are visited in order of decreasing priority, with ties visited in
+    /// order of increasing node index. `priorities` holds the priority of every
+    /// node, in node index order.
+    Priority {
+        heap: BinaryHeap<(i64, Reverse<usize>)>,
+        priorities: Vec<i64>,
+    },
+}
+
+impl<Ni: NodeIndex> Ready<Ni> {
+    fn push(&mut self, ni: Ni) {
+        match self {
+            Ready::Stack(s) => s.push(ni),
+            Ready::Priority { heap, priorities } => {
+                heap.push((priorities[ni.node_index()], Reverse(ni.node_index())))
+            }
+        }
+    }
+
+    fn pop(&mut self) -> Option<Ni> {
+        match self {
+            Ready::Stack(s) => s.pop(),
+            Ready::Priority { heap, .. } => {
+                heap.pop().map(|(_, Reverse(ni))| Ni::from_node_index(ni))
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        match self {
+            Ready::Stack(s) => s.clear(),
+            Ready::Priority { heap, .. } => heap.clear(),
+        }
+    }
+}
 
 /// Provides a topological ordering on a boolean network.
-struct TopologicalOrder<Ni: NodeIndex> {
-    s: Vec<Ni>,
-    visited: HashSet<Ni>,
+///
+/// The nodes of a cycle, and every node they drive, are never ready, so they're
+/// silently left out of the ordering. Use `BooleanNetwork::find_cycle` to check
+/// a network which might contain a cycle first.
+pub struct TopologicalOrder<Ni: NodeIndex> {
+    s: Ready<Ni>,
+    /// Whether each node has been visited, in node index order.
+    visited: Vec<bool>,
 }
 
 impl<Ni: NodeIndex> TopologicalOrder<Ni> {
     /// Creates a new topological ordering over the provided network.
-    fn new<N: Default, E: Default>(network: &BooleanNetwork<N, E, Ni>) -> TopologicalOrder<Ni> {
-        let s = (0..network.node_count())
-            .map(Ni::from_node_index)
-            .filter(|ni| network.ancestors(*ni).is_empty())
-            .collect();
-
-        TopologicalOrder {
-            s,
-            // We'll eventually completely fill our visited list with every node
-            // on the graph, so make space now
-            visited: HashSet::with_capacity(network.node_count()),
+    pub fn new<N: Default, E: Default>(network: &BooleanNetwork<N, E, Ni>) -> TopologicalOrder<Ni> {
+        TopologicalOrder::with_ready(network, Ready::Stack(Vec::new()))
+    }
+
+    /// Creates a new topological ordering over the provided network which,
+    /// whenever more than one node is ready, visits the node with the highest
+    /// priority next.
+    ///
+    /// The priority of every node is computed once, when the ordering is
+    /// created. For example, `|ni| network.fan_out(ni) as i64` visits
+    /// nodes with a higher fan-out first.
+    pub fn new_with_priority<N: Default, E: Default>(
+        network: &BooleanNetwork<N, E, Ni>,
+        priority: impl Fn(Ni) -> i64,
+    ) -> TopologicalOrder<Ni> {
+        let priorities = network.nodes().map(priority).collect();
+        let ready = Ready::Priority {
+            heap: BinaryHeap::new(),
+            priorities,
+        };
+
+        TopologicalOrder::with_ready(network, ready)
+    }
+
+    fn with_ready<N: Default, E: Default>(
+        network: &BooleanNetwork<N, E, Ni>,
+        ready: Ready<Ni>,
+    ) -> TopologicalOrder<Ni> {
+        let mut topo = TopologicalOrder {
+            s: ready,
+            visited: vec![],
+        };
+
+        topo.restart(network);
+        topo
+    }
+
+    /// Restarts the topological ordering from the beginning, reusing the
+    /// already allocated storage.
+    ///
+    /// This allows multi-pass algorithms to traverse the network in
+    /// topological order more than once without reallocating.
+    pub fn restart<N: Default, E: Default>(&mut self, network: &BooleanNetwork<N, E, Ni>) {
+        self.s.clear();
+        self.visited.clear();
+        self.visited.resize(network.node_count(), false);
+
+        for ni in network.nodes() {
+            if network.ancestors(ni).is_empty() {
+                self.s.push(ni);
+            }
         }
     }
 
     /// Returns the next node in the topological ordering, or `None` of no nodes
     /// remain.
-    fn next<N: Default, E: Default>(&mut self, network: &BooleanNetwork<N, E, Ni>) -> Option<Ni> {
+    pub fn next<N: Default, E: Default>(
+        &mut self,
+        network: &BooleanNetwork<N, E, Ni>,
+    ) -> Option<Ni> {
         let n = self.s.pop();
 
         if let Some(n) = n {
-            self.visited.insert(n);
+            self.visited[n.node_index()] = true;
 
             for descendent in network.descendents(n) {
                 let remaining_ancestors = network
                     .ancestors(*descendent)
                     .iter()
-                    .filter(|ni| !self.visited.contains(ni));
+                    .filter(|ni| !self.visited[ni.node_index()]);
 
                 if remaining_ancestors.count() == 0 {
                     self.s.push(*descendent);
@@ -48,12 +178,34 @@ impl<Ni: NodeIndex> TopologicalOrder<Ni> {
     }
 }
 
+/// The minimum cut selected as \bar{X} when labelling a node.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum CutSelection {
+    /// The minimum cut closest to the source, which gives the largest \bar{X}.
+    MaxVolume,
+    /// Whichever of the minimum cuts closest to the source and to the sink
+    /// duplicates the fewest nodes, preferring the largest \bar{X} when they
+    /// are equal.
+    MinDuplication,
+}
+
 /// Returns the label for a single node of the network.
 fn label_node<Ni: 'static + NodeIndex + std::fmt::Debug>(
-    mut network: &mut FlowMapBooleanNetwork<Ni>,
+    network: &mut FlowMapBooleanNetwork<Ni>,
+    scratch: &mut FlowScratch<Ni>,
     node: Ni,
     k: u32,
+    cut_selection: CutSelection,
 ) -> (u32, Vec<Ni>) {
+    // Inverters and buffers of a PI are common in AIGER networks, and are
+    // always a LUT by themselves with a flow of exactly 1 between the PI and
+    // the node, so skip computing the flow entirely
+    if let [ancestor] = network.ancestors(node) {
+        if network.node_value(*ancestor).is_pi {
+            return (1, vec![node]);
+        }
+    }
+
     let p = network
         .ancestors(node)
         .iter()
@@ -73,15 +225,19 @@ fn label_node<Ni: 'static + NodeIndex + std::fmt::Debug>(
         // This would mean the maximum flow on the graph is infinite, and thus
         // the label of the node we're evaluating is p + 1.
         // This also gives us an \bar{X} which only contains the node we're
-        // evaluating.
+        // evaluating, which is the only possible \bar{X} since PIs can never
+        // be inside one. Its inputs are exactly the node's PIs, so if there
+        // are more than K of them, no LUT can implement the node and mapping
+        // reports the \bar{X} as too large.
         return (p + 1, vec![node]);
     }
 
-    let mut source = vec![];
-    let mut sink = vec![];
+    let mut source = HashSet::new();
+    let mut sink = HashSet::new();
+    let mut collapsed = vec![node];
     // Every node which is an input to the node we're labelling now is connected
     // to the sink, since the sink replaces the node we're labelling.
-    sink.extend_from_slice(network.ancestors(node));
+    sink.extend(network.ancestors(node));
 
     let mut visited = HashSet::new();
     visited.insert(node);
@@ -92,7 +248,7 @@ fn label_node<Ni: 'static + NodeIndex + std::fmt::Debug>(
         network.node_value_mut(node).flow = 0;
 
         for ancestor in ancestors {
-            *network.edge_value_mut(From(ancestor), To(node)) = (0, 1);
+            *network.edge_value_mut(From(ancestor), To(node)) = (0, Capacity::Finite(1));
             if visited.insert(ancestor) {
                 let (label, is_pi) = {
                     let node_value = network.node_value(ancestor);
@@ -102,17 +258,13 @@ fn label_node<Ni: 'static + NodeIndex + std::fmt::Debug>(
 
                 if label == Some(p) {
                     // This node needs to be collapsed
-                    for ancestor2 in network.ancestors(ancestor) {
-                        if !sink.contains(ancestor2) {
-                            sink.push(*ancestor2);
-                        }
-                    }
+                    collapsed.push(ancestor);
+                    sink.extend(network.ancestors(ancestor));
                 } else if is_pi {
                     // This node needs to be joined to the source
-                    source.push(ancestor);
+                    source.insert(ancestor);
                 } else {
-                    // TODO: Handle infinite capacity better
-                    *network.edge_value_mut(From(ancestor), To(node)) = (0, 1000);
+                    *network.edge_value_mut(From(ancestor), To(node)) = (0, Capacity::Infinite);
                 }
 
                 s.push(ancestor);
@@ -120,43 +272,644 @@ fn label_node<Ni: 'static + NodeIndex + std::fmt::Debug>(
         }
     }
 
-    let mut flow = Flow::new(&mut network, node, &source, &sink);
-    let mut max_flow = 0;
-    while max_flow < k + 1 && flow.step() {
-        max_flow += 1;
+    reset_cone_exits(network, &visited);
+
+    let source = source.into_iter().collect::<Vec<_>>();
+    let sink = sink.into_iter().collect::<Vec<_>>();
+    let mut flow = Flow::new(network, scratch, node, &source, &sink);
+    let max_flow = flow.max_flow(k + 1);
+
+    if max_flow > k {
+        return (p + 1, vec![node]);
+    }
+
+    let max_volume = flow.cut(&visited);
+    if cut_selection == CutSelection::MaxVolume {
+        return (p, max_volume);
+    }
+
+    // Nodes collapsed into the sink are on the sink side of every cut, but only
+    // those which still reach the sink on the residual graph are found by
+    // `sink_cut`, so the rest must be added back
+    let mut min_volume = flow.sink_cut(&visited);
+    for ni in collapsed {
+        if !min_volume.contains(&ni) {
+            min_volume.push(ni);
+        }
     }
 
-    if max_flow <= k {
-        (p, flow.cut(&visited))
+    if duplicated_count(network, node, &min_volume) < duplicated_count(network, node, &max_volume) {
+        (p, min_volume)
     } else {
-        (p + 1, vec![node])
+        (p, max_volume)
+    }
+}
+
+/// Gives every edge from a node inside the cone to a node outside of it no flow
+/// and no capacity.
+///
+/// The flow follows every descendent of a node, but edges leaving the cone
+/// still hold the flows and capacities left by labelling earlier nodes, which
+/// would otherwise add paths through nodes outside of the cone.
+fn reset_cone_exits<Ni: 'static + NodeIndex>(
+    network: &mut FlowMapBooleanNetwork<Ni>,
+    cone: &HashSet<Ni>,
+) {
+    for ni in cone {
+        let exits = network
+            .descendents(*ni)
+            .iter()
+            .filter(|descendent| !cone.contains(*descendent))
+            .copied()
+            .collect::<Vec<_>>();
+
+        for exit in exits {
+            *network.edge_value_mut(From(*ni), To(exit)) = (0, Capacity::Finite(0));
+        }
+    }
+}
+
+/// Returns the number of nodes inside the \bar{X} of `node`, other than `node`
+/// itself, which drive nodes outside of it or are POs. Each of them is needed
+/// by some other LUT too, so a LUT implementing \bar{X} duplicates it.
+///
+/// Every minimum cut has the same number of inputs, but they can differ in how
+/// many nodes they duplicate.
+fn duplicated_count<Ni: 'static + NodeIndex>(
+    network: &FlowMapBooleanNetwork<Ni>,
+    node: Ni,
+    x_bar: &[Ni],
+) -> usize {
+    x_bar
+        .iter()
+        .filter(|ni| **ni != node)
+        .filter(|ni| {
+            network.node_value(**ni).is_po
+                || network
+                    .descendents(**ni)
+                    .iter()
+                    .any(|descendent| !x_bar.contains(descendent))
+        })
+        .count()
+}
+
+/// Returns the \bar{X} for a node which extends from the node up to PIs and
+/// the nodes in `boundaries`, or `None` if it would have more than K inputs.
+///
+/// A boundary is only used as an input if its label is less than `required`,
+/// so that the node's label is at most `required`. Any other node is inside
+/// the \bar{X}.
+fn bounded_cut<Ni: 'static + NodeIndex + std::fmt::Debug>(
+    network: &FlowMapBooleanNetwork<Ni>,
+    node: Ni,
+    k: u32,
+    boundaries: &HashSet<Ni>,
+    labels: &[u32],
+    required: u32,
+) -> Option<Vec<Ni>> {
+    let mut x_bar = vec![node];
+    let mut inputs = HashSet::new();
+
+    let mut visited = HashSet::new();
+    visited.insert(node);
+    let mut s = vec![node];
+    while let Some(ni) = s.pop() {
+        for ancestor in network.ancestors(ni) {
+            if !visited.insert(*ancestor) {
+                continue;
+            }
+
+            let is_input = network.node_value(*ancestor).is_pi
+                || (boundaries.contains(ancestor) && labels[ancestor.node_index()] < required);
+
+            if is_input {
+                inputs.insert(*ancestor);
+                if inputs.len() > k as usize {
+                    return None;
+                }
+            } else {
+                x_bar.push(*ancestor);
+                s.push(*ancestor);
+            }
+        }
+    }
+
+    Some(x_bar)
+}
+
+/// Returns the nodes which the network's mapping would generate a LUT for, by
+/// following the \bar{X} of each node from the mapping roots.
+fn lut_outputs<Ni: 'static + NodeIndex + std::fmt::Debug>(
+    network: &FlowMapBooleanNetwork<Ni>,
+) -> HashSet<Ni> {
+    let mut outputs = HashSet::new();
+    let mut s = network.mapping_roots();
+
+    while let Some(ni) = s.pop() {
+        if network.node_value(ni).is_pi || !outputs.insert(ni) {
+            continue;
+        }
+
+        s.extend(map::inputs(network, &network.node_value(ni).x_bar));
+    }
+
+    outputs
+}
+
+/// Returns the nodes which have a path to at least one PO or latch next state,
+/// including those nodes themselves.
+fn live_nodes<Ni: 'static + NodeIndex>(network: &FlowMapBooleanNetwork<Ni>) -> HashSet<Ni> {
+    let mut live = HashSet::new();
+    let mut s = network.mapping_roots();
+
+    while let Some(ni) = s.pop() {
+        if live.insert(ni) {
+            s.extend_from_slice(network.ancestors(ni));
+        }
+    }
+
+    live
+}
+
+/// An error which occurs while labelling a network.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum LabelError<Ni: NodeIndex> {
+    /// The network contains a cycle through the given nodes, in the order of
+    /// the edges between them, so it can't be labelled in topological order.
+    Cycle(Vec<Ni>),
+}
+
+/// Panics if the network contains a cycle, which labelling can't handle.
+fn assert_acyclic<Ni: 'static + NodeIndex + std::fmt::Debug>(network: &FlowMapBooleanNetwork<Ni>) {
+    if let Some(cycle) = network.find_cycle() {
+        panic!("cannot label a network containing the cycle {:?}", cycle);
     }
 }
 
 /// Perform the FlowMap labelling pass on the entire network.
-pub fn label_network<Ni: 'static + NodeIndex + std::fmt::Debug>(
-    mut network: &mut FlowMapBooleanNetwork<Ni>,
+///
+/// Nodes without a path to any PO or latch next state are dead, and are left
+/// unlabelled with an empty \bar{X}, since no LUT will ever be generated for
+/// them.
+///
+/// Cuts are found for the largest LUT size allowed by `k`.
+///
+/// With the `rayon` feature, the nodes at each topological level are labelled
+/// in parallel.
+///
+/// Panics if the network contains a cycle. See `try_label_network` for a
+/// version which returns an error instead.
+pub fn label_network<Ni: 'static + LabelNodeIndex + std::fmt::Debug>(
+    network: FlowMapBooleanNetwork<Ni>,
+    k: impl Into<KSpec>,
+) -> LabeledNetwork<Ni> {
+    assert_acyclic(&network);
+
+    label_network_unchecked(network, k)
+}
+
+/// Perform the FlowMap labelling pass on the entire network, which must not
+/// contain a cycle.
+fn label_network_unchecked<Ni: 'static + LabelNodeIndex + std::fmt::Debug>(
+    network: FlowMapBooleanNetwork<Ni>,
+    k: impl Into<KSpec>,
+) -> LabeledNetwork<Ni> {
+    #[cfg(feature = "rayon")]
+    {
+        label_network_parallel(network, k)
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    {
+        label_network_with_unchecked(network, k, &mut |_, _| ControlFlow::Continue(()))
+            .expect("labelling to run to completion")
+    }
+}
+
+/// Returns the label and \bar{X} for a single node of the network, using a copy
+/// of the node's cone so that the network is left untouched.
+///
+/// This allows nodes whose cones overlap to be labelled at the same time.
+#[cfg(feature = "rayon")]
+fn label_node_isolated<Ni: 'static + NodeIndex + std::fmt::Debug>(
+    network: &FlowMapBooleanNetwork<Ni>,
+    scratch: &mut FlowScratch<Ni>,
+    node: Ni,
     k: u32,
-) {
+) -> (u32, Vec<Ni>) {
+    let mut cone = vec![node];
+    let mut indices = HashMap::new();
+    indices.insert(node, Ni::from_node_index(0));
+
+    let mut s = vec![node];
+    while let Some(ni) = s.pop() {
+        for ancestor in network.ancestors(ni) {
+            if !indices.contains_key(ancestor) {
+                indices.insert(*ancestor, Ni::from_node_index(cone.len()));
+                cone.push(*ancestor);
+                s.push(*ancestor);
+            }
+        }
+    }
+
+    let mut copy = FlowMapBooleanNetwork::new(Ni::from_node_index(cone.len() - 1));
+    for ni in &cone {
+        // Every field is copied so that labelling sees the same node as it
+        // would in the original network. The \bar{X} of a copied node still
+        // refers to the original network, but labelling never reads it.
+        let copy_ni = indices[ni];
+        *copy.node_value_mut(copy_ni) = network.node_value(*ni).clone();
+
+        for ancestor in network.ancestors(*ni) {
+            copy.add_edge(From(indices[ancestor]), To(copy_ni));
+        }
+    }
+
+    let (label, x_bar) = label_node(
+        &mut copy,
+        scratch,
+        indices[&node],
+        k,
+        CutSelection::MaxVolume,
+    );
+    let x_bar = x_bar.into_iter().map(|ni| cone[ni.node_index()]).collect();
+
+    (label, x_bar)
+}
+
+/// Perform the FlowMap labelling pass on the entire network, labelling the
+/// nodes at each topological level in parallel.
+///
+/// A node's level is one more than the deepest level of the nodes driving it,
+/// so every node at a level only depends on the labels of earlier levels. Each
+/// node is labelled with a copy of its cone, since the cones of nodes at the
+/// same level can overlap.
+///
+/// The network must not contain a cycle.
+#[cfg(feature = "rayon")]
+fn label_network_parallel<Ni: 'static + NodeIndex + Send + Sync + std::fmt::Debug>(
+    mut network: FlowMapBooleanNetwork<Ni>,
+    k: impl Into<KSpec>,
+) -> LabeledNetwork<Ni> {
+    use rayon::prelude::*;
+
+    let k = k.into().max();
+    let live = live_nodes(&network);
+
+    let mut levels = Vec::<Vec<Ni>>::new();
+    let mut node_levels = vec![0; network.node_count()];
     let mut topo = TopologicalOrder::new(&network);
+    while let Some(ni) = topo.next(&network) {
+        if network.node_value(ni).is_pi || !live.contains(&ni) {
+            continue;
+        }
+
+        let level = network
+            .ancestors(ni)
+            .iter()
+            .map(|ancestor| node_levels[ancestor.node_index()])
+            .max()
+            .unwrap_or(0)
+            + 1;
+        node_levels[ni.node_index()] = level;
+
+        if levels.len() < level {
+            levels.resize(level, vec![]);
+        }
+        levels[level - 1].push(ni);
+    }
+
+    for level in levels {
+        let labelled = level
+            .par_iter()
+            .map_init(FlowScratch::new, |scratch, ni| {
+                label_node_isolated(&network, scratch, *ni, k)
+            })
+            .collect::<Vec<_>>();
+
+        for (ni, (label, x_bar)) in level.into_iter().zip(labelled) {
+            network.node_value_mut(ni).label = Some(label);
+            network.node_value_mut(ni).x_bar = x_bar;
+        }
+    }
+
+    LabeledNetwork(network)
+}
+
+/// Perform the FlowMap labelling pass on the entire network, or return an
+/// error if the network contains a cycle.
+pub fn try_label_network<Ni: 'static + LabelNodeIndex + std::fmt::Debug>(
+    network: FlowMapBooleanNetwork<Ni>,
+    k: impl Into<KSpec>,
+) -> Result<LabeledNetwork<Ni>, LabelError<Ni>> {
+    if let Some(cycle) = network.find_cycle() {
+        return Err(LabelError::Cycle(cycle));
+    }
+
+    Ok(label_network_unchecked(network, k))
+}
+
+/// Perform the FlowMap labelling pass on the entire network, calling
+/// `progress` with the number of nodes labelled so far and the total number of
+/// nodes to label after each node is labelled.
+///
+/// If `progress` returns `ControlFlow::Break`, labelling stops and `None` is
+/// returned. Panics if the network contains a cycle.
+pub fn label_network_with<Ni: 'static + NodeIndex + std::fmt::Debug>(
+    network: FlowMapBooleanNetwork<Ni>,
+    k: impl Into<KSpec>,
+    progress: &mut impl FnMut(usize, usize) -> ControlFlow<()>,
+) -> Option<LabeledNetwork<Ni>> {
+    assert_acyclic(&network);
+
+    label_network_with_unchecked(network, k, progress)
+}
+
+/// Perform the FlowMap labelling pass as `label_network_with` does, on a
+/// network which must not contain a cycle.
+fn label_network_with_unchecked<Ni: 'static + NodeIndex + std::fmt::Debug>(
+    mut network: FlowMapBooleanNetwork<Ni>,
+    k: impl Into<KSpec>,
+    progress: &mut impl FnMut(usize, usize) -> ControlFlow<()>,
+) -> Option<LabeledNetwork<Ni>> {
+    let k = k.into().max();
+    let live = live_nodes(&network);
+    let total = live
+        .iter()
+        .filter(|ni| !network.node_value(**ni).is_pi)
+        .count();
+    let mut done = 0;
+    let mut topo = TopologicalOrder::new(&network);
+    let mut scratch = FlowScratch::new();
 
     while let Some(ni) = topo.next(&network) {
         let node_value = network.node_value(ni);
 
-        if node_value.is_pi {
+        if node_value.is_pi || !live.contains(&ni) {
+            continue;
+        }
+
+        let (label, x_bar) = label_node(&mut network, &mut scratch, ni, k, CutSelection::MaxVolume);
+        network.node_value_mut(ni).label = Some(label);
+        network.node_value_mut(ni).x_bar = x_bar;
+
+        done += 1;
+        if progress(done, total).is_break() {
+            return None;
+        }
+    }
+
+    Some(LabeledNetwork(network))
+}
+
+/// Perform a variant of the FlowMap labelling pass on the entire network which
+/// reduces the area of the mapping.
+///
+/// The labels are the same as those of `label_network`, but the \bar{X} of
+/// each node is whichever of the minimum cuts closest to the source and to the
+/// sink duplicates fewer nodes, i.e. contains fewer nodes which are also used
+/// outside of it. A label is final as soon as it's computed, so the label and
+/// \bar{X} of each node come from the same flow computation, rather than from
+/// a separate pass once every node is labelled.
+///
+/// As with `label_network`, dead nodes are left unlabelled, and a network
+/// containing a cycle causes a panic.
+pub fn label_network_two_pass<Ni: 'static + NodeIndex + std::fmt::Debug>(
+    mut network: FlowMapBooleanNetwork<Ni>,
+    k: impl Into<KSpec>,
+) -> LabeledNetwork<Ni> {
+    assert_acyclic(&network);
+
+    let k = k.into().max();
+    let live = live_nodes(&network);
+    let mut topo = TopologicalOrder::new(&network);
+    let mut scratch = FlowScratch::new();
+
+    while let Some(ni) = topo.next(&network) {
+        if network.node_value(ni).is_pi || !live.contains(&ni) {
             continue;
         }
 
-        let (label, x_bar) = label_node(&mut network, ni, k);
+        let (label, x_bar) = label_node(
+            &mut network,
+            &mut scratch,
+            ni,
+            k,
+            CutSelection::MinDuplication,
+        );
         network.node_value_mut(ni).label = Some(label);
         network.node_value_mut(ni).x_bar = x_bar;
     }
+
+    LabeledNetwork(network)
+}
+
+/// Perform the FlowMap labelling pass, then relax the labels of nodes off the
+/// critical path to reduce the number of LUTs, as long as the depth of the
+/// mapped network stays within `depth_budget`.
+///
+/// Each LUT of the depth-optimal mapping has a required level, the largest
+/// label it can take without any PO or latch next state exceeding the budget.
+/// Then, for each LUT in topological order, the \bar{X} of every LUT using it
+/// is extended up to the other LUTs and PIs instead, using a LUT as an input
+/// only if its label is less than the user's required level. If every such
+/// \bar{X} has at most K inputs, the LUT is no longer needed, so the mapping
+/// has one LUT fewer. Otherwise the LUT is kept.
+///
+/// If the budget is less than the depth of the depth-optimal mapping, that
+/// depth is used instead. As with `label_network`, dead nodes are left
+/// unlabelled, and a network containing a cycle causes a panic.
+pub fn label_network_relaxed<Ni: 'static + LabelNodeIndex + std::fmt::Debug>(
+    network: FlowMapBooleanNetwork<Ni>,
+    k: impl Into<KSpec>,
+    depth_budget: u32,
+) -> LabeledNetwork<Ni> {
+    let k = k.into();
+    let network = label_network(network, k.clone());
+    let depth_budget = std::cmp::max(depth_budget, max_depth(&network));
+    let mut network = network.into_inner();
+
+    let k = k.max();
+    let live = live_nodes(&network);
+    let roots = network.mapping_roots().into_iter().collect::<HashSet<_>>();
+    let mut lut_outputs = lut_outputs(&network);
+
+    let mut order = vec![];
+    let mut positions = vec![0; network.node_count()];
+    let mut topo = TopologicalOrder::new(&network);
+    while let Some(ni) = topo.next(&network) {
+        if !network.node_value(ni).is_pi && live.contains(&ni) {
+            positions[ni.node_index()] = order.len();
+            order.push(ni);
+        }
+    }
+
+    // The inputs of each LUT, and the LUTs using each node as an input
+    let mut inputs = HashMap::new();
+    let mut users = HashMap::<Ni, HashSet<Ni>>::new();
+    for ni in &lut_outputs {
+        let lut_inputs = map::inputs(&network, &network.node_value(*ni).x_bar);
+        for input in &lut_inputs {
+            users.entry(*input).or_default().insert(*ni);
+        }
+        inputs.insert(*ni, lut_inputs);
+    }
+
+    let mut labels = vec![0; network.node_count()];
+    let mut required = vec![u32::MAX; network.node_count()];
+    for ni in &order {
+        labels[ni.node_index()] = network
+            .node_value(*ni)
+            .label
+            .expect("live node to be labelled");
+    }
+    for root in &roots {
+        required[root.node_index()] = depth_budget;
+    }
+    for ni in order.iter().rev() {
+        for input in inputs.get(ni).into_iter().flatten() {
+            let input_required = required[ni.node_index()].saturating_sub(1);
+            required[input.node_index()] =
+                std::cmp::min(required[input.node_index()], input_required);
+        }
+    }
+
+    for ni in &order {
+        if roots.contains(ni) || !lut_outputs.contains(ni) {
+            continue;
+        }
+
+        // Check that every user of the LUT has a \bar{X} without it before
+        // changing any of them
+        lut_outputs.remove(ni);
+        let mut lut_users = users
+            .get(ni)
+            .into_iter()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+        let feasible = lut_users.iter().all(|user| {
+            let required = required[user.node_index()];
+            bounded_cut(&network, *user, k, &lut_outputs, &labels, required).is_some()
+        });
+
+        if !feasible {
+            lut_outputs.insert(*ni);
+            continue;
+        }
+
+        // Giving a user new inputs lowers the required levels of the LUTs
+        // generating them, which are never after it in topological order, so
+        // the users are changed from last to first, each with a \bar{X} found
+        // using the required levels left by the ones after it
+        lut_users.sort_by_key(|user| Reverse(positions[user.node_index()]));
+        for user in lut_users {
+            let required_level = required[user.node_index()];
+            let x_bar = match bounded_cut(&network, user, k, &lut_outputs, &labels, required_level)
+            {
+                Some(x_bar) => x_bar,
+                None => break,
+            };
+
+            let user_inputs = map::inputs(&network, &x_bar);
+            for input in inputs
+                .insert(user, user_inputs.clone())
+                .expect("LUT to have inputs")
+            {
+                users.entry(input).or_default().remove(&user);
+            }
+
+            // Each new input must stay below the user's required level, as
+            // must the inputs of its LUT in turn
+            let mut s = vec![];
+            for input in &user_inputs {
+                users.entry(*input).or_default().insert(user);
+
+                if required_level - 1 < required[input.node_index()] {
+                    required[input.node_index()] = required_level - 1;
+                    s.push(*input);
+                }
+            }
+            while let Some(ni) = s.pop() {
+                for input in inputs.get(&ni).into_iter().flatten() {
+                    let input_required = required[ni.node_index()] - 1;
+                    if input_required < required[input.node_index()] {
+                        required[input.node_index()] = input_required;
+                        s.push(*input);
+                    }
+                }
+            }
+
+            // The user may now be deeper, as may every LUT using it in turn,
+            // but none beyond its required level
+            labels[user.node_index()] = user_inputs
+                .iter()
+                .map(|input| labels[input.node_index()] + 1)
+                .max()
+                .unwrap_or(1);
+            let mut s = vec![user];
+            while let Some(ni) = s.pop() {
+                debug_assert!(labels[ni.node_index()] <= required[ni.node_index()]);
+
+                for user in users.get(&ni).into_iter().flatten() {
+                    if labels[ni.node_index()] + 1 > labels[user.node_index()] {
+                        labels[user.node_index()] = labels[ni.node_index()] + 1;
+                        s.push(*user);
+                    }
+                }
+            }
+
+            network.node_value_mut(user).x_bar = x_bar;
+        }
+
+        // If a user couldn't be changed after all, the LUT is still needed
+        if users.get(ni).into_iter().flatten().next().is_none() {
+            for input in inputs.remove(ni).expect("LUT to have inputs") {
+                users.entry(input).or_default().remove(ni);
+            }
+        } else {
+            lut_outputs.insert(*ni);
+        }
+    }
+
+    // Relabel every node as one more than the deepest input of its \bar{X}
+    for ni in order {
+        let label = map::inputs(&network, &network.node_value(ni).x_bar)
+            .iter()
+            .map(|input| {
+                network
+                    .node_value(*input)
+                    .label
+                    .expect("input to be labelled")
+                    + 1
+            })
+            .max()
+            .unwrap_or(1);
+
+        network.node_value_mut(ni).label = Some(label);
+    }
+
+    LabeledNetwork(network)
+}
+
+/// Returns the depth of the mapped network, i.e. the largest label of any PO
+/// or latch next state, or 0 if there are none.
+///
+/// The label of an individual node is its `label` in `node_value`.
+pub fn max_depth<Ni: 'static + NodeIndex>(network: &LabeledNetwork<Ni>) -> u32 {
+    network
+        .mapping_roots()
+        .iter()
+        .filter_map(|ni| network.node_value(*ni).label)
+        .max()
+        .unwrap_or(0)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::assert_equiv;
+    use crate::flowmap::builder::NetworkBuilder;
 
     #[test]
     fn topological_order() {
@@ -187,7 +940,30 @@ mod tests {
     }
 
     #[test]
-    fn label() {
+    fn topological_order_restart() {
+        let mut network = BooleanNetwork::<(), (), usize>::new(3);
+        network.add_edge(From(0), To(1));
+        network.add_edge(From(1), To(2));
+        network.add_edge(From(2), To(3));
+
+        let mut topo = TopologicalOrder::new(&network);
+        assert_eq!(topo.next(&network), Some(0));
+        assert_eq!(topo.next(&network), Some(1));
+
+        topo.restart(&network);
+        assert_eq!(topo.next(&network), Some(0));
+        assert_eq!(topo.next(&network), Some(1));
+        assert_eq!(topo.next(&network), Some(2));
+        assert_eq!(topo.next(&network), Some(3));
+        assert_eq!(topo.next(&network), None);
+
+        topo.restart(&network);
+        assert_eq!(topo.next(&network), Some(0));
+    }
+
+    /// Returns the network of Fig. 5(a) from the FlowMap paper, with its PIs
+    /// marked and labelled.
+    fn get_fig_5a_network() -> FlowMapBooleanNetwork<usize> {
         // Fig. 5(a) from FlowMap paper, numbered top-to-bottom, left-to-right.
         let mut network = FlowMapBooleanNetwork::<usize>::new(12);
 
@@ -225,7 +1001,47 @@ mod tests {
         node_value.label = Some(0);
         node_value.is_pi = true;
 
-        label_network(&mut network, 3);
+        // Mark PO node
+        network.node_value_mut(12).is_po = true;
+
+        network
+    }
+
+    #[test]
+    fn topological_order_with_priority() {
+        // 0 -> 1 -> 3
+        // |         ^
+        // \--> 2 ---/
+        let mut network = BooleanNetwork::<(), (), usize>::new(3);
+        network.add_edge(From(0), To(1));
+        network.add_edge(From(0), To(2));
+        network.add_edge(From(1), To(3));
+        network.add_edge(From(2), To(3));
+
+        // Both 1 and 2 are ready after 0, so the priority decides between them
+        let mut topo = TopologicalOrder::new_with_priority(&network, |ni| ni as i64);
+        assert_eq!(topo.next(&network), Some(0));
+        assert_eq!(topo.next(&network), Some(2));
+        assert_eq!(topo.next(&network), Some(1));
+        assert_eq!(topo.next(&network), Some(3));
+        assert_eq!(topo.next(&network), None);
+
+        let mut topo = TopologicalOrder::new_with_priority(&network, |ni| -(ni as i64));
+        assert_eq!(topo.next(&network), Some(0));
+        assert_eq!(topo.next(&network), Some(1));
+        assert_eq!(topo.next(&network), Some(2));
+        assert_eq!(topo.next(&network), Some(3));
+
+        // Restarting keeps the priorities
+        topo.restart(&network);
+        assert_eq!(topo.next(&network), Some(0));
+        assert_eq!(topo.next(&network), Some(1));
+    }
+
+    #[test]
+    fn label() {
+        let network = get_fig_5a_network();
+        let network = label_network(network, 3);
 
         // The label of PI nodes should not have changed
         assert_eq!(network.node_value(0).label, Some(0));
@@ -255,6 +1071,66 @@ mod tests {
         assert_equiv!(&network.node_value(12).x_bar, [8, 9, 10, 11, 12]);
     }
 
+    #[test]
+    fn label_two_pass() {
+        // 4 and 5 are POs, so they're generated by LUTs of their own, and the
+        // largest \bar{X} of 7 would duplicate 5
+        let get_network = || {
+            let mut network = FlowMapBooleanNetwork::<usize>::new(7);
+            network.add_edge(From(0), To(4));
+            network.add_edge(From(2), To(4));
+            network.add_edge(From(1), To(5));
+            network.add_edge(From(3), To(5));
+            network.add_edge(From(4), To(6));
+            network.add_edge(From(5), To(6));
+            network.add_edge(From(6), To(7));
+            network.add_edge(From(1), To(7));
+
+            for pi in 0..=3 {
+                network.node_value_mut(pi).is_pi = true;
+                network.node_value_mut(pi).label = Some(0);
+            }
+            network.node_value_mut(4).is_po = true;
+            network.node_value_mut(5).is_po = true;
+            network.node_value_mut(7).is_po = true;
+
+            network
+        };
+
+        let single_pass = label_network(get_network(), 3);
+        let network = label_network_two_pass(get_network(), 3);
+
+        for ni in 0..network.node_count() {
+            assert_eq!(
+                network.node_value(ni).label,
+                single_pass.node_value(ni).label
+            );
+        }
+
+        assert_equiv!(&single_pass.node_value(7).x_bar, [5, 6, 7]);
+        assert_equiv!(&network.node_value(7).x_bar, [6, 7]);
+    }
+
+    #[test]
+    fn label_single_pi_ancestor() {
+        // --0-->|~|>--1-->|~|>--2--
+        let mut network = FlowMapBooleanNetwork::<usize>::new(2);
+        network.add_edge(From(0), To(1));
+        network.add_edge(From(1), To(2));
+
+        // The PI is deliberately left unlabelled, since the label of a single
+        // PI ancestor is never needed
+        network.node_value_mut(0).is_pi = true;
+        network.node_value_mut(2).is_po = true;
+
+        let network = label_network(network, 3);
+
+        assert_eq!(network.node_value(1).label, Some(1));
+        assert_equiv!(&network.node_value(1).x_bar, [1]);
+        assert_eq!(network.node_value(2).label, Some(1));
+        assert_equiv!(&network.node_value(2).x_bar, [1, 2]);
+    }
+
     #[test]
     fn label_uncollapsed_nodes_feed_sink() {
         // The following network contains a node, 4, which has an input from a
@@ -269,22 +1145,311 @@ mod tests {
         // 0   1    2
         // |   v    v
         // \-> 3 -> 4
-        let mut network = FlowMapBooleanNetwork::new(4);
-        network.add_edge(From(0), To(3));
-        network.add_edge(From(1), To(3));
-        network.add_edge(From(2), To(4));
-        network.add_edge(From(3), To(4));
+        let network = NetworkBuilder::<usize>::new()
+            .input(0)
+            .input(1)
+            .input(2)
+            .and(3, 0, 1)
+            .and(4, 2, 3)
+            .output(4)
+            .build();
 
-        // Mark PI nodes and give label of 0
-        for pi in &[0, 1, 2] {
-            let node_value = network.node_value_mut(*pi);
-            node_value.label = Some(0);
-            node_value.is_pi = true;
+        let network = label_network(network, 2);
+
+        assert_eq!(network.node_value(3).label, Some(1));
+        assert_eq!(network.node_value(4).label, Some(2));
+    }
+
+    #[test]
+    fn try_label_network_cycle() {
+        // 0 -> 1 -> 2 -> 3 -> 2, where 3 is the PO
+        let mut network = NetworkBuilder::<usize>::new()
+            .input(0)
+            .inverter(1, 0)
+            .inverter(2, 1)
+            .inverter(3, 2)
+            .output(3)
+            .build();
+        network.add_edge(From(3), To(2));
+
+        assert_eq!(
+            try_label_network(network, 3).err(),
+            Some(LabelError::Cycle(vec![2, 3]))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot label a network containing the cycle [2, 3]")]
+    fn label_network_cycle() {
+        let mut network = NetworkBuilder::<usize>::new()
+            .input(0)
+            .inverter(1, 0)
+            .inverter(2, 1)
+            .inverter(3, 2)
+            .output(3)
+            .build();
+        network.add_edge(From(3), To(2));
+
+        label_network(network, 3);
+    }
+
+    #[test]
+    fn max_depth_test() {
+        // y = (a & b) & (c & d), which is 2 levels deep with 2-input LUTs but
+        // only 1 with 4-input LUTs
+        let builder = NetworkBuilder::<usize>::new()
+            .input(0)
+            .input(1)
+            .input(2)
+            .input(3)
+            .and(4, 0, 1)
+            .and(5, 2, 3)
+            .and(6, 4, 5)
+            .output(4)
+            .output(6)
+            .clone();
+
+        assert_eq!(max_depth(&label_network(builder.build(), 2)), 2);
+        assert_eq!(max_depth(&label_network(builder.build(), 4)), 1);
+    }
+
+    #[test]
+    fn max_depth_no_outputs() {
+        let network = NetworkBuilder::<usize>::new()
+            .input(0)
+            .inverter(1, 0)
+            .build();
+
+        assert_eq!(max_depth(&label_network(network, 2)), 0);
+    }
+
+    #[test]
+    fn label_excludes_nodes_behind_inputs() {
+        // Node 7 is on the sink side of the cut for node 12, since the flow
+        // through node 6 saturates node 0, but it only drives node 12 through
+        // node 9, which is an input
+        let network = NetworkBuilder::<usize>::new()
+            .input(0)
+            .input(1)
+            .input(2)
+            .input(3)
+            .input(4)
+            .input(5)
+            .inverter(6, 0)
+            .and(7, 6, 0)
+            .and(8, 7, 2)
+            .and(9, 8, 5)
+            .and(10, 3, 4)
+            .and(11, 9, 10)
+            .and(12, 11, 6)
+            .and(13, 12, 1)
+            .output(13)
+            .build();
+
+        let network = label_network(network, 3);
+        assert_eq!(network.node_value(12).label, Some(2));
+        assert_equiv!(&network.node_value(12).x_bar, [12, 11, 6]);
+        assert_eq!(map::map(&network, 3, &*network).unwrap().len(), 4);
+    }
+
+    fn get_relaxed_network() -> FlowMapBooleanNetwork<usize> {
+        NetworkBuilder::new()
+            .input(0)
+            .input(1)
+            .input(2)
+            .input(3)
+            .input(4)
+            .and(5, 3, 2)
+            .and(6, 1, 3)
+            .and(7, 4, 1)
+            .and(8, 6, 7)
+            .and(9, 5, 0)
+            .and(10, 8, 9)
+            .and(11, 10, 3)
+            .and(12, 4, 11)
+            .and(13, 5, 7)
+            .and(14, 5, 13)
+            .output(6)
+            .output(10)
+            .output(11)
+            .output(12)
+            .output(13)
+            .output(14)
+            .build()
+    }
+
+    #[test]
+    fn label_network_relaxed_test() {
+        let network = label_network(get_relaxed_network(), 3);
+        assert_eq!(max_depth(&network), 3);
+        assert_eq!(map::map(&network, 3, &*network).unwrap().len(), 10);
+
+        // Even without slack, some LUTs can be removed without increasing the
+        // depth
+        let network = label_network_relaxed(get_relaxed_network(), 3, 3);
+        assert_eq!(max_depth(&network), 3);
+        assert_eq!(map::map(&network, 3, &*network).unwrap().len(), 9);
+
+        let network = label_network_relaxed(get_relaxed_network(), 3, 4);
+        assert_eq!(max_depth(&network), 4);
+        assert_eq!(map::map(&network, 3, &*network).unwrap().len(), 8);
+    }
+
+    #[test]
+    fn label_network_relaxed_budget_too_small() {
+        let network = label_network_relaxed(get_relaxed_network(), 3, 1);
+        assert_eq!(max_depth(&network), 3);
+        assert_eq!(map::map(&network, 3, &*network).unwrap().len(), 9);
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn label_network_parallel_matches_serial() {
+        let networks: [fn() -> FlowMapBooleanNetwork<usize>; 3] = [
+            get_relaxed_network,
+            get_fig_5a_network,
+            get_dead_nodes_network,
+        ];
+
+        for network in &networks {
+            for k in 2..=4 {
+                let serial =
+                    label_network_with(network(), k, &mut |_, _| ControlFlow::Continue(()))
+                        .unwrap();
+                let parallel = label_network_parallel(network(), k);
+
+                for ni in serial.nodes() {
+                    assert_eq!(parallel.node_value(ni).label, serial.node_value(ni).label);
+                    assert_equiv!(&parallel.node_value(ni).x_bar, &serial.node_value(ni).x_bar);
+                }
+            }
         }
+    }
+
+    #[test]
+    #[should_panic(expected = "node being labelled to have ancestors")]
+    fn label_network_no_ancestors() {
+        // A node which isn't a PI can't be labelled without ancestors, with
+        // or without the rayon feature
+        let mut network = FlowMapBooleanNetwork::<usize>::new(0);
+        network.node_value_mut(0).is_po = true;
+
+        label_network(network, 3);
+    }
+
+    #[test]
+    fn label_node_capacity() {
+        // With a capacity of 2, PI 2 alone can carry as much flow as K allows,
+        // so node 4 can't share a LUT with node 3
+        let mut network = NetworkBuilder::<usize>::new()
+            .input(0)
+            .input(1)
+            .input(2)
+            .and(3, 0, 1)
+            .and(4, 3, 2)
+            .output(4)
+            .build();
+        network.node_value_mut(2).capacity = 2;
+
+        let network = label_network(network, 3);
+
+        assert_eq!(network.node_value(3).label, Some(1));
+        assert_eq!(network.node_value(4).label, Some(2));
+        assert_eq!(network.node_value(4).x_bar, vec![4]);
+    }
+
+    /// Returns a network where node 4 has no path to the PO, 3.
+    ///
+    /// 0 -> 2 -> 3 <- 1 -> 4
+    fn get_dead_nodes_network() -> FlowMapBooleanNetwork<usize> {
+        NetworkBuilder::new()
+            .input(0)
+            .input(1)
+            .inverter(2, 0)
+            .and(3, 1, 2)
+            .inverter(4, 1)
+            .output(3)
+            .build()
+    }
 
-        label_network(&mut network, 2);
+    #[test]
+    fn label_dead_nodes() {
+        // Node 4 is dead, so it should never be labelled
+        let network = label_network(get_dead_nodes_network(), 3);
 
+        assert_eq!(network.node_value(2).label, Some(1));
         assert_eq!(network.node_value(3).label, Some(1));
+        assert_eq!(network.node_value(4).label, None);
+        assert!(network.node_value(4).x_bar.is_empty());
+    }
+
+    #[test]
+    fn label_network_with_progress() {
+        let mut calls = vec![];
+        let network = label_network_with(get_fig_5a_network(), 3, &mut |done, total| {
+            calls.push((done, total));
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+
+        assert_eq!(network.node_value(12).label, Some(2));
+        assert_eq!(calls, (1..=8).map(|done| (done, 8)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn label_network_with_cancel() {
+        let mut calls = 0;
+        let network = label_network_with(get_fig_5a_network(), 3, &mut |done, _| {
+            calls += 1;
+            if done == 2 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+
+        assert!(network.is_none());
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn label_overlapping_cones() {
+        // Labelling 6 leaves flow on the edge from 3 to 6, which is outside of
+        // the cone of 8 and must not add a path to the flow for 8
+        //
+        // 2 -> 3 <- 1 -> 5 <- 0
+        //      3 -> 4 <- 0
+        //      3 -> 6 <- 1, 6 <- 5, 6 -> 7
+        //      4 -> 8
+        let mut network = FlowMapBooleanNetwork::<usize>::new(8);
+        for (from, to) in &[
+            (2, 3),
+            (1, 3),
+            (3, 4),
+            (0, 4),
+            (0, 5),
+            (1, 5),
+            (5, 6),
+            (3, 6),
+            (1, 6),
+            (6, 7),
+            (4, 8),
+        ] {
+            network.add_edge(From(*from), To(*to));
+        }
+
+        for pi in 0..=2 {
+            network.node_value_mut(pi).label = Some(0);
+            network.node_value_mut(pi).is_pi = true;
+        }
+        network.node_value_mut(7).is_po = true;
+        network.node_value_mut(8).is_po = true;
+
+        let network = label_network(network, 2);
+
         assert_eq!(network.node_value(4).label, Some(2));
+        assert_eq!(network.node_value(7).label, Some(3));
+        assert_eq!(network.node_value(8).label, Some(2));
+        assert_equiv!(&network.node_value(8).x_bar, [4, 8]);
     }
 }
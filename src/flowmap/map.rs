@@ -1,17 +1,35 @@
+use super::label::{self, LabeledNetwork};
 use super::*;
-use hashbrown::HashSet;
+use crate::evaluate::EvaluateLUT;
+use crate::truth_table::TruthTable;
+use hashbrown::{HashMap, HashSet};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct LUT<Ni: NodeIndex> {
     /// The node that the LUT generates.
     pub output: Ni,
-    /// The nodes which serve as inputs to the LUT.
+    /// The nodes which serve as inputs to the LUT, i.e. every node outside of
+    /// `contains` which drives a node inside of `contains`.
     pub inputs: Vec<Ni>,
-    /// The nodes which the LUT replaces.
-    pub contains: Vec<Ni>,
+    /// The nodes whose logic is implemented inside the LUT, i.e. the \bar{X}
+    /// of the cut which generated the LUT. This always includes `output`.
+    pub contains: HashSet<Ni>,
+    /// The output of the LUT for every combination of its inputs, as computed
+    /// by the evaluator passed to `map`.
+    pub truth_table: TruthTable,
+    /// The number of LUTs on the longest path from a PI to the output of the
+    /// LUT, including the LUT itself. A LUT whose inputs are all PIs has a
+    /// depth of 1.
+    pub depth: u32,
+    /// The size of the LUT which implements this LUT, i.e. the smallest size
+    /// allowed by the `KSpec` passed to `map` which fits its inputs. Any
+    /// inputs beyond `inputs` are unused.
+    pub k_used: u32,
 }
 
-fn inputs<Ni: 'static + NodeIndex + std::fmt::Debug>(
+/// Returns the distinct nodes outside of \bar{X} which are inputs to nodes
+/// inside it, i.e. the inputs of the LUT it would produce.
+pub(super) fn inputs<Ni: 'static + NodeIndex + std::fmt::Debug>(
     network: &FlowMapBooleanNetwork<Ni>,
     x_bar: &[Ni],
 ) -> Vec<Ni> {
@@ -28,59 +46,451 @@ fn inputs<Ni: 'static + NodeIndex + std::fmt::Debug>(
     inputs
 }
 
+/// An error which occurs while generating the LUTs for a labelled network.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum MappingError<Ni: NodeIndex> {
+    /// The \bar{X} of a node would produce a LUT with more than K inputs,
+    /// which means either labelling or an override produced a bad \bar{X}.
+    XbarTooLarge {
+        /// The node whose LUT was being generated.
+        node: Ni,
+        /// The \bar{X} of the node.
+        x_bar: Vec<Ni>,
+        /// The label of every node in \bar{X}, in the same order.
+        x_bar_labels: Vec<Option<u32>>,
+        /// The inputs which \bar{X} would have as a LUT.
+        inputs: Vec<Ni>,
+        /// The number of inputs which \bar{X} would have as a LUT.
+        computed_input_count: usize,
+        k: u32,
+    },
+    /// The \bar{X} of a node would produce a LUT without any inputs, which
+    /// means an override included every PI which drives the node.
+    NoInputs {
+        /// The node whose LUT was being generated.
+        node: Ni,
+        /// The \bar{X} of the node.
+        x_bar: Vec<Ni>,
+    },
+}
+
+/// Generates the LUTs which implement the labelled network.
+///
+/// The truth table of each LUT is computed once, with `evaluator`, as the LUT
+/// is generated. Each LUT uses the smallest size allowed by `k` which fits its
+/// inputs. The LUTs are ordered by the index of their output, so the same
+/// network always gives the same LUTs in the same order.
 pub fn map<Ni: 'static + NodeIndex + std::fmt::Debug>(
-    network: &FlowMapBooleanNetwork<Ni>,
-    k: u32,
-) -> Vec<LUT<Ni>> {
+    network: &LabeledNetwork<Ni>,
+    k: impl Into<KSpec>,
+    evaluator: &impl EvaluateLUT<Ni>,
+) -> Result<Vec<LUT<Ni>>, MappingError<Ni>> {
+    map_with_overrides(network, k, evaluator, &HashMap::new())
+}
+
+/// Generates the LUTs which implement the labelled network, using the \bar{X}
+/// sets in `overrides` in place of those found by labelling for the nodes they
+/// contain.
+///
+/// As with the \bar{X} sets found by labelling, the overriding sets must
+/// result in LUTs with at most K inputs, otherwise an error is returned. The
+/// LUTs are ordered as for `map`.
+pub fn map_with_overrides<Ni: 'static + NodeIndex + std::fmt::Debug>(
+    network: &LabeledNetwork<Ni>,
+    k: impl Into<KSpec>,
+    evaluator: &impl EvaluateLUT<Ni>,
+    overrides: &HashMap<Ni, Vec<Ni>>,
+) -> Result<Vec<LUT<Ni>>, MappingError<Ni>> {
+    let k = k.into();
     let mut done = HashSet::new();
     let mut luts = vec![];
 
-    let mut s = (0..network.node_count())
-        .map(Ni::from_node_index)
-        .filter(|ni| network.node_value(*ni).is_po)
-        .collect::<Vec<_>>();
+    let mut s = network.mapping_roots();
     while let Some(n) = s.pop() {
         if !done.insert(n) {
             continue;
         }
 
+        // PIs are never generated by a LUT, even when they're also POs, since
+        // the backends connect such a feedthrough directly from its input.
+        // Latch outputs are PIs, which are generated by their latch.
         let node_value = network.node_value(n);
-        if node_value.is_pi && !node_value.is_po {
+        if node_value.is_pi {
             continue;
         }
 
-        let inputs = inputs(&network, &node_value.x_bar);
-        luts.push(LUT {
+        let x_bar = overrides.get(&n).unwrap_or(&node_value.x_bar);
+        let inputs = inputs(network, x_bar);
+
+        // Labelling never puts a PI in \bar{X}, so this means an override did
+        if inputs.is_empty() {
+            return Err(MappingError::NoInputs {
+                node: n,
+                x_bar: x_bar.clone(),
+            });
+        }
+
+        // Labelling only produces \bar{X} sets with at most K inputs, so this
+        // means either labelling or an override is wrong
+        let k_used = match k.fit(inputs.len()) {
+            Some(k_used) => k_used,
+            None => {
+                return Err(MappingError::XbarTooLarge {
+                    node: n,
+                    x_bar: x_bar.clone(),
+                    x_bar_labels: x_bar
+                        .iter()
+                        .map(|ni| network.node_value(*ni).label)
+                        .collect(),
+                    computed_input_count: inputs.len(),
+                    inputs,
+                    k: k.max(),
+                })
+            }
+        };
+
+        let mut lut = LUT {
             output: n,
             inputs: inputs.clone(),
-            contains: node_value.x_bar.clone(),
-        });
-
-        let num_inputs = inputs.len();
-        assert!(
-            num_inputs > 0 && num_inputs <= (k as usize),
-            "number of inputs to LUT generating {:?} was {}, however K is {}",
-            n,
-            num_inputs,
-            k
-        );
+            contains: x_bar.iter().copied().collect(),
+            truth_table: TruthTable::default(),
+            depth: 0,
+            k_used,
+        };
+        lut.truth_table = evaluator.truth_table(&lut);
+        luts.push(lut);
 
         for i in inputs {
             s.push(i);
         }
     }
 
+    // The worklist visits nodes in an order which depends on how the network
+    // was built, so sort the LUTs to keep the output of the backends stable
+    luts.sort_by_key(|lut| lut.output.node_index());
+    assign_depths(&mut luts);
+
+    if cfg!(debug_assertions) {
+        assert_all_pos_covered(network, &luts);
+
+        for lut in &luts {
+            assert!(
+                lut_is_consistent(lut, network),
+                "LUT generating {:?} is inconsistent with the network (inputs {:?}, contains {:?})",
+                lut.output,
+                lut.inputs,
+                lut.contains
+            );
+        }
+    }
+
+    Ok(luts)
+}
+
+/// Reduces the number of LUTs by merging each LUT into the LUT which uses its
+/// output, wherever that LUT is the only one which uses it and the merged LUT
+/// has at most K inputs.
+///
+/// The LUTs generating POs and latch next states are never merged away, and a
+/// merge is skipped if the merged LUT would be deeper than the LUT it replaces,
+/// so no LUT becomes deeper than it was or than its output's label. The truth
+/// table of each merged LUT is computed with `evaluator`.
+pub fn pack_area<Ni: 'static + NodeIndex + std::fmt::Debug>(
+    network: &LabeledNetwork<Ni>,
+    luts: Vec<LUT<Ni>>,
+    k: impl Into<KSpec>,
+    evaluator: &impl EvaluateLUT<Ni>,
+) -> Vec<LUT<Ni>> {
+    let k = k.into();
+    let roots = network.mapping_roots().into_iter().collect::<HashSet<_>>();
+    let original_depths = luts
+        .iter()
+        .map(|lut| (lut.output, lut.depth))
+        .collect::<HashMap<_, _>>();
+
+    // Merged LUTs keep the index of the LUT they replace, so the index of the
+    // LUT generating each node never changes
+    let lut_index = luts
+        .iter()
+        .enumerate()
+        .map(|(i, lut)| (lut.output, i))
+        .collect::<HashMap<_, _>>();
+    let mut fanouts = vec![HashSet::new(); luts.len()];
+    for (i, lut) in luts.iter().enumerate() {
+        for input in &lut.inputs {
+            if let Some(&j) = lut_index.get(input) {
+                fanouts[j].insert(i);
+            }
+        }
+    }
+
+    let mut luts = luts.into_iter().map(Some).collect::<Vec<_>>();
+
+    // Visit every LUT in order, then revisit the LUTs whose inputs or fanouts
+    // change when LUTs are merged
+    let mut worklist = (0..luts.len()).rev().collect::<Vec<_>>();
+    while let Some(i) = worklist.pop() {
+        let (j, mut merged) = match find_merge(network, &luts, &lut_index, &fanouts, i, &k, &roots)
+        {
+            Some(merge) => merge,
+            None => continue,
+        };
+        merged.truth_table = evaluator.truth_table(&merged);
+
+        let removed = luts[i].take().unwrap();
+        let replaced = luts[j].replace(merged).unwrap();
+        for input in removed.inputs.iter().chain(&replaced.inputs) {
+            if let Some(&l) = lut_index.get(input) {
+                fanouts[l].remove(&i);
+                fanouts[l].remove(&j);
+            }
+        }
+        fanouts[i].clear();
+
+        for input in &luts[j].as_ref().unwrap().inputs {
+            if let Some(&l) = lut_index.get(input) {
+                fanouts[l].insert(j);
+                worklist.push(l);
+            }
+        }
+        worklist.push(j);
+    }
+
+    let mut luts = luts.into_iter().flatten().collect::<Vec<_>>();
+    assign_depths(&mut luts);
+
+    for lut in &luts {
+        debug_assert!(
+            lut.depth <= original_depths[&lut.output],
+            "merging LUTs increased the depth of the LUT generating {:?} from {} to {}",
+            lut.output,
+            original_depths[&lut.output],
+            lut.depth
+        );
+
+        if let Some(label) = network.node_value(lut.output).label {
+            debug_assert!(
+                lut.depth <= label,
+                "LUT generating {:?} has depth {}, which exceeds its label {}",
+                lut.output,
+                lut.depth,
+                label
+            );
+        }
+
+        debug_assert!(
+            lut_is_consistent(lut, network),
+            "LUT generating {:?} is inconsistent with the network (inputs {:?}, contains {:?})",
+            lut.output,
+            lut.inputs,
+            lut.contains
+        );
+    }
+
     luts
 }
 
+/// Returns the merge of the LUT at index `i` into the only LUT which uses its
+/// output, as the index of the latter and the merged LUT which replaces it, if
+/// `pack_area` can merge them.
+///
+/// The merged LUT has no truth table. Its depth is computed from the depths of
+/// the LUTs generating its inputs, and the merge is rejected if that is deeper
+/// than the LUT it replaces, as every LUT using its output would be deeper too.
+fn find_merge<Ni: 'static + NodeIndex + std::fmt::Debug>(
+    network: &FlowMapBooleanNetwork<Ni>,
+    luts: &[Option<LUT<Ni>>],
+    lut_index: &HashMap<Ni, usize>,
+    fanouts: &[HashSet<usize>],
+    i: usize,
+    k: &KSpec,
+    roots: &HashSet<Ni>,
+) -> Option<(usize, LUT<Ni>)> {
+    let lut = luts[i].as_ref()?;
+    if roots.contains(&lut.output) {
+        return None;
+    }
+
+    let mut i_fanouts = fanouts[i].iter();
+    let j = match (i_fanouts.next(), i_fanouts.next()) {
+        (Some(&j), None) => j,
+        _ => return None,
+    };
+    let fanout = luts[j].as_ref()?;
+
+    let mut contains = lut
+        .contains
+        .union(&fanout.contains)
+        .copied()
+        .collect::<Vec<_>>();
+    contains.sort_by_key(|ni| ni.node_index());
+
+    let inputs = inputs(network, &contains);
+    let k_used = k.fit(inputs.len())?;
+
+    // Inputs which aren't generated by a LUT are PIs
+    let depth = 1 + inputs
+        .iter()
+        .filter_map(|ni| lut_index.get(ni))
+        .filter_map(|&l| luts[l].as_ref())
+        .map(|input_lut| input_lut.depth)
+        .max()
+        .unwrap_or(0);
+    if depth > fanout.depth {
+        return None;
+    }
+
+    let merged = LUT {
+        output: fanout.output,
+        inputs,
+        contains: contains.into_iter().collect(),
+        truth_table: TruthTable::default(),
+        depth,
+        k_used,
+    };
+
+    Some((j, merged))
+}
+
+/// Sets the depth of every LUT, based on the depths of the LUTs which generate
+/// its inputs.
+fn assign_depths<Ni: 'static + NodeIndex + std::fmt::Debug>(luts: &mut [LUT<Ni>]) {
+    let lut_index = luts
+        .iter()
+        .enumerate()
+        .map(|(i, lut)| (lut.output, i))
+        .collect::<HashMap<_, _>>();
+    let mut depths = vec![None; luts.len()];
+
+    for i in 0..luts.len() {
+        let mut s = vec![i];
+        while let Some(&j) = s.last() {
+            if depths[j].is_some() {
+                s.pop();
+                continue;
+            }
+
+            // Inputs which aren't generated by a LUT are PIs
+            let input_luts = luts[j]
+                .inputs
+                .iter()
+                .filter_map(|ni| lut_index.get(ni).copied())
+                .collect::<Vec<_>>();
+            let pending = input_luts
+                .iter()
+                .filter(|k| depths[**k].is_none())
+                .copied()
+                .collect::<Vec<_>>();
+
+            if pending.is_empty() {
+                let max_input_depth = input_luts.iter().map(|k| depths[*k].unwrap()).max();
+                depths[j] = Some(1 + max_input_depth.unwrap_or(0));
+                s.pop();
+            } else {
+                s.extend(pending);
+            }
+        }
+    }
+
+    for (lut, depth) in luts.iter_mut().zip(depths) {
+        lut.depth = depth.unwrap();
+    }
+}
+
+/// Returns `true` if the LUT contains its output, and its inputs are exactly
+/// the nodes outside of the LUT which drive a node inside of the LUT.
+pub fn lut_is_consistent<Ni: 'static + NodeIndex>(
+    lut: &LUT<Ni>,
+    network: &FlowMapBooleanNetwork<Ni>,
+) -> bool {
+    let expected_inputs = lut
+        .contains
+        .iter()
+        .flat_map(|ni| network.ancestors(*ni))
+        .filter(|ni| !lut.contains.contains(*ni))
+        .copied()
+        .collect::<HashSet<_>>();
+    let inputs = lut.inputs.iter().copied().collect::<HashSet<_>>();
+
+    lut.contains.contains(&lut.output)
+        && inputs.len() == lut.inputs.len()
+        && inputs == expected_inputs
+}
+
+/// Statistics describing the quality of a mapping.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct MapStats {
+    /// The number of LUTs in the mapping.
+    pub lut_count: usize,
+    /// The largest label of any node which the mapping must generate, i.e.
+    /// the number of LUTs on the longest path from a PI to a PO or latch.
+    pub depth: u32,
+    /// The number of LUTs with each number of inputs, indexed by the number of
+    /// inputs, up to the largest number of inputs of any LUT.
+    pub inputs_histogram: Vec<usize>,
+}
+
+/// Returns statistics describing the LUTs which implement the labelled
+/// network.
+pub fn stats<Ni: 'static + NodeIndex>(network: &LabeledNetwork<Ni>, luts: &[LUT<Ni>]) -> MapStats {
+    let depth = label::max_depth(network);
+
+    let mut inputs_histogram = vec![];
+    for lut in luts {
+        let num_inputs = lut.inputs.len();
+        if inputs_histogram.len() <= num_inputs {
+            inputs_histogram.resize(num_inputs + 1, 0);
+        }
+        inputs_histogram[num_inputs] += 1;
+    }
+
+    MapStats {
+        lut_count: luts.len(),
+        depth,
+        inputs_histogram,
+    }
+}
+
+/// Asserts that every PO of the network is generated by one of the LUTs, or by
+/// a latch.
+fn assert_all_pos_covered<Ni: 'static + NodeIndex + std::fmt::Debug>(
+    network: &FlowMapBooleanNetwork<Ni>,
+    luts: &[LUT<Ni>],
+) {
+    let uncovered = network
+        .nodes()
+        .filter(|ni| network.node_value(*ni).is_po)
+        .filter(|ni| !network.node_value(*ni).is_pi)
+        .filter(|ni| !luts.iter().any(|lut| lut.output == *ni))
+        .collect::<Vec<_>>();
+
+    assert!(
+        uncovered.is_empty(),
+        "POs {:?} were not generated by any LUT",
+        uncovered
+    );
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::assert_equiv;
+    use crate::flowmap::builder::NetworkBuilder;
+    use crate::flowmap::label;
+
+    /// Evaluates every LUT as a constant false.
+    struct FalseEvaluator;
+
+    impl EvaluateLUT<usize> for FalseEvaluator {
+        fn evaluate_lut(&self, _lut: &LUT<usize>, _inputs: &[bool]) -> bool {
+            false
+        }
+    }
 
     #[test]
     fn input() {
         // Fig. 5(a) from FlowMap paper, numbered top-to-bottom, left-to-right.
-        let mut network = FlowMapBooleanNetwork::new(12);
+        let mut network = FlowMapBooleanNetwork::<usize>::new(12);
 
         network.add_edge(From(0), To(5));
         network.add_edge(From(1), To(5));
@@ -205,29 +615,381 @@ mod tests {
         network.node_value_mut(7).x_bar = vec![7];
         network.node_value_mut(12).x_bar = vec![8, 9, 10, 11, 12];
 
-        let luts = map(&network, 3);
+        let luts = map(&LabeledNetwork(network), 3, &FalseEvaluator).unwrap();
 
         // As per Fig. 4 (c), we should end up with 4 LUTs
         assert_eq!(luts.len(), 4);
         assert!(luts.contains(&LUT {
             output: 5,
             inputs: vec![0, 1],
-            contains: vec![5],
+            contains: [5].iter().copied().collect(),
+            truth_table: TruthTable::from_entries(&[false; 4]),
+            depth: 1,
+            k_used: 2,
         }));
         assert!(luts.contains(&LUT {
             output: 6,
             inputs: vec![1, 2],
-            contains: vec![6],
+            contains: [6].iter().copied().collect(),
+            truth_table: TruthTable::from_entries(&[false; 4]),
+            depth: 1,
+            k_used: 2,
         }));
         assert!(luts.contains(&LUT {
             output: 7,
             inputs: vec![3, 4],
-            contains: vec![7],
+            contains: [7].iter().copied().collect(),
+            truth_table: TruthTable::from_entries(&[false; 4]),
+            depth: 1,
+            k_used: 2,
+        }));
+        assert!(luts.contains(&LUT {
+            output: 12,
+            inputs: vec![5, 6, 7],
+            contains: [8, 9, 10, 11, 12].iter().copied().collect(),
+            truth_table: TruthTable::from_entries(&[false; 8]),
+            depth: 2,
+            k_used: 3,
         }));
+
+        // The LUTs are always in order of their output
+        assert_eq!(
+            luts.iter().map(|lut| lut.output).collect::<Vec<_>>(),
+            vec![5, 6, 7, 12]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "POs [2] were not generated by any LUT")]
+    fn assert_all_pos_covered_uncovered() {
+        let mut network = FlowMapBooleanNetwork::<usize>::new(2);
+        network.add_edge(From(0), To(1));
+        network.add_edge(From(0), To(2));
+        network.node_value_mut(0).is_pi = true;
+        network.node_value_mut(1).is_po = true;
+        network.node_value_mut(2).is_po = true;
+
+        let luts = [LUT {
+            output: 1,
+            inputs: vec![0],
+            contains: [1].iter().copied().collect(),
+            truth_table: TruthTable::from_entries(&[false, true]),
+            depth: 1,
+            k_used: 1,
+        }];
+
+        assert_all_pos_covered(&network, &luts);
+    }
+
+    /// Returns the network from Fig. 5(a) of the FlowMap paper, numbered
+    /// top-to-bottom, left-to-right, with the \bar{X} sets for the LUTs in
+    /// Fig. 4(c).
+    fn get_labeled_network() -> FlowMapBooleanNetwork<usize> {
+        let mut network = FlowMapBooleanNetwork::<usize>::new(12);
+
+        network.add_edge(From(0), To(5));
+        network.add_edge(From(1), To(5));
+        network.add_edge(From(1), To(6));
+        network.add_edge(From(2), To(6));
+        network.add_edge(From(3), To(7));
+        network.add_edge(From(4), To(7));
+        network.add_edge(From(5), To(8));
+        network.add_edge(From(5), To(12));
+        network.add_edge(From(6), To(8));
+        network.add_edge(From(6), To(10));
+        network.add_edge(From(7), To(9));
+        network.add_edge(From(7), To(11));
+        network.add_edge(From(8), To(9));
+        network.add_edge(From(9), To(10));
+        network.add_edge(From(10), To(11));
+        network.add_edge(From(11), To(12));
+
+        for pi in 0..=4 {
+            network.node_value_mut(pi).is_pi = true;
+        }
+        network.node_value_mut(12).is_po = true;
+
+        network.node_value_mut(5).x_bar = vec![5];
+        network.node_value_mut(6).x_bar = vec![6];
+        network.node_value_mut(7).x_bar = vec![7];
+        network.node_value_mut(10).x_bar = vec![8, 9, 10];
+        network.node_value_mut(12).x_bar = vec![8, 9, 10, 11, 12];
+
+        network
+    }
+
+    #[test]
+    fn map_with_overrides_test() {
+        let network = LabeledNetwork(get_labeled_network());
+
+        let mut overrides = HashMap::new();
+        overrides.insert(12, vec![11, 12]);
+        let luts = map_with_overrides(&network, 3, &FalseEvaluator, &overrides).unwrap();
+
+        assert_eq!(luts.len(), 5);
         assert!(luts.contains(&LUT {
             output: 12,
+            inputs: vec![7, 10, 5],
+            contains: [11, 12].iter().copied().collect(),
+            truth_table: TruthTable::from_entries(&[false; 8]),
+            depth: 3,
+            k_used: 3,
+        }));
+        assert!(luts.contains(&LUT {
+            output: 10,
             inputs: vec![5, 6, 7],
-            contains: vec![8, 9, 10, 11, 12],
+            contains: [8, 9, 10].iter().copied().collect(),
+            truth_table: TruthTable::from_entries(&[false; 8]),
+            depth: 2,
+            k_used: 3,
         }));
     }
+
+    #[test]
+    fn map_heterogeneous_k() {
+        let network = LabeledNetwork(get_labeled_network());
+
+        let luts = map(&network, KSpec::new(&[3, 5]), &FalseEvaluator).unwrap();
+
+        assert_eq!(luts.len(), 4);
+        for lut in &luts {
+            assert_eq!(lut.k_used, 3);
+            assert_eq!(lut.truth_table.num_inputs(), lut.inputs.len());
+        }
+    }
+
+    #[test]
+    fn map_with_overrides_too_many_inputs() {
+        let mut network = get_labeled_network();
+        network.node_value_mut(10).label = Some(2);
+        network.node_value_mut(11).label = Some(2);
+        network.node_value_mut(12).label = Some(2);
+        let network = LabeledNetwork(network);
+
+        let mut overrides = HashMap::new();
+        overrides.insert(12, vec![10, 11, 12]);
+        assert_eq!(
+            map_with_overrides(&network, 3, &FalseEvaluator, &overrides),
+            Err(MappingError::XbarTooLarge {
+                node: 12,
+                x_bar: vec![10, 11, 12],
+                x_bar_labels: vec![Some(2), Some(2), Some(2)],
+                inputs: vec![6, 9, 7, 5],
+                computed_input_count: 4,
+                k: 3,
+            })
+        );
+    }
+
+    #[test]
+    fn map_feedthrough() {
+        let mut network = get_labeled_network();
+        network.node_value_mut(0).is_po = true;
+
+        // The feedthrough needs no LUT of its own, and doesn't change the LUTs
+        // for the rest of the network
+        let luts = map(&LabeledNetwork(network), 3, &FalseEvaluator).unwrap();
+        assert_eq!(
+            luts.iter().map(|lut| lut.output).collect::<Vec<_>>(),
+            vec![5, 6, 7, 12]
+        );
+    }
+
+    #[test]
+    fn map_feedthrough_only() {
+        let mut network = FlowMapBooleanNetwork::<usize>::new(0);
+        network.node_value_mut(0).is_pi = true;
+        network.node_value_mut(0).is_po = true;
+
+        let network = label::label_network(network, 3);
+        assert_eq!(map(&network, 3, &FalseEvaluator), Ok(vec![]));
+    }
+
+    #[test]
+    fn map_with_overrides_no_inputs() {
+        let network = get_labeled_network();
+        let overrides = [(5, vec![0, 1, 5])].iter().cloned().collect();
+
+        assert_eq!(
+            map_with_overrides(&LabeledNetwork(network), 3, &FalseEvaluator, &overrides),
+            Err(MappingError::NoInputs {
+                node: 5,
+                x_bar: vec![0, 1, 5]
+            })
+        );
+    }
+
+    #[test]
+    fn lut_is_consistent_test() {
+        let network = get_labeled_network();
+        let lut = |inputs: Vec<usize>, contains: &[usize]| LUT {
+            output: 12,
+            k_used: inputs.len() as u32,
+            inputs,
+            contains: contains.iter().copied().collect(),
+            truth_table: TruthTable::default(),
+            depth: 0,
+        };
+
+        assert_eq!(
+            lut_is_consistent(&lut(vec![5, 6, 7], &[8, 9, 10, 11, 12]), &network),
+            true
+        );
+        // Inputs may be in any order
+        assert_eq!(
+            lut_is_consistent(&lut(vec![7, 6, 5], &[8, 9, 10, 11, 12]), &network),
+            true
+        );
+        // The output must be contained in the LUT
+        assert_eq!(
+            lut_is_consistent(&lut(vec![5, 6, 7], &[8, 9, 10, 11]), &network),
+            false
+        );
+        // Every input must be present exactly once
+        assert_eq!(
+            lut_is_consistent(&lut(vec![5, 6], &[8, 9, 10, 11, 12]), &network),
+            false
+        );
+        assert_eq!(
+            lut_is_consistent(&lut(vec![5, 6, 7, 7], &[8, 9, 10, 11, 12]), &network),
+            false
+        );
+        // A node inside the LUT isn't an input
+        assert_eq!(
+            lut_is_consistent(&lut(vec![5, 6, 7, 8], &[8, 9, 10, 11, 12]), &network),
+            false
+        );
+    }
+
+    /// Returns a network where 3 is driven by PIs 0 and 1, 4 is driven by 3
+    /// and PI 2, and 5 is driven by 3 and 4, with each node labelled and given
+    /// an \bar{X} containing only itself.
+    fn get_pack_area_network() -> FlowMapBooleanNetwork<usize> {
+        let mut network = NetworkBuilder::<usize>::new()
+            .input(0)
+            .input(1)
+            .input(2)
+            .and(3, 0, 1)
+            .and(4, 3, 2)
+            .and(5, 3, 4)
+            .build();
+
+        for (ni, label) in &[(3, 1), (4, 2), (5, 3)] {
+            network.node_value_mut(*ni).label = Some(*label);
+            network.node_value_mut(*ni).x_bar = vec![*ni];
+        }
+
+        network
+    }
+
+    #[test]
+    fn stats_pack_area_network() {
+        let mut network = get_pack_area_network();
+        network.node_value_mut(5).is_po = true;
+        let network = LabeledNetwork(network);
+
+        let luts = map(&network, 3, &FalseEvaluator).unwrap();
+        assert_eq!(
+            stats(&network, &luts),
+            MapStats {
+                lut_count: 3,
+                depth: 3,
+                inputs_histogram: vec![0, 0, 3],
+            }
+        );
+
+        let luts = pack_area(&network, luts, 3, &FalseEvaluator);
+        assert_eq!(stats(&network, &luts).inputs_histogram, vec![0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn stats_no_luts() {
+        let network = LabeledNetwork(FlowMapBooleanNetwork::<usize>::new(0));
+
+        assert_eq!(
+            stats(&network, &[]),
+            MapStats {
+                lut_count: 0,
+                depth: 0,
+                inputs_histogram: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn pack_area_merges_single_fanout() {
+        let mut network = get_pack_area_network();
+        network.node_value_mut(4).is_po = true;
+        let network = LabeledNetwork(network);
+
+        // 3 has two fanouts when 5 is mapped, so only map up to 4
+        let luts = map(&network, 3, &FalseEvaluator).unwrap();
+        assert_eq!(luts.len(), 2);
+
+        let luts = pack_area(&network, luts, 3, &FalseEvaluator);
+        assert_eq!(luts.len(), 1);
+        assert_eq!(luts[0].output, 4);
+        assert_equiv!(&luts[0].inputs, &vec![0, 1, 2]);
+        assert_eq!(luts[0].contains, [3, 4].iter().copied().collect());
+        assert_eq!(luts[0].truth_table, TruthTable::from_entries(&[false; 8]));
+        assert_eq!(luts[0].depth, 1);
+    }
+
+    #[test]
+    fn pack_area_respects_k() {
+        let mut network = get_pack_area_network();
+        network.node_value_mut(4).is_po = true;
+        let network = LabeledNetwork(network);
+
+        let luts = map(&network, 2, &FalseEvaluator).unwrap();
+        let packed = pack_area(&network, luts.clone(), 2, &FalseEvaluator);
+
+        assert_eq!(packed, luts);
+    }
+
+    #[test]
+    fn pack_area_keeps_multiple_fanouts_and_pos() {
+        let mut network = get_pack_area_network();
+        network.node_value_mut(4).is_po = true;
+        network.node_value_mut(5).is_po = true;
+        let network = LabeledNetwork(network);
+
+        // 3 is used by both 4 and 5, and 4 is a PO, so nothing can be merged
+        let luts = map(&network, 3, &FalseEvaluator).unwrap();
+        let packed = pack_area(&network, luts.clone(), 3, &FalseEvaluator);
+
+        assert_eq!(packed, luts);
+    }
+
+    #[test]
+    fn map_all_pi_ancestors_exceed_k() {
+        // 3 is driven by three PIs, which can't fit in a 2-input LUT
+        let mut network = FlowMapBooleanNetwork::<usize>::new(3);
+        for pi in 0..=2 {
+            network.add_edge(From(pi), To(3));
+            network.node_value_mut(pi).is_pi = true;
+            network.node_value_mut(pi).label = Some(0);
+        }
+        network.node_value_mut(3).is_po = true;
+
+        let network = label::label_network(network, 2);
+        assert_eq!(network.node_value(3).label, Some(1));
+        assert_eq!(network.node_value(3).x_bar, vec![3]);
+        assert_eq!(
+            inputs(&network, &network.node_value(3).x_bar),
+            vec![0, 1, 2]
+        );
+
+        match map(&network, 2, &FalseEvaluator) {
+            Err(MappingError::XbarTooLarge {
+                node,
+                computed_input_count,
+                ..
+            }) => {
+                assert_eq!(node, 3);
+                assert_eq!(computed_input_count, 3);
+            }
+            result => panic!("expected XbarTooLarge, got {:?}", result),
+        }
+    }
 }
@@ -1,19 +1,227 @@
-mod flow;
+pub mod builder;
+pub mod constant;
+pub mod flow;
 pub mod label;
 pub mod map;
 
 use crate::boolean_network::*;
 
-pub type FlowMapBooleanNetwork<Ni> = BooleanNetwork<NodeValue<Ni>, (u32, u32), Ni>;
+/// A boolean network whose edge values are the current flow and current
+/// capacity of each edge, for use while labelling.
+pub type FlowMapBooleanNetwork<Ni> = BooleanNetwork<NodeValue<Ni>, (u32, Capacity), Ni>;
+
+/// The sizes of LUT available to the mapped network, i.e. the numbers of
+/// inputs which a LUT may have.
+///
+/// Labelling finds cuts which fit in the largest size, and mapping records the
+/// smallest size which fits each LUT. A single K converts to every size from 1
+/// to K, so each LUT uses exactly as many inputs as it needs.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct KSpec {
+    /// The available sizes, in ascending order without duplicates.
+    sizes: Vec<u32>,
+}
+
+impl KSpec {
+    /// Creates a specification allowing LUTs of each of the provided sizes.
+    ///
+    /// Panics if no sizes are provided, or if any size is 0.
+    pub fn new(sizes: &[u32]) -> KSpec {
+        assert!(!sizes.is_empty(), "at least one LUT size must be provided");
+        assert!(
+            !sizes.contains(&0),
+            "LUT sizes must be at least 1, but 0 was provided"
+        );
+
+        let mut sizes = sizes.to_vec();
+        sizes.sort_unstable();
+        sizes.dedup();
+
+        KSpec { sizes }
+    }
+
+    /// Creates a specification allowing LUTs of every size up to and
+    /// including `k`.
+    pub fn up_to(k: u32) -> KSpec {
+        KSpec::new(&(1..=k).collect::<Vec<_>>())
+    }
+
+    /// Returns the largest available size.
+    pub fn max(&self) -> u32 {
+        *self.sizes.last().unwrap()
+    }
+
+    /// Returns the smallest available size which can implement a LUT with
+    /// `inputs` inputs, or `None` if every size is too small.
+    pub fn fit(&self, inputs: usize) -> Option<u32> {
+        self.sizes
+            .iter()
+            .copied()
+            .find(|size| *size as usize >= inputs)
+    }
+}
+
+impl std::convert::From<u32> for KSpec {
+    fn from(k: u32) -> Self {
+        KSpec::up_to(k)
+    }
+}
+
+/// The current capacity of an edge in a flow network, i.e. the capacity of the
+/// edge minus its current flow.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Capacity {
+    /// The edge can carry the given amount of additional flow.
+    Finite(u32),
+    /// The edge can carry any amount of additional flow, so it never limits an
+    /// augmenting path or appears in a minimum cut.
+    Infinite,
+}
+
+impl Default for Capacity {
+    fn default() -> Self {
+        Capacity::Finite(0)
+    }
+}
+
+impl Capacity {
+    /// Returns `true` if the edge can't carry any additional flow.
+    pub fn is_zero(self) -> bool {
+        self == Capacity::Finite(0)
+    }
+
+    /// Returns the capacity after `f` more flow is sent along the edge.
+    ///
+    /// Panics if a finite capacity is exceeded.
+    pub fn decrease(self, f: u32) -> Capacity {
+        match self {
+            Capacity::Finite(cap) => Capacity::Finite(
+                cap.checked_sub(f)
+                    .expect("flow exceeded the capacity of an edge"),
+            ),
+            Capacity::Infinite => Capacity::Infinite,
+        }
+    }
+
+    /// Returns the capacity after `f` flow is removed from the edge.
+    pub fn increase(self, f: u32) -> Capacity {
+        match self {
+            Capacity::Finite(cap) => Capacity::Finite(cap + f),
+            Capacity::Infinite => Capacity::Infinite,
+        }
+    }
+}
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NodeValue<Ni> {
     pub symbol: Option<String>,
     pub label: Option<u32>,
     pub x_bar: Vec<Ni>,
     pub is_pi: bool,
     pub is_po: bool,
+    /// Whether the node has a constant value, rather than being driven by the
+    /// outside world or by logic.
+    pub is_constant: bool,
+    /// For the output of a latch, the node which drives the latch's next
+    /// state. Latch outputs are PIs, so labelling cuts the network at latch
+    /// boundaries and each combinational cone is labelled separately.
+    pub latch_next: Option<Ni>,
+    /// For the output of a latch, the value of the latch on reset.
+    pub latch_reset: LatchReset,
+    /// The function of the node over the values of its ancestors, which is
+    /// set by the frontend for every node driven by logic.
+    pub logic: Option<NodeLogic>,
     pub flow: u32,
+    /// The capacity of the node in the flow network used while labelling.
+    /// FlowMap needs every node to have a capacity of 1, the default, so that
+    /// the maximum flow is the size of the smallest cut. Other capacities
+    /// weight the nodes, for experiments with cuts minimising other costs.
+    pub capacity: u32,
+}
+
+/// The function of a node over the values of its ancestors, which lets the
+/// logic of a LUT be evaluated regardless of the frontend the network came
+/// from.
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NodeLogic {
+    /// The node is true if every ancestor is true, as for an AIGER AND gate.
+    And,
+    /// The node is the inverse of its only ancestor, as for an AIGER inverter.
+    Not,
+    /// The node is given by a cover over its ancestors, in order, as for a
+    /// BLIF `.names` block.
+    Cover(Cover),
+}
+
+impl NodeLogic {
+    /// Returns the value of the node for the provided values of its ancestors,
+    /// in order.
+    pub fn evaluate(&self, inputs: &[bool]) -> bool {
+        match self {
+            NodeLogic::And => inputs.iter().all(|input| *input),
+            NodeLogic::Not => {
+                assert_eq!(
+                    inputs.len(),
+                    1,
+                    "inverter should only be driven by one node"
+                );
+
+                !inputs[0]
+            }
+            NodeLogic::Cover(cover) => cover.evaluate(inputs),
+        }
+    }
+}
+
+/// A sum-of-products cover of a node's function, as in a BLIF `.names` block.
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Cover {
+    /// The cubes of the cover, each with one entry for each ancestor of the
+    /// node, in order. An entry is the value the ancestor must have for the
+    /// cube to match, or `None` if the cube matches either value.
+    pub cubes: Vec<Vec<Option<bool>>>,
+    /// The value of the node when any cube matches. The node has the opposite
+    /// value when no cube matches.
+    pub value: bool,
+}
+
+impl Cover {
+    /// Returns the value of the node for the provided values of its ancestors,
+    /// in order.
+    pub fn evaluate(&self, inputs: &[bool]) -> bool {
+        let matches = self.cubes.iter().any(|cube| {
+            assert_eq!(
+                cube.len(),
+                inputs.len(),
+                "cube has {} entries but the cover was given {} inputs",
+                cube.len(),
+                inputs.len()
+            );
+
+            cube.iter().zip(inputs).all(|(entry, input)| match entry {
+                Some(entry) => entry == input,
+                None => true,
+            })
+        });
+
+        matches == self.value
+    }
+}
+
+/// The value of a latch on reset.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LatchReset {
+    /// The latch is reset to false.
+    Zero,
+    /// The latch is reset to true.
+    One,
+    /// The latch is uninitialized, and may take either value on reset.
+    NonDeterministic,
 }
 
 impl<Ni: 'static + NodeIndex> Default for NodeValue<Ni> {
@@ -24,7 +232,174 @@ impl<Ni: 'static + NodeIndex> Default for NodeValue<Ni> {
             x_bar: vec![],
             is_pi: false,
             is_po: false,
+            is_constant: false,
+            latch_next: None,
+            latch_reset: LatchReset::Zero,
+            logic: None,
             flow: 0,
+            capacity: 1,
         }
     }
 }
+
+impl<Ni> NodeValue<Ni> {
+    /// Returns `true` if the node is neither a PI nor a PO.
+    pub fn is_internal(&self) -> bool {
+        !self.is_pi && !self.is_po
+    }
+
+    /// Returns `true` if the node is a PI or a PO.
+    pub fn is_boundary(&self) -> bool {
+        self.is_pi || self.is_po
+    }
+}
+
+impl<Ni: 'static + NodeIndex> FlowMapBooleanNetwork<Ni> {
+    /// Returns the nodes which the mapped network must generate, i.e. every PO
+    /// and the next state of every latch, in node index order.
+    pub fn mapping_roots(&self) -> Vec<Ni> {
+        let pos = self.nodes().filter(|ni| self.node_value(*ni).is_po);
+        let latch_nexts = self.nodes().filter_map(|ni| self.node_value(ni).latch_next);
+
+        let mut roots = pos.chain(latch_nexts).collect::<Vec<_>>();
+        roots.sort_by_key(|ni| ni.node_index());
+        roots.dedup();
+
+        roots
+    }
+
+    /// Returns the number of PIs, POs and internal nodes in the network, in
+    /// that order.
+    ///
+    /// A node which is both a PI and a PO is counted as both.
+    pub fn node_count_by_category(&self) -> (usize, usize, usize) {
+        self.nodes().map(|ni| self.node_value(ni)).fold(
+            (0, 0, 0),
+            |(pis, pos, internal), node_value| {
+                (
+                    pis + node_value.is_pi as usize,
+                    pos + node_value.is_po as usize,
+                    internal + node_value.is_internal() as usize,
+                )
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_value_internal_boundary() {
+        let mut node_value = NodeValue::<usize>::default();
+        assert_eq!(node_value.is_internal(), true);
+        assert_eq!(node_value.is_boundary(), false);
+
+        node_value.is_pi = true;
+        assert_eq!(node_value.is_internal(), false);
+        assert_eq!(node_value.is_boundary(), true);
+
+        node_value.is_pi = false;
+        node_value.is_po = true;
+        assert_eq!(node_value.is_internal(), false);
+        assert_eq!(node_value.is_boundary(), true);
+    }
+
+    #[test]
+    fn node_count_by_category() {
+        let mut network = FlowMapBooleanNetwork::<usize>::new(5);
+        network.node_value_mut(0).is_pi = true;
+        network.node_value_mut(1).is_pi = true;
+        network.node_value_mut(4).is_po = true;
+        network.node_value_mut(5).is_pi = true;
+        network.node_value_mut(5).is_po = true;
+
+        assert_eq!(network.node_count_by_category(), (3, 2, 2));
+    }
+
+    #[test]
+    fn capacity() {
+        assert_eq!(Capacity::default(), Capacity::Finite(0));
+        assert_eq!(Capacity::Finite(0).is_zero(), true);
+        assert_eq!(Capacity::Finite(1).is_zero(), false);
+        assert_eq!(Capacity::Infinite.is_zero(), false);
+
+        assert_eq!(Capacity::Finite(1).decrease(1), Capacity::Finite(0));
+        assert_eq!(Capacity::Finite(0).increase(1), Capacity::Finite(1));
+        assert_eq!(Capacity::Infinite.decrease(u32::MAX), Capacity::Infinite);
+        assert_eq!(Capacity::Infinite.increase(1), Capacity::Infinite);
+    }
+
+    #[test]
+    #[should_panic(expected = "flow exceeded the capacity of an edge")]
+    fn capacity_exceeded() {
+        Capacity::Finite(1).decrease(2);
+    }
+
+    #[test]
+    fn k_spec() {
+        let k = KSpec::new(&[6, 5, 6]);
+        assert_eq!(k.max(), 6);
+        assert_eq!(k.fit(2), Some(5));
+        assert_eq!(k.fit(5), Some(5));
+        assert_eq!(k.fit(6), Some(6));
+        assert_eq!(k.fit(7), None);
+
+        let k = KSpec::from(4);
+        assert_eq!(k, KSpec::up_to(4));
+        assert_eq!(k.max(), 4);
+        assert_eq!(k.fit(2), Some(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one LUT size must be provided")]
+    fn k_spec_empty() {
+        KSpec::new(&[]);
+    }
+
+    #[test]
+    fn cover_evaluate() {
+        // a XOR b, with a don't care for c
+        let xor = Cover {
+            cubes: vec![
+                vec![Some(true), Some(false), None],
+                vec![Some(false), Some(true), None],
+            ],
+            value: true,
+        };
+        assert_eq!(xor.evaluate(&[false, false, true]), false);
+        assert_eq!(xor.evaluate(&[true, false, true]), true);
+        assert_eq!(xor.evaluate(&[false, true, false]), true);
+        assert_eq!(xor.evaluate(&[true, true, false]), false);
+
+        // An off-set cover, true unless both inputs are true
+        let nand = Cover {
+            cubes: vec![vec![Some(true), Some(true)]],
+            value: false,
+        };
+        assert_eq!(nand.evaluate(&[true, true]), false);
+        assert_eq!(nand.evaluate(&[false, true]), true);
+
+        let constant_false = Cover {
+            cubes: vec![],
+            value: true,
+        };
+        assert_eq!(constant_false.evaluate(&[]), false);
+    }
+
+    #[test]
+    fn node_logic_evaluate() {
+        assert_eq!(NodeLogic::And.evaluate(&[true, true]), true);
+        assert_eq!(NodeLogic::And.evaluate(&[true, false]), false);
+        assert_eq!(NodeLogic::Not.evaluate(&[true]), false);
+        assert_eq!(NodeLogic::Not.evaluate(&[false]), true);
+
+        let or = NodeLogic::Cover(Cover {
+            cubes: vec![vec![Some(false), Some(false)]],
+            value: false,
+        });
+        assert_eq!(or.evaluate(&[false, false]), false);
+        assert_eq!(or.evaluate(&[false, true]), true);
+    }
+}
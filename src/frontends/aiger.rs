@@ -1,10 +1,14 @@
 use crate::boolean_network::*;
-use crate::flowmap::map::LUT;
 use crate::flowmap::*;
 use aiger::*;
-use hashbrown::HashSet;
+use std::fmt;
 use std::io;
 
+/// The AIGER literal which is always false.
+pub const FALSE: Literal = Literal(0);
+/// The AIGER literal which is always true.
+pub const TRUE: Literal = Literal(1);
+
 impl NodeIndex for Literal {
     fn from_node_index(ni: usize) -> Literal {
         Literal(ni)
@@ -17,7 +21,68 @@ impl NodeIndex for Literal {
 
 pub type AIG = FlowMapBooleanNetwork<Literal>;
 
-pub fn from_reader<T: io::Read>(reader: Reader<T>) -> AIG {
+/// Displays an AIGER literal as its raw value, or with the alternate flag
+/// (`{:#}`) as its variable, prefixed with `!` if the literal is inverted.
+///
+/// `aiger::Literal` belongs to the `aiger` crate, so it can't implement
+/// `fmt::Display` itself.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct DisplayLiteral(pub Literal);
+
+impl fmt::Display for DisplayLiteral {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let DisplayLiteral(literal) = self;
+
+        if f.alternate() {
+            let not = if literal.is_inverted() { "!" } else { "" };
+            write!(f, "{}v{}", not, literal.variable())
+        } else {
+            write!(f, "{}", literal.0)
+        }
+    }
+}
+
+/// An error which occurs while building a network from an AIGER file.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum AigerNetworkError {
+    /// The AIGER file could not be parsed.
+    Parse(AigerError),
+    /// A record of the AIGER file could not be parsed, at the given 1-based
+    /// line number, where the header is line 1.
+    ParseAt { line: usize, error: AigerError },
+    /// An AND gate was not driven by two literals, or by one literal twice.
+    InvalidAndGate(Literal),
+    /// An inverter was not driven by exactly one literal.
+    InvalidInverter(Literal),
+    /// The file ended before all of the inputs, latches, outputs and AND gates
+    /// declared in the header were read.
+    RecordCountMismatch { expected: usize, found: usize },
+    /// A PI was driven by another literal.
+    DrivenInput(Literal),
+    /// A literal was driven by itself.
+    SelfLoop(Literal),
+    /// A symbol at the given 1-based line number named an input, latch or
+    /// output past the end of those declared in the header.
+    SymbolOutOfRange {
+        line: usize,
+        type_spec: Symbol,
+        position: usize,
+    },
+}
+
+impl std::convert::From<AigerError> for AigerNetworkError {
+    fn from(error: AigerError) -> Self {
+        AigerNetworkError::Parse(error)
+    }
+}
+
+/// Builds a network from an ASCII AIGER file.
+///
+/// Every literal is its own node, and each odd literal is driven by an inverter
+/// from the even literal of its variable. An output of an inverted literal is
+/// therefore a distinct PO from an output of the uninverted literal, so both
+/// polarities of a gate can be outputs at once.
+pub fn from_reader<T: io::Read>(reader: Reader<T>) -> Result<AIG, AigerNetworkError> {
     let header = reader.header();
 
     let max_variable = header.m;
@@ -31,14 +96,22 @@ pub fn from_reader<T: io::Read>(reader: Reader<T>) -> AIG {
         network.add_edge(From(from), To(to));
     }
 
-    network.node_value_mut(Literal(0)).label = Some(0);
-    network.node_value_mut(Literal(0)).is_pi = true;
+    // Literal 0 is the constant false, so treat it as a PI which is never
+    // exposed as an input
+    network.node_value_mut(FALSE).label = Some(0);
+    network.node_value_mut(FALSE).is_pi = true;
+    network.node_value_mut(FALSE).is_constant = true;
 
     let mut inputs = vec![];
+    let mut latches = vec![];
     let mut outputs = vec![];
+    let mut and_gate_count = 0;
+
+    // Every record is on its own line, following the header on line 1
+    for (line, record) in (2..).zip(reader.records()) {
+        let record = record.map_err(|error| AigerNetworkError::ParseAt { line, error })?;
 
-    for record in reader.records() {
-        match record.unwrap() {
+        match record {
             Aiger::Input(l) => {
                 network.node_value_mut(l).label = Some(0);
                 network.node_value_mut(l).is_pi = true;
@@ -46,10 +119,13 @@ pub fn from_reader<T: io::Read>(reader: Reader<T>) -> AIG {
                 inputs.push(l);
             }
             Aiger::Latch { output, input } => {
-                network.node_value_mut(output).is_pi = true;
-                network.node_value_mut(output).is_po = true;
+                latches.push(output);
 
-                network.add_edge(From(input), To(output));
+                // The output of a latch is a pseudo-PI, so the combinational
+                // logic on either side of the latch is mapped separately
+                network.node_value_mut(output).label = Some(0);
+                network.node_value_mut(output).is_pi = true;
+                network.node_value_mut(output).latch_next = Some(input);
             }
             Aiger::Output(l) => {
                 network.node_value_mut(l).is_po = true;
@@ -60,8 +136,28 @@ pub fn from_reader<T: io::Read>(reader: Reader<T>) -> AIG {
                 output,
                 inputs: [input0, input1],
             } => {
-                network.add_edge(From(input0), To(output));
-                network.add_edge(From(input1), To(output));
+                // A gate with a constant input is simplified, so that it's
+                // either a buffer or an AND of two distinct literals: false
+                // AND y is a buffer of false, and true AND y is a buffer of y
+                let inputs = if input0 == FALSE || input1 == FALSE {
+                    vec![FALSE]
+                } else if input0 == TRUE {
+                    vec![input1]
+                } else if input1 == TRUE {
+                    vec![input0]
+                } else {
+                    vec![input0, input1]
+                };
+
+                // An AND gate of a literal with itself is a buffer of the
+                // literal, which has a single edge from it. A gate defined
+                // twice gains the edges of both definitions, which validation
+                // rejects if they differ.
+                for input in inputs {
+                    network.add_edge_unique(From(input), To(output));
+                }
+
+                and_gate_count += 1;
             }
             Aiger::Symbol {
                 type_spec,
@@ -69,415 +165,725 @@ pub fn from_reader<T: io::Read>(reader: Reader<T>) -> AIG {
                 symbol,
             } => {
                 let l = match type_spec {
-                    Symbol::Input => inputs[position],
-                    Symbol::Output => outputs[position],
-                    Symbol::Latch => continue,
+                    Symbol::Input => inputs.get(position),
+                    Symbol::Output => outputs.get(position),
+                    Symbol::Latch => latches.get(position),
                 };
+                let l = *l.ok_or(AigerNetworkError::SymbolOutOfRange {
+                    line,
+                    type_spec,
+                    position,
+                })?;
 
                 network.node_value_mut(l).symbol = Some(symbol);
             }
         }
     }
 
-    network
-}
+    // The records iterator stops quietly at the end of the file, so a
+    // truncated file would otherwise be missing records without any error
+    let expected = header.i + header.l + header.o + header.a;
+    let found = inputs.len() + latches.len() + outputs.len() + and_gate_count;
+    if found != expected {
+        return Err(AigerNetworkError::RecordCountMismatch { expected, found });
+    }
+
+    validate_aiger_network(&network)?;
+    assign_logic(&mut network);
 
-/// The internal logic of the LUT, encoded as a recursive structure.
-#[derive(Debug, PartialEq, Clone)]
-enum LogicNode {
-    Literal(Literal),
-    And(Box<LogicNode>, Box<LogicNode>),
-    Inverter(Box<LogicNode>),
-    Value(bool),
+    Ok(network)
 }
 
-impl LogicNode {
-    /// Recursively replaces the literal `n` with the specified replacement.
-    fn replace(self, n: Literal, replacement: LogicNode) -> LogicNode {
-        match self {
-            LogicNode::Literal(l) if l == n => replacement,
-            LogicNode::Literal(l) => LogicNode::Literal(l),
-            LogicNode::And(input0, input1) => {
-                let input0 = Box::new(input0.replace(n, replacement.clone()));
-                let input1 = Box::new(input1.replace(n, replacement));
-
-                LogicNode::And(input0, input1)
-            }
-            LogicNode::Inverter(ln) => LogicNode::Inverter(Box::new(ln.replace(n, replacement))),
-            LogicNode::Value(v) => LogicNode::Value(v),
+/// Sets the logic of every node of an AIGER network which is driven by other
+/// nodes, from the structure of the network: odd literals are inverters of
+/// their variable, and even literals are AND gates.
+///
+/// `from_reader` does this itself, so it's only needed for networks built
+/// literal by literal.
+pub fn assign_logic(network: &mut AIG) {
+    for l in network.nodes().collect::<Vec<_>>() {
+        if network.ancestors(l).is_empty() {
+            continue;
         }
+
+        network.node_value_mut(l).logic = Some(if l.is_inverted() {
+            NodeLogic::Not
+        } else {
+            NodeLogic::And
+        });
     }
+}
 
-    /// Evaluates the logic function, panicing if any unspecified values (i.e.
-    /// LogicNode::Literal instances) remain.
-    fn evaluate(&self) -> bool {
-        match self {
-            LogicNode::Literal(_) => panic!("can't evaluate logic node with literal"),
-            LogicNode::And(input0, input1) => input0.evaluate() && input1.evaluate(),
-            LogicNode::Inverter(ln) => !ln.evaluate(),
-            LogicNode::Value(v) => *v,
-        }
+/// Builds a network from either an ASCII (`aag`) or binary (`aig`) AIGER file,
+/// including the reset values of its latches.
+pub fn from_any<T: io::Read>(reader: T) -> Result<AIG, AigerNetworkError> {
+    let (ascii, latch_resets) = ascii_from_any(reader)?;
+    let mut network = from_reader(Reader::from_reader(io::Cursor::new(ascii))?)?;
+
+    for (output, reset) in latch_resets {
+        network.node_value_mut(output).latch_reset = reset;
     }
+
+    Ok(network)
 }
 
-/// Returns a function which can be used to determine the output value of a LUT
-/// based on the value of its inputs.
+/// Creates an AIGER reader from either an ASCII (`aag`) or binary (`aig`)
+/// AIGER file, detected using the magic string in the header.
 ///
-/// The inputs to the LUT must be passed to the function returned in the same
-/// order as the input literals in `lut.inputs`.
-pub fn evaluate_lut<'a>(
-    network: &FlowMapBooleanNetwork<Literal>,
-    lut: &'a LUT<Literal>,
-) -> impl Fn(&[bool]) -> bool + 'a {
-    let LUT {
-        output,
-        contains,
-        inputs,
-    } = lut;
-
-    // TODO: This is just another topo search from the output, looking at
-    // ancestors. Consider extracting this into the boolean network itself
-    let mut logic = LogicNode::Literal(*output);
-
-    let mut visited = HashSet::new();
-    let mut s = vec![*output];
-    while let Some(n) = s.pop() {
-        if !visited.insert(n) {
-            continue;
+/// `aiger::Reader` only understands the ASCII format, so a binary file is first
+/// converted to the equivalent ASCII file with `binary_to_ascii`. It also only
+/// understands AIGER 1.0 latches, so any latch reset values are dropped; use
+/// `from_any` to keep them.
+pub fn reader_from_any<T: io::Read>(reader: T) -> Result<Reader<io::Cursor<Vec<u8>>>, AigerError> {
+    let (ascii, _) = ascii_from_any(reader)?;
+
+    Reader::from_reader(io::Cursor::new(ascii))
+}
+
+/// The reset value of each latch which has one, by the latch's output.
+type LatchResets = Vec<(Literal, LatchReset)>;
+
+/// Reads an ASCII or binary AIGER file as an ASCII AIGER 1.0 file, along with
+/// the reset value of every latch which has one.
+///
+/// `aiger::Reader` leaves the `\r` of CRLF line endings on each line and then
+/// fails to parse it, so CRLF line endings are converted to LF.
+fn ascii_from_any<T: io::Read>(mut reader: T) -> Result<(Vec<u8>, LatchResets), AigerError> {
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+
+    if bytes.starts_with(b"aig ") {
+        bytes = binary_to_ascii(&bytes)?;
+    }
+
+    // The binary sections of a binary AIGER file may contain CRLF pairs, so
+    // line endings are only converted once the file is ASCII
+    let mut lf = Vec::with_capacity(bytes.len());
+    for (i, byte) in bytes.iter().enumerate() {
+        if !(*byte == b'\r' && bytes.get(i + 1) == Some(&b'\n')) {
+            lf.push(*byte);
         }
+    }
 
-        if !inputs.contains(&n) {
-            let ancestors = network.ancestors(n);
-            if n.is_inverted() {
-                assert_eq!(
-                    ancestors.len(),
-                    1,
-                    "inverter should only be driven by its non-inverted variable"
-                );
-                let parent = ancestors[0];
-
-                logic = logic.replace(n, LogicNode::Inverter(Box::new(LogicNode::Literal(parent))));
-            } else {
-                // An AND gate should only be driven by two signals
-                assert_eq!(
-                    ancestors.len(),
-                    2,
-                    "and gate should only be driven by two literals"
-                );
-                let input0 = ancestors[0];
-                let input1 = ancestors[1];
-
-                logic = logic.replace(
-                    n,
-                    LogicNode::And(
-                        Box::new(LogicNode::Literal(input0)),
-                        Box::new(LogicNode::Literal(input1)),
-                    ),
-                );
-            }
+    strip_latch_resets(&lf)
+}
 
-            for ancestor in ancestors {
-                let remaining_descendents = network
-                    .descendents(*ancestor)
-                    .iter()
-                    .filter(|ni| contains.contains(ni))
-                    .filter(|ni| !visited.contains(ni));
+/// Removes the optional reset literal from the latches of an ASCII AIGER file,
+/// returning the file without them and the reset value of each latch which had
+/// one.
+///
+/// A reset literal of 0 or 1 is a constant reset value, and a reset literal
+/// equal to the latch's output means the latch is uninitialized.
+fn strip_latch_resets(ascii: &[u8]) -> Result<(Vec<u8>, LatchResets), AigerError> {
+    let mut rest = ascii;
+
+    let header_line = next_line(&mut rest).ok_or(AigerError::InvalidHeader)?;
+    let header_fields = header_line
+        .strip_prefix("aag ")
+        .ok_or(AigerError::InvalidHeader)?
+        .split(' ')
+        .map(|field| field.parse::<usize>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| AigerError::InvalidHeader)?;
+    let (i, l) = match header_fields[..] {
+        [_, i, l, _, _] => (i, l),
+        _ => return Err(AigerError::InvalidHeader),
+    };
+
+    let mut stripped = format!("{}\n", header_line);
+    let mut latch_resets = vec![];
+
+    for _ in 0..i {
+        let line = next_line(&mut rest).ok_or(AigerError::InvalidLiteralCount)?;
+        stripped += &format!("{}\n", line);
+    }
 
-                if remaining_descendents.count() == 0 {
-                    s.push(*ancestor);
-                }
+    for _ in 0..l {
+        let line = next_line(&mut rest).ok_or(AigerError::InvalidLiteralCount)?;
+        let literals = line
+            .split(' ')
+            .map(|literal| literal.parse::<usize>().map(Literal))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| AigerError::InvalidLiteral)?;
+
+        match literals[..] {
+            [output, input] => stripped += &format!("{} {}\n", output.0, input.0),
+            [output, input, reset] => {
+                let reset = match reset {
+                    FALSE => LatchReset::Zero,
+                    TRUE => LatchReset::One,
+                    reset if reset == output => LatchReset::NonDeterministic,
+                    _ => return Err(AigerError::InvalidLiteral),
+                };
+
+                stripped += &format!("{} {}\n", output.0, input.0);
+                latch_resets.push((output, reset));
             }
+            _ => return Err(AigerError::InvalidLiteralCount),
         }
     }
 
-    move |literal_values| {
-        let mut logic = logic.clone();
+    let mut stripped = stripped.into_bytes();
+    stripped.extend_from_slice(rest);
+
+    Ok((stripped, latch_resets))
+}
+
+/// Returns the comment section of an ASCII or binary AIGER file, or `None` if
+/// the file has no comment section.
+///
+/// `aiger::Reader` stops reading at the comment section, so the comments are
+/// found separately here. As with `aiger::Reader`, the comment section begins
+/// at the first line after the header which starts with `c`, and the text
+/// returned is everything after that line.
+pub fn comments<T: io::Read>(mut reader: T) -> Result<Option<String>, AigerError> {
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+
+    if bytes.starts_with(b"aig ") {
+        bytes = binary_to_ascii(&bytes)?;
+    }
+
+    let text = String::from_utf8_lossy(&bytes);
+    let mut lines = text.split_inclusive('\n').skip(1);
+    if lines.any(|line| line.starts_with('c')) {
+        Ok(Some(lines.collect()))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Converts a binary AIGER file to the equivalent ASCII AIGER file.
+///
+/// In the binary format, inputs and AND gate outputs are implicit, and the
+/// inputs of each AND gate are delta-encoded as variable-length integers. The
+/// symbol table and comments are the same in both formats, so they're copied
+/// as-is.
+pub fn binary_to_ascii(bytes: &[u8]) -> Result<Vec<u8>, AigerError> {
+    let mut rest = bytes;
+
+    let header_line = next_line(&mut rest).ok_or(AigerError::InvalidHeader)?;
+    let header_fields = header_line
+        .strip_prefix("aig ")
+        .ok_or(AigerError::InvalidHeader)?
+        .split(' ')
+        .map(|field| field.parse::<usize>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| AigerError::InvalidHeader)?;
+    let (m, i, l, o, a) = match header_fields[..] {
+        [m, i, l, o, a] => (m, i, l, o, a),
+        _ => return Err(AigerError::InvalidHeader),
+    };
+
+    // Every variable in a binary AIGER file is an input, latch or AND gate,
+    // since their literals are implicit
+    if m != i + l + a {
+        return Err(AigerError::InvalidHeader);
+    }
+
+    let mut ascii = format!("aag {} {} {} {} {}\n", m, i, l, o, a);
+
+    for input in 0..i {
+        ascii += &format!("{}\n", Literal::from_variable(input + 1, false).0);
+    }
+
+    for latch in 0..l {
+        let line = next_line(&mut rest).ok_or(AigerError::InvalidLiteralCount)?;
+        let output = Literal::from_variable(i + latch + 1, false).0;
+        ascii += &format!("{} {}\n", output, line);
+    }
+
+    for _ in 0..o {
+        let line = next_line(&mut rest).ok_or(AigerError::InvalidLiteralCount)?;
+        ascii += &format!("{}\n", line);
+    }
+
+    for and_gate in 0..a {
+        let output = Literal::from_variable(i + l + and_gate + 1, false).0;
+
+        // The first input is encoded relative to the output, and the second
+        // relative to the first, and both deltas must keep the literals
+        // positive
+        let delta0 = next_delta(&mut rest)?;
+        let input0 = output
+            .checked_sub(delta0)
+            .ok_or(AigerError::InvalidLiteral)?;
+        let delta1 = next_delta(&mut rest)?;
+        let input1 = input0
+            .checked_sub(delta1)
+            .ok_or(AigerError::InvalidLiteral)?;
+
+        ascii += &format!("{} {} {}\n", output, input0, input1);
+    }
 
-        for (literal, value) in inputs.iter().zip(literal_values.iter()) {
-            logic = logic.replace(*literal, LogicNode::Value(*value));
+    let mut ascii = ascii.into_bytes();
+    ascii.extend_from_slice(rest);
+
+    Ok(ascii)
+}
+
+/// Returns the next line of a binary AIGER file without its newline or any
+/// carriage return before it, advancing past it, or `None` if no complete ASCII
+/// line remains.
+fn next_line<'a>(rest: &mut &'a [u8]) -> Option<&'a str> {
+    let newline = rest.iter().position(|b| *b == b'\n')?;
+    let line = std::str::from_utf8(&rest[..newline]).ok()?;
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    *rest = &rest[newline + 1..];
+
+    Some(line)
+}
+
+/// Returns the next variable-length integer of a binary AIGER file, advancing
+/// past it.
+///
+/// Each byte holds 7 bits of the integer, least significant first, with the
+/// most significant bit of the byte set if more bytes follow.
+fn next_delta(rest: &mut &[u8]) -> Result<usize, AigerError> {
+    let mut delta = 0usize;
+    let mut shift = 0;
+
+    loop {
+        let (byte, remaining) = rest.split_first().ok_or(AigerError::InvalidLiteral)?;
+        *rest = remaining;
+
+        let bits = ((byte & 0x7f) as usize)
+            .checked_shl(shift)
+            .filter(|bits| bits >> shift == (byte & 0x7f) as usize)
+            .ok_or(AigerError::InvalidLiteral)?;
+        delta |= bits;
+
+        if byte & 0x80 == 0 {
+            return Ok(delta);
         }
 
-        logic.evaluate()
+        shift += 7;
     }
 }
 
+/// Checks that a network built from an AIGER file has the structure expected
+/// by the rest of the flow, to avoid opaque panics when labelling and mapping.
+fn validate_aiger_network(network: &AIG) -> Result<(), AigerNetworkError> {
+    for ni in network.nodes() {
+        let ancestors = network.ancestors(ni);
+        let node_value = network.node_value(ni);
+
+        if ancestors.contains(&ni) {
+            return Err(AigerNetworkError::SelfLoop(ni));
+        }
+
+        if node_value.is_pi {
+            // Latch outputs are PIs too, since the latch's next state isn't
+            // connected to its output in the network
+            if !ancestors.is_empty() {
+                return Err(AigerNetworkError::DrivenInput(ni));
+            }
+        } else if ni.is_inverted() {
+            if ancestors.len() != 1 {
+                return Err(AigerNetworkError::InvalidInverter(ni));
+            }
+        } else if ancestors.len() > 2 {
+            // Variables which are never defined have no ancestors, and are
+            // never used by well-formed files, and an AND gate of a literal
+            // with itself has only one
+            return Err(AigerNetworkError::InvalidAndGate(ni));
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::evaluate::EvaluateLUT;
+
+    fn network_from_str(s: &str) -> Result<AIG, AigerNetworkError> {
+        from_reader(Reader::from_reader(s.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn from_reader_and_gate() {
+        let network = network_from_str("aag 3 2 0 1 1\n2\n4\n6\n6 2 4\n").unwrap();
+
+        assert_eq!(network.node_value(Literal(2)).is_pi, true);
+        assert_eq!(network.node_value(Literal(4)).is_pi, true);
+        assert_eq!(network.node_value(Literal(6)).is_po, true);
+        assert_eq!(network.ancestors(Literal(6)), [Literal(2), Literal(4)]);
+        assert_eq!(network.ancestors(Literal(7)), [Literal(6)]);
+
+        assert_eq!(network.node_value(Literal(2)).logic, None);
+        assert_eq!(network.node_value(Literal(3)).logic, Some(NodeLogic::Not));
+        assert_eq!(network.node_value(Literal(6)).logic, Some(NodeLogic::And));
+        assert_eq!(network.node_value(Literal(7)).logic, Some(NodeLogic::Not));
+    }
 
     #[test]
-    fn logic_node_replace() {
-        let logic = LogicNode::Literal(Literal(2));
+    fn from_reader_both_polarities() {
+        let network = network_from_str("aag 3 2 0 2 1\n2\n4\n6\n7\n6 2 4\no0 y\no1 ny\n").unwrap();
 
-        let logic = logic.replace(Literal(2), LogicNode::Literal(Literal(4)));
+        assert_eq!(network.node_value(Literal(6)).is_po, true);
+        assert_eq!(network.node_value(Literal(7)).is_po, true);
+        assert_eq!(network.ancestors(Literal(7)), [Literal(6)]);
+        assert_eq!(network.node_value(Literal(6)).symbol, Some("y".to_string()));
+        assert_eq!(
+            network.node_value(Literal(7)).symbol,
+            Some("ny".to_string())
+        );
+    }
 
-        assert_eq!(logic, LogicNode::Literal(Literal(4)));
+    #[test]
+    fn from_reader_parse_error() {
+        assert_eq!(
+            network_from_str("aag 3 2 0 1 1\n2\n4\n6\n6 2 x\n").err(),
+            Some(AigerNetworkError::ParseAt {
+                line: 5,
+                error: AigerError::InvalidLiteral
+            })
+        );
     }
 
     #[test]
-    fn logic_node_replace_missing() {
-        let logic = LogicNode::Literal(Literal(2));
+    fn from_reader_truncated() {
+        assert_eq!(
+            network_from_str("aag 3 2 0 1 1\n2\n4\n6\n").err(),
+            Some(AigerNetworkError::RecordCountMismatch {
+                expected: 4,
+                found: 3
+            })
+        );
+    }
 
-        let logic = logic.replace(Literal(4), LogicNode::Literal(Literal(6)));
+    #[test]
+    fn from_reader_parse_error_symbol_line() {
+        assert_eq!(
+            network_from_str("aag 1 1 0 1 0\n2\n2\ni0 a\nx0 b\n").err(),
+            Some(AigerNetworkError::ParseAt {
+                line: 5,
+                error: AigerError::InvalidSymbol
+            })
+        );
+    }
 
-        assert_eq!(logic, LogicNode::Literal(Literal(2)));
+    #[test]
+    fn from_reader_symbol_out_of_range() {
+        assert_eq!(
+            network_from_str("aag 1 1 0 1 0\n2\n2\ni3 x\n").err(),
+            Some(AigerNetworkError::SymbolOutOfRange {
+                line: 4,
+                type_spec: Symbol::Input,
+                position: 3
+            })
+        );
+        assert_eq!(
+            network_from_str("aag 1 1 0 1 0\n2\n2\no1 y\n").err(),
+            Some(AigerNetworkError::SymbolOutOfRange {
+                line: 4,
+                type_spec: Symbol::Output,
+                position: 1
+            })
+        );
+        assert_eq!(
+            network_from_str("aag 1 1 0 1 0\n2\n2\nl0 q\n").err(),
+            Some(AigerNetworkError::SymbolOutOfRange {
+                line: 4,
+                type_spec: Symbol::Latch,
+                position: 0
+            })
+        );
     }
 
     #[test]
-    fn logic_node_replace_value_unaffected() {
-        let logic = LogicNode::Value(false);
+    fn from_reader_literal_out_of_range() {
+        // The aiger crate checks every literal against the maximum variable in
+        // the header before the network ever sees it
+        assert_eq!(
+            network_from_str("aag 3 2 0 1 1\n2\n4\n6\n6 100 4\n").err(),
+            Some(AigerNetworkError::ParseAt {
+                line: 5,
+                error: AigerError::LiteralOutOfRange
+            })
+        );
+        assert_eq!(
+            network_from_str("aag 3 2 0 1 1\n2\n4\n8\n6 2 4\n").err(),
+            Some(AigerNetworkError::ParseAt {
+                line: 4,
+                error: AigerError::LiteralOutOfRange
+            })
+        );
+    }
 
-        let logic = logic.replace(Literal(2), LogicNode::Literal(Literal(4)));
+    #[test]
+    fn from_reader_and_gate_constant_inputs() {
+        let network =
+            network_from_str("aag 6 1 0 4 4\n2\n4\n6\n8\n10\n4 0 2\n6 2 1\n8 1 3\n10 1 1\n")
+                .unwrap();
+
+        assert_eq!(network.ancestors(Literal(4)), [FALSE]);
+        assert_eq!(network.ancestors(Literal(6)), [Literal(2)]);
+        assert_eq!(network.ancestors(Literal(8)), [Literal(3)]);
+        assert_eq!(network.ancestors(Literal(10)), [TRUE]);
+
+        let network = crate::flowmap::label::label_network(network, 2);
+        let luts = crate::flowmap::map::map(&network, 2, &*network).unwrap();
+        // Each output as a function of the input, with literal 0 tied to false
+        let outputs = |output: usize| {
+            let lut = luts.iter().find(|lut| lut.output == Literal(output));
+            let lut = lut.unwrap();
+            [false, true]
+                .iter()
+                .map(|x| {
+                    let inputs = lut
+                        .inputs
+                        .iter()
+                        .map(|ni| *ni == Literal(2) && *x)
+                        .collect::<Vec<_>>();
+                    network.evaluate_lut(lut, &inputs)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(outputs(4), vec![false, false]);
+        assert_eq!(outputs(6), vec![false, true]);
+        assert_eq!(outputs(8), vec![true, false]);
+        assert_eq!(outputs(10), vec![true, true]);
+    }
+
+    #[test]
+    fn validate_and_gate_defined_twice() {
+        assert_eq!(
+            network_from_str("aag 3 2 0 1 2\n2\n4\n6\n6 2 4\n6 3 5\n").err(),
+            Some(AigerNetworkError::InvalidAndGate(Literal(6)))
+        );
+        assert_eq!(
+            network_from_str("aag 3 2 0 1 2\n2\n4\n6\n6 2 4\n6 2 5\n").err(),
+            Some(AigerNetworkError::InvalidAndGate(Literal(6)))
+        );
+    }
+
+    #[test]
+    fn from_reader_and_gate_same_input_twice() {
+        let network = network_from_str("aag 2 1 0 1 1\n2\n4\n4 2 2\n").unwrap();
+
+        assert_eq!(network.ancestors(Literal(4)), [Literal(2)]);
+        assert_eq!(network.node_value(Literal(4)).logic, Some(NodeLogic::And));
+
+        let network = crate::flowmap::label::label_network(network, 2);
+        let luts = crate::flowmap::map::map(&network, 2, &*network).unwrap();
+        assert_eq!(luts.len(), 1);
+        assert_eq!(luts[0].inputs, vec![Literal(2)]);
+        assert_eq!(
+            luts[0].truth_table,
+            crate::truth_table::TruthTable::from_entries(&[false, true])
+        );
+    }
 
-        assert_eq!(logic, LogicNode::Value(false));
+    #[test]
+    fn validate_driven_input() {
+        assert_eq!(
+            network_from_str("aag 3 2 0 1 1\n2\n4\n6\n2 4 6\n").err(),
+            Some(AigerNetworkError::DrivenInput(Literal(2)))
+        );
     }
 
     #[test]
-    fn logic_node_replace_and() {
-        let logic = LogicNode::And(
-            Box::new(LogicNode::Literal(Literal(2))),
-            Box::new(LogicNode::Literal(Literal(4))),
+    fn validate_self_loop() {
+        assert_eq!(
+            network_from_str("aag 3 2 0 1 1\n2\n4\n6\n6 6 4\n").err(),
+            Some(AigerNetworkError::SelfLoop(Literal(6)))
         );
+    }
 
-        let logic = logic.replace(Literal(2), LogicNode::Literal(Literal(6)));
+    #[test]
+    fn binary_to_ascii_half_adder() {
+        let binary = b"aig 5 2 0 2 3\n10\n6\n\x02\x02\x03\x02\x01\x02i0 x\ni1 y\no0 s\no1 c\n";
 
         assert_eq!(
-            logic,
-            LogicNode::And(
-                Box::new(LogicNode::Literal(Literal(6))),
-                Box::new(LogicNode::Literal(Literal(4))),
-            )
+            String::from_utf8(binary_to_ascii(binary).unwrap()).unwrap(),
+            "aag 5 2 0 2 3\n2\n4\n10\n6\n6 4 2\n8 5 3\n10 9 7\ni0 x\ni1 y\no0 s\no1 c\n"
         );
     }
 
     #[test]
-    fn logic_node_replace_or() {
-        let logic = LogicNode::Inverter(Box::new(LogicNode::And(
-            Box::new(LogicNode::Inverter(Box::new(LogicNode::Literal(Literal(
-                2,
-            ))))),
-            Box::new(LogicNode::Inverter(Box::new(LogicNode::Literal(Literal(
-                4,
-            ))))),
-        )));
-
-        let logic = logic.replace(Literal(2), LogicNode::Literal(Literal(6)));
+    fn reader_from_any_binary_matches_ascii() {
+        let binary = b"aig 5 2 0 2 3\n10\n6\n\x02\x02\x03\x02\x01\x02i0 x\ni1 y\no0 s\no1 c\n";
+        let ascii = "aag 5 2 0 2 3\n2\n4\n10\n6\n6 4 2\n8 5 3\n10 9 7\ni0 x\ni1 y\no0 s\no1 c\n";
 
+        let from_binary = from_reader(reader_from_any(&binary[..]).unwrap()).unwrap();
+        let from_ascii = from_reader(reader_from_any(ascii.as_bytes()).unwrap()).unwrap();
+
+        assert_eq!(
+            from_binary.adjacency_matrix(),
+            from_ascii.adjacency_matrix()
+        );
         assert_eq!(
-            logic,
-            LogicNode::Inverter(Box::new(LogicNode::And(
-                Box::new(LogicNode::Inverter(Box::new(LogicNode::Literal(Literal(
-                    6
-                ))))),
-                Box::new(LogicNode::Inverter(Box::new(LogicNode::Literal(Literal(
-                    4
-                ))))),
-            )))
+            from_binary.node_value(Literal(10)).symbol,
+            Some("s".to_string())
         );
     }
 
     #[test]
-    fn logic_node_evaluate_value() {
-        assert_eq!(LogicNode::Value(false).evaluate(), false);
-        assert_eq!(LogicNode::Value(true).evaluate(), true);
+    fn binary_to_ascii_multi_byte_delta() {
+        let mut rest = &[0x83, 0x80, 0x01, 0x7f][..];
+
+        assert_eq!(next_delta(&mut rest), Ok(16387));
+        assert_eq!(next_delta(&mut rest), Ok(127));
+        assert_eq!(next_delta(&mut rest), Err(AigerError::InvalidLiteral));
     }
 
     #[test]
-    fn logic_node_evaluate_inverter() {
+    fn binary_to_ascii_invalid() {
+        // The number of variables doesn't match the inputs and AND gates
+        assert_eq!(
+            binary_to_ascii(b"aig 4 2 0 1 1\n6\n\x02\x02"),
+            Err(AigerError::InvalidHeader)
+        );
+        // The first input of the AND gate would be negative
         assert_eq!(
-            LogicNode::Inverter(Box::new(LogicNode::Value(false))).evaluate(),
-            true
+            binary_to_ascii(b"aig 3 2 0 1 1\n6\n\x07\x02"),
+            Err(AigerError::InvalidLiteral)
         );
+        // The AND gate is truncated
         assert_eq!(
-            LogicNode::Inverter(Box::new(LogicNode::Value(true))).evaluate(),
-            false
+            binary_to_ascii(b"aig 3 2 0 1 1\n6\n\x02"),
+            Err(AigerError::InvalidLiteral)
         );
     }
 
     #[test]
-    fn logic_node_evaluate_and() {
+    fn from_reader_symbols() {
+        let network = network_from_str(
+            "aag 4 2 1 1 1\n2\n4\n6 8\n8\n8 2 4\ni0 clk\ni1 data[0]\nl0 state\no0 result[2]\n",
+        )
+        .unwrap();
+
         assert_eq!(
-            LogicNode::And(
-                Box::new(LogicNode::Value(false)),
-                Box::new(LogicNode::Value(false))
-            )
-            .evaluate(),
-            false
+            network.node_value(Literal(2)).symbol,
+            Some("clk".to_string())
         );
         assert_eq!(
-            LogicNode::And(
-                Box::new(LogicNode::Value(false)),
-                Box::new(LogicNode::Value(true))
-            )
-            .evaluate(),
-            false
+            network.node_value(Literal(4)).symbol,
+            Some("data[0]".to_string())
         );
         assert_eq!(
-            LogicNode::And(
-                Box::new(LogicNode::Value(true)),
-                Box::new(LogicNode::Value(false))
-            )
-            .evaluate(),
-            false
+            network.node_value(Literal(6)).symbol,
+            Some("state".to_string())
         );
         assert_eq!(
-            LogicNode::And(
-                Box::new(LogicNode::Value(true)),
-                Box::new(LogicNode::Value(true))
-            )
-            .evaluate(),
-            true
+            network.node_value(Literal(8)).symbol,
+            Some("result[2]".to_string())
         );
     }
 
     #[test]
-    fn evaluate_lut_single_inverter() {
-        // --2-->|~|>--3--
-        let mut network = FlowMapBooleanNetwork::new(Literal(3));
-        network.add_edge(From(Literal(2)), To(Literal(3)));
-
-        let lut = LUT {
-            output: Literal(3),
-            contains: vec![Literal(3)],
-            inputs: vec![Literal(2)],
-        };
-        let f = evaluate_lut(&network, &lut);
+    fn comments_ascii() {
+        let aiger = "aag 3 2 0 1 1\n2\n4\n6\n6 2 4\ni0 x\nc\nmade by hand\nfrom and.v\n";
 
-        assert_eq!(f(&[false]), true);
-        assert_eq!(f(&[true]), false);
+        assert_eq!(
+            comments(aiger.as_bytes()),
+            Ok(Some("made by hand\nfrom and.v\n".to_string()))
+        );
+        assert_eq!(comments("aag 0 0 0 0 0\n".as_bytes()), Ok(None));
     }
 
     #[test]
-    fn evaluate_lut_single_and_gate() {
-        // --2-->|&|>--6--
-        // --4-->| |
-        let mut network = FlowMapBooleanNetwork::new(Literal(6));
-        network.add_edge(From(Literal(2)), To(Literal(6)));
-        network.add_edge(From(Literal(4)), To(Literal(6)));
-
-        let lut = LUT {
-            output: Literal(6),
-            contains: vec![Literal(6)],
-            inputs: vec![Literal(2), Literal(4)],
-        };
-        let f = evaluate_lut(&network, &lut);
+    fn comments_binary() {
+        let binary = b"aig 3 2 0 1 1\n6\n\x02\x02c\nmade by hand\n";
 
-        assert_eq!(f(&[false, false]), false);
-        assert_eq!(f(&[false, true]), false);
-        assert_eq!(f(&[true, false]), false);
-        assert_eq!(f(&[true, true]), true);
+        assert_eq!(
+            comments(&binary[..]),
+            Ok(Some("made by hand\n".to_string()))
+        );
     }
 
     #[test]
-    fn evaluate_lut_single_and_gate_single_inverted_input() {
-        // --2-->|~|>--3-->|&|>--6--
-        // --4------------>| |
-        let mut network = FlowMapBooleanNetwork::new(Literal(6));
-        network.add_edge(From(Literal(2)), To(Literal(3)));
-        network.add_edge(From(Literal(3)), To(Literal(6)));
-        network.add_edge(From(Literal(4)), To(Literal(6)));
-
-        let lut = LUT {
-            output: Literal(6),
-            contains: vec![Literal(3), Literal(6)],
-            inputs: vec![Literal(2), Literal(4)],
-        };
-        let f = evaluate_lut(&network, &lut);
+    fn from_reader_toggle_ff_en_rst() {
+        let network = network_from_str(
+            "aag 7 2 1 2 4\n2\n4\n6 8\n6\n7\n8 4 10\n10 13 15\n12 2 6\n14 3 7\n\
+             i0 enable\ni1 reset\no0 Q\no1 !Q\n",
+        )
+        .unwrap();
+
+        // The latch output is a pseudo-PI which isn't driven by its next state
+        let latch = network.node_value(Literal(6));
+        assert_eq!(latch.is_pi, true);
+        assert_eq!(latch.latch_next, Some(Literal(8)));
+        assert!(network.ancestors(Literal(6)).is_empty());
+        assert_eq!(
+            network.mapping_roots(),
+            vec![Literal(6), Literal(7), Literal(8)]
+        );
 
-        assert_eq!(f(&[false, false]), false);
-        assert_eq!(f(&[false, true]), true);
-        assert_eq!(f(&[true, false]), false);
-        assert_eq!(f(&[true, true]), false);
+        let network = crate::flowmap::label::label_network(network, 6);
+        let luts = crate::flowmap::map::map(&network, 6, &*network).unwrap();
+
+        // The latch output is generated by the latch itself, so only its
+        // inverse and the latch's next state need LUTs
+        assert_eq!(luts.len(), 2);
+
+        let next = luts.iter().find(|lut| lut.output == Literal(8)).unwrap();
+        let mut next_inputs = next.inputs.clone();
+        next_inputs.sort_by_key(|l| l.0);
+        assert_eq!(next_inputs, vec![Literal(2), Literal(4), Literal(6)]);
+        assert_eq!(next.depth, 1);
+
+        let inverse = luts.iter().find(|lut| lut.output == Literal(7)).unwrap();
+        assert_eq!(inverse.inputs, vec![Literal(6)]);
     }
 
     #[test]
-    fn evaluate_lut_single_and_gate_single_inverted_input_unused_output() {
-        //        8
-        //        ^
-        // --2-->|~|>--3-->|&|>--6--
-        // --4------------>| |
-        let mut network = FlowMapBooleanNetwork::new(Literal(8));
-        network.add_edge(From(Literal(2)), To(Literal(3)));
-        network.add_edge(From(Literal(3)), To(Literal(6)));
-        network.add_edge(From(Literal(3)), To(Literal(8)));
-        network.add_edge(From(Literal(4)), To(Literal(6)));
-
-        let lut = LUT {
-            output: Literal(6),
-            contains: vec![Literal(3), Literal(6)],
-            inputs: vec![Literal(2), Literal(4)],
-        };
-        let f = evaluate_lut(&network, &lut);
+    fn from_any_latch_resets() {
+        let network =
+            from_any("aag 4 1 3 3 0\n2\n4 2\n6 2 1\n8 2 8\n4\n6\n8\n".as_bytes()).unwrap();
 
-        assert_eq!(f(&[false, false]), false);
-        assert_eq!(f(&[false, true]), true);
-        assert_eq!(f(&[true, false]), false);
-        assert_eq!(f(&[true, true]), false);
+        assert_eq!(network.node_value(Literal(4)).latch_reset, LatchReset::Zero);
+        assert_eq!(network.node_value(Literal(6)).latch_reset, LatchReset::One);
+        assert_eq!(
+            network.node_value(Literal(8)).latch_reset,
+            LatchReset::NonDeterministic
+        );
+        assert_eq!(network.node_value(Literal(8)).latch_next, Some(Literal(2)));
     }
 
     #[test]
-    fn evaluate_lut_and_chain_single_inverted_input() {
-        // --2-->|~|--3-->|&|>--10-->| |
-        // --4----------->| |        | |
-        //                           |&|>--14--
-        // --6----------->|&|>--12-->| |
-        // --8----------->| |        | |
-        let mut network = FlowMapBooleanNetwork::new(Literal(14));
-        network.add_edge(From(Literal(2)), To(Literal(3)));
-        network.add_edge(From(Literal(3)), To(Literal(10)));
-        network.add_edge(From(Literal(4)), To(Literal(10)));
-        network.add_edge(From(Literal(6)), To(Literal(12)));
-        network.add_edge(From(Literal(8)), To(Literal(12)));
-        network.add_edge(From(Literal(10)), To(Literal(14)));
-        network.add_edge(From(Literal(12)), To(Literal(14)));
-
-        let lut = LUT {
-            output: Literal(14),
-            contains: vec![Literal(3), Literal(10), Literal(12)],
-            inputs: vec![Literal(2), Literal(4), Literal(6), Literal(8)],
-        };
+    fn from_any_binary_latch_resets() {
+        let network = from_any("aig 2 1 1 1 0\n2 1\n4\n".as_bytes()).unwrap();
 
-        let f = evaluate_lut(&network, &lut);
-
-        assert_eq!(f(&[false, false, false, false]), false);
-        assert_eq!(f(&[false, false, false, true]), false);
-        assert_eq!(f(&[false, false, true, false]), false);
-        assert_eq!(f(&[false, false, true, true]), false);
-        assert_eq!(f(&[false, true, false, false]), false);
-        assert_eq!(f(&[false, true, false, true]), false);
-        assert_eq!(f(&[false, true, true, false]), false);
-        assert_eq!(f(&[false, true, true, true]), true);
-        assert_eq!(f(&[true, false, false, false]), false);
-        assert_eq!(f(&[true, false, false, true]), false);
-        assert_eq!(f(&[true, false, true, false]), false);
-        assert_eq!(f(&[true, false, true, true]), false);
-        assert_eq!(f(&[true, true, false, false]), false);
-        assert_eq!(f(&[true, true, false, true]), false);
-        assert_eq!(f(&[true, true, true, false]), false);
-        assert_eq!(f(&[true, true, true, true]), false);
+        assert_eq!(network.node_value(Literal(4)).latch_reset, LatchReset::One);
+        assert_eq!(network.node_value(Literal(4)).latch_next, Some(Literal(2)));
     }
 
     #[test]
-    fn evaluate_lut_single_or_gate() {
-        // --2-->|~|>--3-->|&|>--6-->|~|>--7--
-        // --4-->|~|>--5-->| |
-        let mut network = FlowMapBooleanNetwork::new(Literal(7));
-        network.add_edge(From(Literal(2)), To(Literal(3)));
-        network.add_edge(From(Literal(3)), To(Literal(6)));
-        network.add_edge(From(Literal(4)), To(Literal(5)));
-        network.add_edge(From(Literal(5)), To(Literal(6)));
-        network.add_edge(From(Literal(6)), To(Literal(7)));
-
-        let lut = LUT {
-            output: Literal(7),
-            contains: vec![Literal(3), Literal(5), Literal(6)],
-            inputs: vec![Literal(2), Literal(4)],
-        };
-        let f = evaluate_lut(&network, &lut);
+    fn from_any_invalid_latch_reset() {
+        assert_eq!(
+            from_any("aag 2 1 1 1 0\n2\n4 2 2\n4\n".as_bytes()).err(),
+            Some(AigerNetworkError::Parse(AigerError::InvalidLiteral))
+        );
+    }
+
+    #[test]
+    fn reader_from_any_crlf() {
+        let reader = reader_from_any("aag 1 0 0 1 0\r\n2\r\n".as_bytes()).unwrap();
+        let records = reader.records().collect::<Result<Vec<_>, _>>().unwrap();
+
+        assert_eq!(records, vec![Aiger::Output(Literal(2))]);
+    }
+
+    #[test]
+    fn reader_from_any_binary_crlf() {
+        let crlf = from_any("aig 3 2 0 1 1\r\n6\r\n\x02\x02i0 x\r\n".as_bytes()).unwrap();
+        let lf = from_any("aig 3 2 0 1 1\n6\n\x02\x02i0 x\n".as_bytes()).unwrap();
+
+        assert_eq!(crlf.ancestors(Literal(6)), lf.ancestors(Literal(6)));
+        assert_eq!(crlf.node_value(Literal(2)).symbol, Some("x".to_string()));
+    }
+
+    #[test]
+    fn display_literal() {
+        assert_eq!(format!("{}", DisplayLiteral(FALSE)), "0");
+        assert_eq!(format!("{}", DisplayLiteral(TRUE)), "1");
+        assert_eq!(format!("{}", DisplayLiteral(Literal(7))), "7");
 
-        assert_eq!(f(&[false, false]), false);
-        assert_eq!(f(&[false, true]), true);
-        assert_eq!(f(&[true, false]), true);
-        assert_eq!(f(&[true, true]), true);
+        assert_eq!(format!("{:#}", DisplayLiteral(Literal(6))), "v3");
+        assert_eq!(format!("{:#}", DisplayLiteral(Literal(7))), "!v3");
     }
 }
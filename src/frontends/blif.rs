@@ -0,0 +1,376 @@
+use crate::boolean_network::*;
+use crate::flowmap::*;
+use std::collections::HashMap;
+use std::io;
+
+/// A network built from a BLIF file.
+///
+/// Node 0 is the constant false. Every other node is a signal of the BLIF
+/// file, numbered in the order the signals first appear.
+pub type BlifNetwork = FlowMapBooleanNetwork<u32>;
+
+/// An error which occurs while building a network from a BLIF file.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum BlifError {
+    /// The BLIF file could not be read.
+    Io(io::ErrorKind),
+    /// A construct which isn't supported was found, at the given 1-based line
+    /// number. Only a single `.model` of combinational `.names` blocks is
+    /// supported.
+    Unsupported { line: usize, directive: String },
+    /// A line which isn't a directive was found outside of a `.names` block.
+    UnexpectedLine { line: usize },
+    /// A line of a `.names` block was not a valid cube, or had a different
+    /// output value to the other cubes of the block.
+    InvalidCube { line: usize },
+    /// A `.names` block listed the same input more than once.
+    DuplicateInput { line: usize, signal: String },
+    /// A signal was used, but is neither an input nor the output of a
+    /// `.names` block.
+    UndefinedSignal(String),
+    /// A signal was driven by more than one input or `.names` block.
+    MultipleDrivers(String),
+}
+
+impl std::convert::From<io::Error> for BlifError {
+    fn from(error: io::Error) -> Self {
+        BlifError::Io(error.kind())
+    }
+}
+
+/// A `.names` block of a BLIF file.
+struct Names {
+    inputs: Vec<String>,
+    output: String,
+    cubes: Vec<Vec<Option<bool>>>,
+    value: Option<bool>,
+}
+
+/// Returns the logical lines of a BLIF file, with the 1-based line number
+/// each starts on, with comments removed and continued lines joined.
+fn logical_lines(text: &str) -> Vec<(usize, String)> {
+    let mut lines = vec![];
+    let mut continued: Option<(usize, String)> = None;
+
+    for (line, text) in (1..).zip(text.lines()) {
+        let text = match text.find('#') {
+            Some(comment) => &text[..comment],
+            None => text,
+        };
+
+        let (start, mut joined) = continued.take().unwrap_or((line, String::new()));
+        match text.trim_end().strip_suffix('\\') {
+            Some(text) => {
+                joined += text;
+                joined.push(' ');
+                continued = Some((start, joined));
+            }
+            None => {
+                joined += text;
+                lines.push((start, joined));
+            }
+        }
+    }
+
+    if let Some(continued) = continued {
+        lines.push(continued);
+    }
+
+    lines
+}
+
+/// Parses a cube of a `.names` block with `num_inputs` inputs, returning the
+/// input entries of the cube and its output value.
+fn parse_cube(tokens: &[&str], num_inputs: usize) -> Option<(Vec<Option<bool>>, bool)> {
+    let (plane, output) = match tokens {
+        [output] if num_inputs == 0 => ("", *output),
+        [plane, output] if plane.len() == num_inputs => (*plane, *output),
+        _ => return None,
+    };
+
+    let cube = plane
+        .chars()
+        .map(|c| match c {
+            '0' => Some(Some(false)),
+            '1' => Some(Some(true)),
+            '-' => Some(None),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let value = match output {
+        "0" => false,
+        "1" => true,
+        _ => return None,
+    };
+
+    Some((cube, value))
+}
+
+/// Builds a network from a BLIF file containing a single combinational model.
+///
+/// Each `.names` block becomes a node whose ancestors are the inputs of the
+/// block, in order, and whose logic is the block's cover. A `.names` block
+/// without inputs is a constant, which becomes a node driven by the constant
+/// false node 0 so that it's generated by a LUT. Inputs and outputs are named
+/// by their signals.
+pub fn from_reader<T: io::Read>(mut reader: T) -> Result<BlifNetwork, BlifError> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+
+    let mut seen_model = false;
+    let mut inputs = vec![];
+    let mut outputs = vec![];
+    let mut names: Vec<Names> = vec![];
+    let mut in_names = false;
+
+    for (line, text) in logical_lines(&text) {
+        let tokens = text.split_whitespace().collect::<Vec<_>>();
+        let (directive, arguments) = match tokens.split_first() {
+            Some((directive, arguments)) => (*directive, arguments),
+            None => continue,
+        };
+
+        if !directive.starts_with('.') {
+            let current = match names.last_mut() {
+                Some(current) if in_names => current,
+                _ => return Err(BlifError::UnexpectedLine { line }),
+            };
+
+            let (cube, value) =
+                parse_cube(&tokens, current.inputs.len()).ok_or(BlifError::InvalidCube { line })?;
+            if *current.value.get_or_insert(value) != value {
+                return Err(BlifError::InvalidCube { line });
+            }
+            current.cubes.push(cube);
+
+            continue;
+        }
+
+        in_names = false;
+        match directive {
+            ".model" if !seen_model => seen_model = true,
+            ".inputs" => inputs.extend(arguments.iter().map(|s| s.to_string())),
+            ".outputs" => outputs.extend(arguments.iter().map(|s| s.to_string())),
+            ".names" if !arguments.is_empty() => {
+                let (output, block_inputs) = arguments.split_last().unwrap();
+
+                for (i, input) in block_inputs.iter().enumerate() {
+                    if block_inputs[..i].contains(input) {
+                        return Err(BlifError::DuplicateInput {
+                            line,
+                            signal: input.to_string(),
+                        });
+                    }
+                }
+
+                names.push(Names {
+                    inputs: block_inputs.iter().map(|s| s.to_string()).collect(),
+                    output: output.to_string(),
+                    cubes: vec![],
+                    value: None,
+                });
+                in_names = true;
+            }
+            ".end" => {}
+            _ => {
+                return Err(BlifError::Unsupported {
+                    line,
+                    directive: directive.to_string(),
+                })
+            }
+        }
+    }
+
+    // Node 0 is the constant false, so signals are numbered from 1
+    let mut signals = HashMap::new();
+    for signal in inputs.iter().chain(names.iter().map(|names| &names.output)) {
+        let next = signals.len() as u32 + 1;
+        if signals.insert(signal.clone(), next).is_some() {
+            return Err(BlifError::MultipleDrivers(signal.clone()));
+        }
+    }
+    let signal = |name: &String| {
+        signals
+            .get(name)
+            .copied()
+            .ok_or_else(|| BlifError::UndefinedSignal(name.clone()))
+    };
+
+    let mut network = BlifNetwork::new(signals.len() as u32);
+
+    // The constant false is a PI which is never exposed as an input, as in an
+    // AIGER network
+    network.node_value_mut(0).label = Some(0);
+    network.node_value_mut(0).is_pi = true;
+    network.node_value_mut(0).is_constant = true;
+
+    for input in &inputs {
+        let ni = signal(input)?;
+        network.node_value_mut(ni).label = Some(0);
+        network.node_value_mut(ni).is_pi = true;
+        network.node_value_mut(ni).symbol = Some(input.clone());
+    }
+
+    for block in names {
+        let output = signal(&block.output)?;
+
+        let mut cubes = block.cubes;
+        if block.inputs.is_empty() {
+            network.add_edge(From(0), To(output));
+            for cube in &mut cubes {
+                cube.push(None);
+            }
+        }
+        for input in &block.inputs {
+            network.add_edge(From(signal(input)?), To(output));
+        }
+
+        // A block without any cubes is always false, which an on-set cover
+        // without cubes represents
+        network.node_value_mut(output).logic = Some(NodeLogic::Cover(Cover {
+            cubes,
+            value: block.value.unwrap_or(true),
+        }));
+    }
+
+    for output in &outputs {
+        let ni = signal(output)?;
+        network.node_value_mut(ni).is_po = true;
+        network.node_value_mut(ni).symbol = Some(output.clone());
+    }
+
+    Ok(network)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluate::EvaluateLUT;
+    use crate::flowmap::{label, map};
+    use crate::truth_table::TruthTable;
+
+    fn blif_to_luts(blif: &str, k: u32) -> (label::LabeledNetwork<u32>, Vec<map::LUT<u32>>) {
+        let network = from_reader(blif.as_bytes()).unwrap();
+
+        let network = label::label_network(network, k);
+        let luts = map::map(&network, k, &*network).unwrap();
+
+        (network, luts)
+    }
+
+    #[test]
+    fn logical_lines_test() {
+        assert_eq!(
+            logical_lines(".inputs a \\\n  b # comment\n\n.end\n"),
+            vec![
+                (1, ".inputs a    b ".to_string()),
+                (3, "".to_string()),
+                (4, ".end".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn from_reader_xor() {
+        let (network, luts) = blif_to_luts(
+            ".model xor\n.inputs a b\n.outputs y\n.names a b y\n10 1\n01 1\n.end\n",
+            6,
+        );
+
+        assert_eq!(network.node_value(1).symbol, Some("a".to_string()));
+        assert_eq!(network.node_value(1).is_pi, true);
+        assert_eq!(network.node_value(3).symbol, Some("y".to_string()));
+        assert_eq!(network.node_value(3).is_po, true);
+        assert_eq!(network.ancestors(3), &[1, 2]);
+
+        assert_eq!(luts.len(), 1);
+        assert_eq!(luts[0].inputs.len(), 2);
+        assert_eq!(
+            luts[0].truth_table,
+            TruthTable::from_entries(&[false, true, true, false])
+        );
+    }
+
+    #[test]
+    fn from_reader_multi_level() {
+        // y = (a & b) | !c, with the OR as an off-set cover
+        let (network, luts) = blif_to_luts(
+            ".model m\n.inputs a b c\n.outputs y\n\
+             .names a b t\n11 1\n\
+             .names t c y\n01 0\n.end\n",
+            6,
+        );
+
+        assert_eq!(luts.len(), 1);
+        let lut = &luts[0];
+        for i in 0..8 {
+            let values = [i & 4 != 0, i & 2 != 0, i & 1 != 0];
+            let inputs = lut
+                .inputs
+                .iter()
+                .map(|ni| values[*ni as usize - 1])
+                .collect::<Vec<_>>();
+
+            assert_eq!(
+                network.evaluate_lut(lut, &inputs),
+                (values[0] && values[1]) || !values[2]
+            );
+        }
+    }
+
+    #[test]
+    fn from_reader_constants() {
+        let (network, luts) = blif_to_luts(
+            ".model c\n.inputs a\n.outputs one zero\n.names one\n1\n.names zero\n.end\n",
+            6,
+        );
+
+        assert_eq!(luts.len(), 2);
+        for lut in &luts {
+            assert_eq!(lut.inputs, vec![0]);
+            let expected = network.node_value(lut.output).symbol == Some("one".to_string());
+            assert_eq!(
+                lut.truth_table,
+                TruthTable::from_entries(&[expected, expected])
+            );
+        }
+    }
+
+    #[test]
+    fn from_reader_errors() {
+        assert_eq!(
+            from_reader(".model m\n.inputs a\n.outputs q\n.latch a q 0\n.end\n".as_bytes()).err(),
+            Some(BlifError::Unsupported {
+                line: 4,
+                directive: ".latch".to_string()
+            })
+        );
+        assert_eq!(
+            from_reader(".inputs a\n1 1\n".as_bytes()).err(),
+            Some(BlifError::UnexpectedLine { line: 2 })
+        );
+        assert_eq!(
+            from_reader(".inputs a\n.names a y\n1 1\n0 0\n".as_bytes()).err(),
+            Some(BlifError::InvalidCube { line: 4 })
+        );
+        assert_eq!(
+            from_reader(".inputs a\n.names a y\n11 1\n".as_bytes()).err(),
+            Some(BlifError::InvalidCube { line: 3 })
+        );
+        assert_eq!(
+            from_reader(".inputs a\n.names a a y\n11 1\n".as_bytes()).err(),
+            Some(BlifError::DuplicateInput {
+                line: 2,
+                signal: "a".to_string()
+            })
+        );
+        assert_eq!(
+            from_reader(".inputs a\n.outputs y\n.names a b y\n11 1\n".as_bytes()).err(),
+            Some(BlifError::UndefinedSignal("b".to_string()))
+        );
+        assert_eq!(
+            from_reader(".inputs a\n.names a\n1\n".as_bytes()).err(),
+            Some(BlifError::MultipleDrivers("a".to_string()))
+        );
+    }
+}
@@ -1 +1,2 @@
 pub mod aiger;
+pub mod blif;
@@ -0,0 +1,10 @@
+#![allow(clippy::bool_assert_comparison, clippy::upper_case_acronyms)]
+
+pub mod backends;
+pub mod boolean_network;
+pub mod evaluate;
+pub mod flowmap;
+pub mod frontends;
+mod test_utils;
+pub mod truth_table;
+pub mod verify;
@@ -1,44 +1,262 @@
+use flowmap::flowmap::{label, map, FlowMapBooleanNetwork};
+use flowmap::{backends, frontends, verify};
 use std::env;
+use std::fs::File;
+use std::io;
+use std::process;
 
-mod backends;
-mod boolean_network;
-mod flowmap;
-mod frontends;
-mod test_utils;
+const USAGE: &str = "\
+usage: flowmap [options] <input> [<output>]
+
+Maps an AIGER (.aag or .aig) or BLIF (.blif) file to K-input LUTs.
+
+options:
+  -k <N>             the number of inputs of each LUT (default 6)
+  -o <file>          the file to write to, which may also be given as the
+                     second positional argument (default standard output)
+  --format <format>  the format to write: rtlil, blif, json or verilog
+                     (default rtlil)
+  --stats            print the number of LUTs, the depth and the number of
+                     LUTs with each number of inputs to standard error
+  --verify           check that the mapped network is equivalent to the
+                     input, for every assignment of up to 16 inputs or a
+                     sample of assignments of more
+  -h, --help         print this message";
+
+/// The format to write the mapped network in.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+enum Format {
+    Rtlil,
+    Blif,
+    Json,
+    Verilog,
+}
+
+/// The options given on the command line.
+#[derive(Debug, Eq, PartialEq, Clone)]
+struct Options {
+    input: String,
+    output: Option<String>,
+    k: u32,
+    format: Format,
+    stats: bool,
+    verify: bool,
+}
+
+/// Parses the command line arguments, excluding the program name. Returns
+/// `None` if help was requested.
+fn parse_args(args: &[String]) -> Result<Option<Options>, String> {
+    let mut positionals = vec![];
+    let mut output = None;
+    let mut k = 6;
+    let mut format = Format::Rtlil;
+    let mut stats = false;
+    let mut verify = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("{} requires a value", arg))
+        };
+
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-k" => {
+                let value = value()?;
+                k = match value.parse() {
+                    Ok(k) if k > 0 => k,
+                    _ => return Err(format!("invalid LUT size {:?}", value)),
+                };
+            }
+            "-o" => output = Some(value()?.clone()),
+            "--format" => {
+                let value = value()?;
+                format = match value.as_str() {
+                    "rtlil" => Format::Rtlil,
+                    "blif" => Format::Blif,
+                    "json" => Format::Json,
+                    "verilog" => Format::Verilog,
+                    _ => return Err(format!("unknown format {:?}", value)),
+                };
+            }
+            "--stats" => stats = true,
+            "--verify" => verify = true,
+            _ if arg.starts_with('-') => return Err(format!("unknown option {:?}", arg)),
+            _ => positionals.push(arg.clone()),
+        }
+    }
+
+    let mut positionals = positionals.into_iter();
+    let input = positionals
+        .next()
+        .ok_or_else(|| "no input file given".to_string())?;
+    if let Some(positional) = positionals.next() {
+        if output.is_some() {
+            return Err("the output file was given twice".to_string());
+        }
+        output = Some(positional);
+    }
+    if let Some(extra) = positionals.next() {
+        return Err(format!("unexpected argument {:?}", extra));
+    }
+
+    Ok(Some(Options {
+        input,
+        output,
+        k,
+        format,
+        stats,
+        verify,
+    }))
+}
+
+/// Maps the network and writes it out as the options describe.
+fn map_and_write<Ni: 'static + label::LabelNodeIndex + std::fmt::Debug>(
+    network: FlowMapBooleanNetwork<Ni>,
+    options: &Options,
+) -> Result<(), String> {
+    let network = label::try_label_network(network, options.k)
+        .map_err(|error| format!("could not label the network: {:?}", error))?;
+    let luts = map::map(&network, options.k, &*network)
+        .map_err(|error| format!("could not map the network: {:?}", error))?;
+
+    if options.verify && !verify::equivalent(&network, &luts) {
+        return Err("the mapped network is not equivalent to the input".to_string());
+    }
+
+    if options.stats {
+        let stats = map::stats(&network, &luts);
+        eprintln!("LUTs: {}", stats.lut_count);
+        eprintln!("depth: {}", stats.depth);
+        for (num_inputs, count) in stats.inputs_histogram.iter().enumerate() {
+            if *count > 0 {
+                eprintln!("{}-input LUTs: {}", num_inputs, count);
+            }
+        }
+    }
+
+    let writer: Box<dyn io::Write> = match &options.output {
+        Some(path) => Box::new(
+            File::create(path).map_err(|error| format!("could not create {}: {}", path, error))?,
+        ),
+        None => Box::new(io::stdout()),
+    };
+
+    match options.format {
+        Format::Rtlil => backends::rtlil::write_rtlil(writer, &network, &luts),
+        Format::Blif => backends::blif::write_blif(writer, &network, &luts),
+        Format::Json => backends::json::write_json(writer, &network, &luts),
+        Format::Verilog => backends::verilog::write_verilog(writer, &network, &luts),
+    }
+    .map_err(|error| format!("could not write the mapped network: {}", error))
+}
+
+/// Reads the input file with the frontend for its extension, then maps and
+/// writes it.
+fn run(options: &Options) -> Result<(), String> {
+    let file = File::open(&options.input)
+        .map_err(|error| format!("could not open {}: {}", options.input, error))?;
+
+    if options.input.ends_with(".blif") {
+        let network = frontends::blif::from_reader(file)
+            .map_err(|error| format!("could not read {}: {:?}", options.input, error))?;
+        map_and_write(network, options)
+    } else {
+        let network = frontends::aiger::from_any(file)
+            .map_err(|error| format!("could not read {}: {:?}", options.input, error))?;
+        map_and_write(network, options)
+    }
+}
 
 fn main() {
-    let args = env::args().collect::<Vec<_>>();
-    let aiger_path = args
-        .get(1)
-        .expect("path to aiger file as first command line argument");
-    let rtlil_path = args
-        .get(2)
-        .expect("path to rtlil file as second command line argument");
-
-    let aiger_file = std::fs::File::open(aiger_path).unwrap();
-    let aiger_reader = aiger::Reader::from_reader(aiger_file).unwrap();
-    let mut network = frontends::aiger::from_reader(aiger_reader);
-
-    const K: u32 = 6;
-    flowmap::label::label_network(&mut network, K);
-    let luts = flowmap::map::map(&network, K);
-
-    let rtlil_file = std::fs::File::create(rtlil_path).unwrap();
-    backends::rtlil::write_rtlil(rtlil_file, &network, &luts, |lut| {
-        let f = frontends::aiger::evaluate_lut(&network, lut);
-
-        let num_bits = lut.inputs.len();
-        let max_input = (1 << num_bits) - 1;
-        (0..=max_input)
-            .map(|i| {
-                let bits = (0..num_bits)
-                    .rev()
-                    .map(|bit| i & (1 << bit) != 0)
-                    .collect::<Vec<_>>();
-
-                f(&bits)
-            })
-            .collect()
-    })
-    .unwrap();
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let options = match parse_args(&args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(error) => {
+            eprintln!("error: {}\n\n{}", error, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(error) = run(&options) {
+        eprintln!("error: {}", error);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Options>, String> {
+        parse_args(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn parse_args_defaults() {
+        assert_eq!(
+            parse(&["in.aag"]),
+            Ok(Some(Options {
+                input: "in.aag".to_string(),
+                output: None,
+                k: 6,
+                format: Format::Rtlil,
+                stats: false,
+                verify: false,
+            }))
+        );
+    }
+
+    #[test]
+    fn parse_args_options() {
+        assert_eq!(
+            parse(&[
+                "-k", "4", "--stats", "--verify", "in.blif", "--format", "verilog", "-o", "out.v"
+            ]),
+            Ok(Some(Options {
+                input: "in.blif".to_string(),
+                output: Some("out.v".to_string()),
+                k: 4,
+                format: Format::Verilog,
+                stats: true,
+                verify: true,
+            }))
+        );
+        assert_eq!(
+            parse(&["in.aag", "out.il"]).map(|options| options.unwrap().output),
+            Ok(Some("out.il".to_string()))
+        );
+        assert_eq!(parse(&["in.aag", "--help"]), Ok(None));
+    }
+
+    #[test]
+    fn parse_args_errors() {
+        assert_eq!(parse(&[]), Err("no input file given".to_string()));
+        assert_eq!(parse(&["-k"]), Err("-k requires a value".to_string()));
+        assert_eq!(
+            parse(&["-k", "0", "in.aag"]),
+            Err("invalid LUT size \"0\"".to_string())
+        );
+        assert_eq!(
+            parse(&["--format", "edif", "in.aag"]),
+            Err("unknown format \"edif\"".to_string())
+        );
+        assert_eq!(
+            parse(&["--verbose", "in.aag"]),
+            Err("unknown option \"--verbose\"".to_string())
+        );
+        assert_eq!(
+            parse(&["-o", "a.il", "in.aag", "b.il"]),
+            Err("the output file was given twice".to_string())
+        );
+        assert_eq!(
+            parse(&["in.aag", "a.il", "b.il"]),
+            Err("unexpected argument \"b.il\"".to_string())
+        );
+    }
 }
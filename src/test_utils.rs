@@ -22,3 +22,43 @@ macro_rules! assert_equiv {
         }
     };
 }
+
+/// Reads an AIGER file, then labels and maps it for LUTs of size `k`.
+#[cfg(test)]
+pub(crate) fn map_aiger(
+    aiger: &str,
+    k: impl Into<crate::flowmap::KSpec>,
+) -> (
+    crate::flowmap::label::LabeledNetwork<::aiger::Literal>,
+    Vec<crate::flowmap::map::LUT<::aiger::Literal>>,
+) {
+    use crate::flowmap::{label, map};
+
+    let network = crate::frontends::aiger::from_any(aiger.as_bytes()).unwrap();
+
+    let k = k.into();
+    let network = label::label_network(network, k.clone());
+    let luts = map::map(&network, k, &*network).unwrap();
+
+    (network, luts)
+}
+
+/// Runs the full flow on an AIGER file, writing the mapped network with the
+/// provided backend and returning its output.
+#[cfg(test)]
+pub(crate) fn write_mapped_aiger(
+    aiger: &str,
+    k: impl Into<crate::flowmap::KSpec>,
+    write: impl Fn(
+        &mut Vec<u8>,
+        &crate::frontends::aiger::AIG,
+        &[crate::flowmap::map::LUT<::aiger::Literal>],
+    ) -> std::io::Result<()>,
+) -> String {
+    let (network, luts) = map_aiger(aiger, k);
+
+    let mut output = vec![];
+    write(&mut output, &network, &luts).unwrap();
+
+    String::from_utf8(output).unwrap()
+}
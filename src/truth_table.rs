@@ -0,0 +1,189 @@
+//! The truth table of the function implemented by a LUT.
+
+/// The output of a function for every combination of its inputs.
+///
+/// Entry `i` of the truth table is the output of the function when the inputs
+/// take the values of the bits of `i`, with the first input being the most
+/// significant bit. This is the only bit order used by the crate; backends
+/// which need a different order convert from it, as `to_lut_string` does.
+#[derive(Debug, Eq, PartialEq, Clone, Hash)]
+pub struct TruthTable {
+    num_inputs: usize,
+    /// The entries of the truth table, packed 64 to a word with entry 0 as the
+    /// least significant bit of the first word.
+    bits: Vec<u64>,
+}
+
+impl TruthTable {
+    /// Builds the truth table of a function of `num_inputs` inputs, calling
+    /// `f` with the values of the inputs for every entry.
+    pub fn from_fn(num_inputs: usize, f: impl Fn(&[bool]) -> bool) -> TruthTable {
+        let num_entries = 1usize << num_inputs;
+        // The number of entries is a power of two, so it's either less than a
+        // word or a whole number of words
+        let mut bits = vec![0; std::cmp::max(num_entries / 64, 1)];
+
+        for i in 0..num_entries {
+            let values = (0..num_inputs)
+                .rev()
+                .map(|bit| i & (1 << bit) != 0)
+                .collect::<Vec<_>>();
+
+            if f(&values) {
+                bits[i / 64] |= 1 << (i % 64);
+            }
+        }
+
+        TruthTable { num_inputs, bits }
+    }
+
+    /// Builds a truth table from its entries.
+    ///
+    /// Panics if the number of entries isn't a power of two.
+    pub fn from_entries(entries: &[bool]) -> TruthTable {
+        assert!(
+            entries.len().is_power_of_two(),
+            "a truth table should have a power of two entries, but {} were provided",
+            entries.len()
+        );
+
+        let num_inputs = entries.len().trailing_zeros() as usize;
+        TruthTable::from_fn(num_inputs, |values| {
+            let index = values
+                .iter()
+                .fold(0, |index, value| (index << 1) | *value as usize);
+
+            entries[index]
+        })
+    }
+
+    /// Returns the number of inputs to the function.
+    pub fn num_inputs(&self) -> usize {
+        self.num_inputs
+    }
+
+    /// Returns entry `index` of the truth table.
+    ///
+    /// Panics if there is no such entry.
+    pub fn bit(&self, index: usize) -> bool {
+        assert!(
+            index < 1 << self.num_inputs,
+            "entry {} is outside a truth table of {} inputs",
+            index,
+            self.num_inputs
+        );
+
+        self.bits[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    /// Returns an iterator over the entries of the truth table, in order.
+    pub fn entries(&self) -> impl Iterator<Item = bool> + '_ {
+        (0..1 << self.num_inputs).map(move |i| self.bit(i))
+    }
+
+    /// Returns the output of the function for the provided input values.
+    ///
+    /// Panics if a value isn't provided for every input.
+    pub fn evaluate(&self, values: &[bool]) -> bool {
+        assert_eq!(
+            values.len(),
+            self.num_inputs,
+            "a value should be provided for every input"
+        );
+
+        let index = values
+            .iter()
+            .fold(0, |index, value| (index << 1) | *value as usize);
+
+        self.bit(index)
+    }
+
+    /// Returns the truth table as the value of the `LUT` parameter of a Yosys
+    /// `$lut` cell with `k` inputs, most significant entry first.
+    ///
+    /// Unused inputs are the most significant bits of the cell's input and are
+    /// tied to 0, so the truth table is repeated for every combination of them.
+    ///
+    /// Panics if `k` is less than the number of inputs to the function.
+    pub fn to_lut_string(&self, k: usize) -> String {
+        assert!(
+            k >= self.num_inputs,
+            "a truth table of {} inputs doesn't fit in a {}-input LUT",
+            self.num_inputs,
+            k
+        );
+
+        let num_entries = 1 << self.num_inputs;
+        (0..1 << k)
+            .rev()
+            .map(|i| if self.bit(i % num_entries) { '1' } else { '0' })
+            .collect()
+    }
+}
+
+impl Default for TruthTable {
+    /// Returns the truth table of the constant false function, which has no
+    /// inputs.
+    fn default() -> Self {
+        TruthTable::from_fn(0, |_| false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_fn_order() {
+        // The first input is the most significant bit of the entry's index
+        let truth_table = TruthTable::from_fn(2, |values| values[0] && !values[1]);
+
+        assert_eq!(truth_table.num_inputs(), 2);
+        assert_eq!(
+            truth_table.entries().collect::<Vec<_>>(),
+            vec![false, false, true, false]
+        );
+        assert_eq!(truth_table.evaluate(&[true, false]), true);
+        assert_eq!(truth_table.evaluate(&[false, true]), false);
+    }
+
+    #[test]
+    fn from_entries_round_trip() {
+        let entries = [false, true, true, true, false, false, true, false];
+        let truth_table = TruthTable::from_entries(&entries);
+
+        assert_eq!(truth_table.num_inputs(), 3);
+        assert_eq!(truth_table.entries().collect::<Vec<_>>(), entries);
+    }
+
+    #[test]
+    #[should_panic(expected = "a truth table should have a power of two entries")]
+    fn from_entries_not_power_of_two() {
+        TruthTable::from_entries(&[false, true, false]);
+    }
+
+    #[test]
+    fn more_than_six_inputs() {
+        let truth_table = TruthTable::from_fn(8, |values| values.iter().all(|value| *value));
+
+        assert_eq!(truth_table.bit(255), true);
+        assert_eq!(truth_table.bit(254), false);
+        assert_eq!(truth_table.bit(63), false);
+        assert_eq!(truth_table.entries().filter(|bit| *bit).count(), 1);
+    }
+
+    #[test]
+    fn to_lut_string_test() {
+        let and = TruthTable::from_entries(&[false, false, false, true]);
+
+        assert_eq!(and.to_lut_string(2), "1000");
+        assert_eq!(and.to_lut_string(4), "1000100010001000");
+        assert_eq!(TruthTable::default().to_lut_string(1), "00");
+    }
+
+    #[test]
+    #[should_panic(expected = "doesn't fit in a 1-input LUT")]
+    fn to_lut_string_too_small() {
+        TruthTable::from_entries(&[false, false, false, true]).to_lut_string(1);
+    }
+}
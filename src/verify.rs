@@ -0,0 +1,267 @@
+//! Checking that a mapped network implements the logic of the original network.
+
+use crate::boolean_network::NodeIndex;
+use crate::evaluate::evaluate_from_table;
+use crate::flowmap::label::TopologicalOrder;
+use crate::flowmap::map::LUT;
+use crate::flowmap::*;
+use hashbrown::{HashMap, HashSet};
+
+/// Networks with at most this many PIs are checked with every assignment of
+/// their PIs.
+const EXHAUSTIVE_MAX_PIS: usize = 16;
+
+/// The number of assignments checked for networks with more PIs.
+const SAMPLED_ASSIGNMENTS: usize = 4096;
+
+/// Returns `true` if the mapped network gives the same value as the original
+/// network for every PO and latch next state.
+///
+/// The original network is evaluated with the logic of each node, and the
+/// mapped network with the truth table of each LUT, since that's what the
+/// backends write. Latch outputs are PIs of both, so only the combinational
+/// logic is compared. Networks with at most 16 PIs are checked with every
+/// assignment of their PIs, and larger networks with a fixed set of
+/// pseudo-random assignments, so the same mapping always gives the same
+/// result. A mapping which is missing the LUT for a node it uses is never
+/// equivalent.
+pub fn equivalent<Ni: 'static + NodeIndex>(
+    network: &FlowMapBooleanNetwork<Ni>,
+    luts: &[LUT<Ni>],
+) -> bool {
+    let pis = network
+        .nodes()
+        .filter(|ni| {
+            let node_value = network.node_value(*ni);
+            node_value.is_pi && !node_value.is_constant
+        })
+        .collect::<Vec<_>>();
+    let roots = network.mapping_roots();
+
+    let mut order = vec![];
+    let mut topo = TopologicalOrder::new(network);
+    while let Some(ni) = topo.next(network) {
+        order.push(ni);
+    }
+
+    let lut_order = match lut_order(network, luts, &roots) {
+        Some(lut_order) => lut_order,
+        None => return false,
+    };
+
+    let matches = |pi_values: &[bool]| {
+        let mut expected = vec![None; network.node_count()];
+        for ni in network.nodes() {
+            if network.node_value(ni).is_constant {
+                expected[ni.node_index()] = Some(false);
+            }
+        }
+        for (pi, value) in pis.iter().zip(pi_values) {
+            expected[pi.node_index()] = Some(*value);
+        }
+
+        // Both networks share their PIs, so start from the same values
+        let mut actual = expected.clone();
+
+        for ni in &order {
+            if expected[ni.node_index()].is_some() {
+                continue;
+            }
+
+            if let Some(logic) = &network.node_value(*ni).logic {
+                let inputs = network
+                    .ancestors(*ni)
+                    .iter()
+                    .map(|ancestor| expected[ancestor.node_index()])
+                    .collect::<Option<Vec<_>>>();
+                expected[ni.node_index()] = inputs.map(|inputs| logic.evaluate(&inputs));
+            }
+        }
+
+        for lut in &lut_order {
+            let inputs = lut
+                .inputs
+                .iter()
+                .map(|ni| actual[ni.node_index()])
+                .collect::<Option<Vec<_>>>();
+            actual[lut.output.node_index()] =
+                inputs.map(|inputs| evaluate_from_table(&lut.truth_table, &lut.inputs)(&inputs));
+        }
+
+        roots.iter().all(|root| {
+            let expected = expected[root.node_index()];
+            expected.is_some() && expected == actual[root.node_index()]
+        })
+    };
+
+    if pis.len() <= EXHAUSTIVE_MAX_PIS {
+        (0..1usize << pis.len()).all(|row| {
+            let pi_values = (0..pis.len())
+                .map(|i| (row >> i) & 1 == 1)
+                .collect::<Vec<_>>();
+
+            matches(&pi_values)
+        })
+    } else {
+        // A xorshift generator with a fixed seed, which is plenty to spread
+        // the assignments over the PIs
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next_bit = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+
+            state >> 63 == 1
+        };
+
+        (0..SAMPLED_ASSIGNMENTS).all(|_| {
+            let pi_values = pis.iter().map(|_| next_bit()).collect::<Vec<_>>();
+
+            matches(&pi_values)
+        })
+    }
+}
+
+/// Returns the LUTs needed to generate the roots, with each LUT after the LUTs
+/// generating its inputs, or `None` if a node other than a PI which is needed
+/// has no LUT.
+fn lut_order<'a, Ni: 'static + NodeIndex>(
+    network: &FlowMapBooleanNetwork<Ni>,
+    luts: &'a [LUT<Ni>],
+    roots: &[Ni],
+) -> Option<Vec<&'a LUT<Ni>>> {
+    let lut_for = luts
+        .iter()
+        .map(|lut| (lut.output, lut))
+        .collect::<HashMap<_, _>>();
+
+    let mut order = vec![];
+    let mut visited = HashSet::new();
+    let mut s = roots.iter().map(|root| (*root, false)).collect::<Vec<_>>();
+    while let Some((ni, inputs_done)) = s.pop() {
+        if network.node_value(ni).is_pi {
+            continue;
+        }
+
+        let lut = lut_for.get(&ni)?;
+        if inputs_done {
+            order.push(*lut);
+        } else if visited.insert(ni) {
+            s.push((ni, true));
+            s.extend(lut.inputs.iter().map(|input| (*input, false)));
+        }
+    }
+
+    Some(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flowmap::builder::NetworkBuilder;
+    use crate::flowmap::{label, map};
+    use crate::truth_table::TruthTable;
+
+    fn map_network(
+        network: FlowMapBooleanNetwork<usize>,
+        k: u32,
+    ) -> (label::LabeledNetwork<usize>, Vec<LUT<usize>>) {
+        let network = label::label_network(network, k);
+        let luts = map::map(&network, k, &*network).unwrap();
+
+        (network, luts)
+    }
+
+    /// Returns a network of a full adder, with the sum and carry out as POs.
+    fn get_full_adder() -> FlowMapBooleanNetwork<usize> {
+        // a XOR b is !(!(a & !b) & !(!a & b)), and the sum is 10 XOR c
+        NetworkBuilder::new()
+            .input(0)
+            .input(1)
+            .input(2)
+            .inverter(3, 0)
+            .inverter(4, 1)
+            .and(5, 0, 4)
+            .and(6, 3, 1)
+            .inverter(7, 5)
+            .inverter(8, 6)
+            .and(9, 7, 8)
+            .inverter(10, 9)
+            .inverter(11, 17)
+            .inverter(12, 2)
+            .and(13, 10, 12)
+            .and(14, 9, 2)
+            .inverter(15, 13)
+            .inverter(16, 14)
+            .and(17, 15, 16)
+            .and(18, 0, 1)
+            .and(19, 10, 2)
+            .inverter(20, 18)
+            .inverter(21, 19)
+            .and(22, 20, 21)
+            .inverter(23, 22)
+            .output(11)
+            .output(23)
+            .build()
+    }
+
+    #[test]
+    fn equivalent_full_adder() {
+        for k in 2..=4 {
+            let (network, luts) = map_network(get_full_adder(), k);
+
+            assert_eq!(equivalent(&network, &luts), true);
+        }
+    }
+
+    #[test]
+    fn equivalent_wrong_truth_table() {
+        let (network, mut luts) = map_network(get_full_adder(), 2);
+
+        // Inverting the carry out is wrong for every assignment
+        let lut = luts.iter_mut().find(|lut| lut.output == 23).unwrap();
+        let entries = lut
+            .truth_table
+            .entries()
+            .map(|entry| !entry)
+            .collect::<Vec<_>>();
+        lut.truth_table = TruthTable::from_entries(&entries);
+
+        assert_eq!(equivalent(&network, &luts), false);
+    }
+
+    #[test]
+    fn equivalent_missing_lut() {
+        let (network, mut luts) = map_network(get_full_adder(), 4);
+        luts.retain(|lut| lut.output != 11);
+
+        assert_eq!(equivalent(&network, &luts), false);
+    }
+
+    #[test]
+    fn equivalent_sampled() {
+        // An AND of 20 PIs is too wide to check exhaustively
+        let mut builder = NetworkBuilder::new();
+        for pi in 0..20 {
+            builder.input(pi);
+        }
+        builder.and(20, 0, 1);
+        for pi in 2..20 {
+            builder.and(pi + 19, pi + 18, pi);
+        }
+        builder.inverter(39, 38).output(39);
+
+        let (network, mut luts) = map_network(builder.build(), 4);
+        assert_eq!(equivalent(&network, &luts), true);
+
+        // Inverting the output is wrong for every assignment
+        let lut = luts.iter_mut().find(|lut| lut.output == 39).unwrap();
+        let entries = lut
+            .truth_table
+            .entries()
+            .map(|entry| !entry)
+            .collect::<Vec<_>>();
+        lut.truth_table = TruthTable::from_entries(&entries);
+        assert_eq!(equivalent(&network, &luts), false);
+    }
+}